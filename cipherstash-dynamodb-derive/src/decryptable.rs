@@ -1,6 +1,9 @@
-use crate::settings::Settings;
+use crate::{
+    nested::attributes_impl,
+    settings::{field_bounds, Settings},
+};
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::quote;
 use syn::DeriveInput;
 
 pub(crate) fn derive_decryptable(input: DeriveInput) -> Result<TokenStream, syn::Error> {
@@ -9,71 +12,200 @@ pub(crate) fn derive_decryptable(input: DeriveInput) -> Result<TokenStream, syn:
         .field_attributes(&input)?
         .build()?;
 
-    let protected_excluding_handlers = settings.protected_attributes_excluding_handlers();
     let plaintext_attributes = settings.plaintext_attributes();
 
-    let protected_attributes_cow = settings
-        .protected_attributes()
-        .into_iter()
-        .map(|x| quote! { std::borrow::Cow::Borrowed(#x) });
+    let nested_attributes = settings.nested_attributes();
 
-    let plaintext_attributes_cow = settings
-        .plaintext_attributes()
-        .into_iter()
-        .map(|x| quote! { std::borrow::Cow::Borrowed(#x) });
+    let protected_attributes_impl = attributes_impl(
+        &settings.protected_attributes(),
+        nested_attributes,
+        quote! { protected_roots },
+    );
+
+    let plaintext_attributes_impl = attributes_impl(
+        &plaintext_attributes,
+        nested_attributes,
+        quote! { plaintext_paths },
+    );
 
-    let skipped_attributes = settings.skipped_attributes();
     let ident = settings.ident();
 
-    let from_unsealed_impl = protected_excluding_handlers
-        .iter()
-        .map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+    let generics = settings.generics_with_bounds(
+        field_bounds(
+            settings.protected_attributes_excluding_handlers(),
+            quote! { cipherstash_dynamodb::traits::TryFromPlaintext },
+        )
+        .into_iter()
+        .chain(field_bounds(
+            plaintext_attributes.iter().copied(),
+            quote! { cipherstash_dynamodb::traits::TryFromTableAttr },
+        ))
+        .chain(field_bounds(
+            settings.skipped_attributes(),
+            quote! { Default },
+        ))
+        .chain(field_bounds(
+            settings.default_trait_attributes(),
+            quote! { Default },
+        ))
+        .chain(field_bounds(
+            nested_attributes.iter().map(|nested| nested.name.as_str()),
+            quote! { cipherstash_dynamodb::traits::Nested },
+        )),
+        None,
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let from_unsealed_impl = if settings.is_enum() {
+        let tag = &settings.tag;
 
+        let take_tag = if settings.plaintext_tag {
             quote! {
-                #attr_ident: ::cipherstash_dynamodb::traits::TryFromPlaintext::try_from_optional_plaintext(unsealed.take_protected(#attr))?
+                <String as ::cipherstash_dynamodb::traits::TryFromTableAttr>::try_from_table_attr(unsealed.take_unprotected(#tag))?
             }
-        })
-        .chain(plaintext_attributes.iter().map(|attr| {
-            let attr_ident = format_ident!("{attr}");
-
+        } else {
             quote! {
-                #attr_ident: ::cipherstash_dynamodb::traits::TryFromTableAttr::try_from_table_attr(unsealed.take_unprotected(#attr))?
+                <String as ::cipherstash_dynamodb::traits::TryFromPlaintext>::try_from_optional_plaintext(unsealed.take_protected(#tag))?
             }
-        }))
-        .chain(skipped_attributes.iter().map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+        };
+
+        let arms = settings.variants().iter().map(|variant| {
+            let variant_ident = &variant.ident;
+            let variant_name = variant_ident.to_string();
+            let fields = from_unsealed_fields(&variant.settings);
 
             quote! {
-                #attr_ident: Default::default()
+                #variant_name => Ok(Self::#variant_ident {
+                    #(#fields,)*
+                }),
             }
-        }))
-        .chain(settings.decrypt_handlers().iter().map(|(attr, handler)| {
-            let attr_ident = format_ident!("{attr}");
+        });
 
-            quote! {
-                #attr_ident: #handler(&mut unsealed)?
+        quote! {
+            let tag: String = #take_tag;
+
+            match tag.as_str() {
+                #(#arms)*
+                _ => Err(cipherstash_dynamodb::traits::ReadConversionError::UnknownVariant(tag).into()),
             }
-        }));
+        }
+    } else {
+        let fields = from_unsealed_fields(&settings);
+
+        quote! {
+            Ok(Self {
+                #(#fields,)*
+            })
+        }
+    };
+
+    let unknown_attributes_impl = if settings.deny_unknown_attributes {
+        quote! { cipherstash_dynamodb::traits::UnknownAttributes::Deny }
+    } else {
+        quote! { cipherstash_dynamodb::traits::UnknownAttributes::Ignore }
+    };
+
+    let record_mac_impl = if settings.required_record_mac {
+        quote! { cipherstash_dynamodb::traits::RecordMac::Required }
+    } else {
+        quote! { cipherstash_dynamodb::traits::RecordMac::Optional }
+    };
+
+    let version = settings.version;
+
+    let upcast_impl = if settings.upcast {
+        let legacy_protected_attributes = &settings.legacy_protected_attributes;
+
+        quote! {
+            fn legacy_protected_attributes() -> std::borrow::Cow<'static, [std::borrow::Cow<'static, str>]> {
+                std::borrow::Cow::Borrowed(&[#(std::borrow::Cow::Borrowed(#legacy_protected_attributes),)*])
+            }
+
+            fn upcast_from(version: u32, unsealed: cipherstash_dynamodb::crypto::Unsealed) -> Result<cipherstash_dynamodb::crypto::Unsealed, cipherstash_dynamodb::crypto::SealError> {
+                <Self as cipherstash_dynamodb::traits::Upcast>::upcast(version, unsealed)
+            }
+        }
+    } else {
+        quote! {}
+    };
 
     let expanded = quote! {
         #[automatically_derived]
-        impl cipherstash_dynamodb::traits::Decryptable for #ident {
+        impl #impl_generics cipherstash_dynamodb::traits::Decryptable for #ident #ty_generics #where_clause {
             fn protected_attributes() -> std::borrow::Cow<'static, [std::borrow::Cow<'static, str>]> {
-                std::borrow::Cow::Borrowed(&[#(#protected_attributes_cow,)*])
+                #protected_attributes_impl
             }
 
             fn plaintext_attributes() -> std::borrow::Cow<'static, [std::borrow::Cow<'static, str>]> {
-                std::borrow::Cow::Borrowed(&[#(#plaintext_attributes_cow,)*])
+                #plaintext_attributes_impl
             }
 
+            fn unknown_attributes() -> cipherstash_dynamodb::traits::UnknownAttributes {
+                #unknown_attributes_impl
+            }
+
+            fn record_mac() -> cipherstash_dynamodb::traits::RecordMac {
+                #record_mac_impl
+            }
+
+            fn schema_version() -> u32 {
+                #version
+            }
+
+            #upcast_impl
+
             fn from_unsealed(mut unsealed: cipherstash_dynamodb::crypto::Unsealed) -> Result<Self, cipherstash_dynamodb::crypto::SealError> {
-                Ok(Self {
-                    #(#from_unsealed_impl,)*
-                })
+                #from_unsealed_impl
             }
         }
     };
 
     Ok(expanded)
 }
+
+/// Returns the field initializers that take the fields of a record from `unsealed`.
+fn from_unsealed_fields(settings: &Settings) -> Vec<TokenStream> {
+    settings
+        .protected_attributes_excluding_handlers()
+        .iter()
+        .map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+            let value = settings.read_protected(attr, quote! { unsealed.take_protected(#attr) });
+
+            quote! {
+                #attr_ident: #value
+            }
+        })
+        .chain(settings.plaintext_attributes().iter().map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+            let value = settings.read_plaintext(attr, quote! { unsealed.take_unprotected(#attr) });
+
+            quote! {
+                #attr_ident: #value
+            }
+        }))
+        .chain(settings.skipped_attributes().iter().map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+
+            quote! {
+                #attr_ident: Default::default()
+            }
+        }))
+        .chain(settings.decrypt_handlers().iter().map(|(attr, handler)| {
+            let attr_ident = settings.field_ident(attr);
+
+            quote! {
+                #attr_ident: #handler(&mut unsealed)?
+            }
+        }))
+        .chain(settings.nested_attributes().iter().map(|nested| {
+            let attr_ident = settings.field_ident(&nested.name);
+            let ty = &nested.ty;
+            let prefix = nested.prefix();
+
+            quote! {
+                #attr_ident: <#ty as cipherstash_dynamodb::traits::Nested>::read_unsealed(#prefix, &mut unsealed)?
+            }
+        }))
+        .collect()
+}
@@ -1,6 +1,9 @@
-use crate::settings::Settings;
+use crate::{
+    nested::attributes_impl,
+    settings::{field_bounds, FieldAccess, Settings},
+};
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::quote;
 use syn::DeriveInput;
 
 pub(crate) fn derive_encryptable(input: DeriveInput) -> Result<TokenStream, syn::Error> {
@@ -9,61 +12,98 @@ pub(crate) fn derive_encryptable(input: DeriveInput) -> Result<TokenStream, syn:
         .field_attributes(&input)?
         .build()?;
 
-    let protected_excluding_handlers = settings.protected_attributes_excluding_handlers();
     let plaintext_attributes = settings.plaintext_attributes();
 
-    let protected_attributes_cow = settings
-        .protected_attributes()
-        .into_iter()
-        .map(|x| quote! { std::borrow::Cow::Borrowed(#x) });
+    let nested_attributes = settings.nested_attributes();
 
-    let plaintext_attributes_cow = settings
-        .plaintext_attributes()
-        .into_iter()
-        .map(|x| quote! { std::borrow::Cow::Borrowed(#x) });
+    let protected_attributes_impl = attributes_impl(
+        &settings.protected_attributes(),
+        nested_attributes,
+        quote! { protected_roots },
+    );
+
+    let plaintext_attributes_impl = attributes_impl(
+        &plaintext_attributes,
+        nested_attributes,
+        quote! { plaintext_paths },
+    );
 
     let ident = settings.ident();
 
-    let into_unsealed_impl = protected_excluding_handlers
-        .iter()
-        .map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+    let generics = settings.generics_with_bounds(
+        field_bounds(
+            settings.protected_attributes_excluding_handlers(),
+            quote! { Into<cipherstash_dynamodb::traits::Plaintext> },
+        )
+        .into_iter()
+        .chain(field_bounds(
+            plaintext_attributes.iter().copied(),
+            quote! { Into<cipherstash_dynamodb::traits::TableAttribute> },
+        ))
+        .chain(field_bounds(
+            nested_attributes.iter().map(|nested| nested.name.as_str()),
+            quote! { cipherstash_dynamodb::traits::Nested },
+        )),
+        Some(quote! { std::fmt::Debug + cipherstash_dynamodb::traits::Identifiable }),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-            quote! {
-                unsealed.add_protected(#attr, self.#attr_ident);
-            }
-        })
-        .chain(plaintext_attributes.iter().map(|attr| {
-            let attr_ident = format_ident!("{attr}");
+    let into_unsealed_impl = if settings.is_enum() {
+        let tag = &settings.tag;
+
+        let add_tag = if settings.plaintext_tag {
+            quote! { unsealed.add_unprotected(#tag, tag); }
+        } else {
+            quote! { unsealed.add_protected(#tag, tag); }
+        };
+
+        let arms = settings.variants().iter().map(|variant| {
+            let variant_ident = &variant.ident;
+            let variant_name = variant_ident.to_string();
+            let fields = variant.settings.stored_fields();
+            let statements = into_unsealed_statements(&variant.settings, FieldAccess::Binding);
 
             quote! {
-                unsealed.add_unprotected(#attr, self.#attr_ident);
+                Self::#variant_ident { #(#fields,)* .. } => {
+                    let tag = #variant_name;
+                    #add_tag
+                    #(#statements)*
+                }
             }
-        }))
-        .chain(settings.encrypt_handlers().iter().map(|(attr, handler)| {
-            let attr_ident = format_ident!("{attr}");
+        });
 
-            quote! {
-                #handler(&mut unsealed, self.#attr_ident);
+        quote! {
+            match self {
+                #(#arms)*
             }
-        }));
+        }
+    } else {
+        let statements = into_unsealed_statements(&settings, FieldAccess::SelfField);
+        quote! { #(#statements)* }
+    };
+
+    let version = settings.version;
 
     let expanded = quote! {
         #[automatically_derived]
-        impl cipherstash_dynamodb::traits::Encryptable for #ident {
+        impl #impl_generics cipherstash_dynamodb::traits::Encryptable for #ident #ty_generics #where_clause {
             fn protected_attributes() -> std::borrow::Cow<'static, [std::borrow::Cow<'static, str>]> {
-                std::borrow::Cow::Borrowed(&[#(#protected_attributes_cow,)*])
+                #protected_attributes_impl
             }
 
             fn plaintext_attributes() -> std::borrow::Cow<'static, [std::borrow::Cow<'static, str>]> {
-                std::borrow::Cow::Borrowed(&[#(#plaintext_attributes_cow,)*])
+                #plaintext_attributes_impl
+            }
+
+            fn schema_version() -> u32 {
+                #version
             }
 
             #[allow(clippy::needless_question_mark)]
             fn into_unsealed(self) -> cipherstash_dynamodb::crypto::Unsealed {
                 let mut unsealed = cipherstash_dynamodb::crypto::Unsealed::new_with_descriptor(<Self as cipherstash_dynamodb::traits::Identifiable>::type_name());
 
-                #(#into_unsealed_impl)*
+                #into_unsealed_impl
 
                 unsealed
             }
@@ -72,3 +112,40 @@ pub(crate) fn derive_encryptable(input: DeriveInput) -> Result<TokenStream, syn:
 
     Ok(expanded)
 }
+
+/// Returns the statements that add the fields of a record to `unsealed`.
+fn into_unsealed_statements(settings: &Settings, access: FieldAccess) -> Vec<TokenStream> {
+    settings
+        .protected_attributes_excluding_handlers()
+        .iter()
+        .map(|attr| {
+            let attr_value = access.value(&settings.field_ident(attr));
+
+            quote! {
+                unsealed.add_protected(#attr, #attr_value);
+            }
+        })
+        .chain(settings.plaintext_attributes().iter().map(|attr| {
+            let attr_value = access.value(&settings.field_ident(attr));
+
+            quote! {
+                unsealed.add_unprotected(#attr, #attr_value);
+            }
+        }))
+        .chain(settings.encrypt_handlers().iter().map(|(attr, handler)| {
+            let attr_value = access.value(&settings.field_ident(attr));
+
+            quote! {
+                #handler(&mut unsealed, #attr_value);
+            }
+        }))
+        .chain(settings.nested_attributes().iter().map(|nested| {
+            let attr_value = access.value(&settings.field_ident(&nested.name));
+            let prefix = nested.prefix();
+
+            quote! {
+                cipherstash_dynamodb::traits::Nested::write_unsealed(#attr_value, #prefix, &mut unsealed);
+            }
+        }))
+        .collect()
+}
@@ -1,6 +1,7 @@
-use crate::settings::Settings;
-use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use crate::settings::{field_bounds, FieldAccess, Settings};
+use itertools::Itertools;
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
 use syn::DeriveInput;
 
 pub(crate) fn derive_identifiable(input: DeriveInput) -> Result<TokenStream, syn::Error> {
@@ -9,50 +10,97 @@ pub(crate) fn derive_identifiable(input: DeriveInput) -> Result<TokenStream, syn
         .field_attributes(&input)?
         .build()?;
 
-    let Some(partition_key_field) = settings.get_partition_key() else {
+    if settings.get_partition_key().is_none() {
         return Err(syn::Error::new(
             proc_macro2::Span::call_site(),
             "Missing required attribute for Identifiable: #[partition_key]",
         ));
-    };
-
-    let partition_key_attr = format_ident!("{partition_key_field}");
+    }
 
     let protected_attributes = settings.protected_attributes();
     let ident = settings.ident();
 
-    let is_partition_key_encrypted = protected_attributes.contains(&partition_key_field.as_str());
+    let partition_key_fields = settings.partition_key_fields();
+    let sort_key_fields = settings.sort_key_fields();
+
+    let generics = settings.generics_with_bounds(
+        field_bounds(
+            partition_key_fields.iter().chain(&sort_key_fields).copied(),
+            if settings.typed_keys {
+                quote! { cipherstash_dynamodb::traits::KeyPart + ::core::clone::Clone }
+            } else {
+                quote! { ToString }
+            },
+        ),
+        None,
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // A composite key is encrypted if any of its fields are
+    let is_partition_key_encrypted = partition_key_fields
+        .iter()
+        .any(|field| protected_attributes.contains(field));
+
+    let is_sort_key_encrypted = sort_key_fields.is_empty()
+        || sort_key_fields
+            .iter()
+            .any(|field| protected_attributes.contains(field));
+
+    let primary_key_type = if settings.typed_keys {
+        // Every variant of an enum must use the same key types so the first one found is used.
+        // Composite keys are tuples of the types of their fields.
+        let key_type = |fields: &[&str]| {
+            let types = fields.iter().map(|field| settings.field_type(field));
+
+            match fields {
+                [_] => quote! { #(#types)* },
+                _ => quote! { ( #(#types),* ) },
+            }
+        };
+
+        let partition_key_type = key_type(&partition_key_fields);
 
-    let is_sort_key_encrypted = settings
-        .sort_key_field
-        .as_ref()
-        .map(|x| protected_attributes.contains(&x.as_str()))
-        .unwrap_or(true);
+        if sort_key_fields.is_empty() {
+            quote! { cipherstash_dynamodb::Pk<#partition_key_type> }
+        } else {
+            let sort_key_type = key_type(&sort_key_fields);
+            quote! { cipherstash_dynamodb::PkSk<#partition_key_type, #sort_key_type> }
+        }
+    } else if settings.sort_key_field.is_some() {
+        quote! { cipherstash_dynamodb::PkSk }
+    } else {
+        quote! { cipherstash_dynamodb::Pk }
+    };
 
-    let primary_key_impl = if let Some(sort_key_field) = &settings.sort_key_field {
-        let sort_key_attr = format_ident!("{sort_key_field}");
+    let get_primary_key_impl = if settings.is_enum() {
+        // Enum records bind the keys of each variant in a match arm
+        let arms = settings.variants().iter().map(|variant| {
+            let variant_ident = &variant.ident;
+            let key_fields = key_fields(&variant.settings);
+            let primary_key = primary_key(&variant.settings, FieldAccess::Binding);
 
-        quote! {
-            type PrimaryKey = cipherstash_dynamodb::PkSk;
+            quote! {
+                Self::#variant_ident { #(#key_fields,)* .. } => #primary_key,
+            }
+        });
 
-            fn get_primary_key(&self) -> Self::PrimaryKey {
-                cipherstash_dynamodb::PkSk(
-                    self.#partition_key_attr.to_string(),
-                    self.#sort_key_attr.to_string()
-                )
+        quote! {
+            match self {
+                #(#arms)*
             }
         }
     } else {
-        quote! {
-            type PrimaryKey = cipherstash_dynamodb::Pk;
+        primary_key(&settings, FieldAccess::SelfField)
+    };
 
-            fn get_primary_key(&self) -> Self::PrimaryKey {
-                cipherstash_dynamodb::Pk(
-                    self.#partition_key_attr.to_string()
-                )
-            }
+    let primary_key_impl = quote! {
+        type PrimaryKey = #primary_key_type;
+
+        fn get_primary_key(&self) -> Self::PrimaryKey {
+            #get_primary_key_impl
         }
     };
+
     let type_name = &settings.type_name;
 
     let sort_key_prefix_impl = if let Some(prefix) = &settings.sort_key_prefix {
@@ -61,8 +109,16 @@ pub(crate) fn derive_identifiable(input: DeriveInput) -> Result<TokenStream, syn
         quote! { None }
     };
 
+    let key_binding_impl = settings.key_binding.map(|key_binding| {
+        quote! {
+            fn key_binding() -> cipherstash_dynamodb::traits::KeyBinding {
+                #key_binding
+            }
+        }
+    });
+
     let expanded = quote! {
-        impl cipherstash_dynamodb::traits::Identifiable for #ident {
+        impl #impl_generics cipherstash_dynamodb::traits::Identifiable for #ident #ty_generics #where_clause {
             #primary_key_impl
 
             #[inline]
@@ -82,8 +138,67 @@ pub(crate) fn derive_identifiable(input: DeriveInput) -> Result<TokenStream, syn
             fn is_sk_encrypted() -> bool {
                 #is_sort_key_encrypted
             }
+
+            #key_binding_impl
         }
     };
 
     Ok(expanded)
 }
+
+/// Returns the fields that make up the primary key of a record.
+fn key_fields(settings: &Settings) -> Vec<Ident> {
+    settings
+        .partition_key_fields()
+        .into_iter()
+        .chain(settings.sort_key_fields())
+        .unique()
+        .map(|field| settings.field_ident(field))
+        .collect()
+}
+
+/// Returns an expression that builds the primary key of a record from its key fields.
+fn primary_key(settings: &Settings, access: FieldAccess) -> TokenStream {
+    let partition_key = key(settings, &settings.partition_key_fields(), access);
+    let sort_key_fields = settings.sort_key_fields();
+
+    if sort_key_fields.is_empty() {
+        quote! { cipherstash_dynamodb::Pk(#partition_key) }
+    } else {
+        let sort_key = key(settings, &sort_key_fields, access);
+        quote! { cipherstash_dynamodb::PkSk(#partition_key, #sort_key) }
+    }
+}
+
+/// Returns an expression for the value of a key made of `fields`.
+///
+/// Typed keys clone their fields, and composite typed keys are tuples. Other keys are converted
+/// to strings, and composite keys are encoded as a tuple of those strings so that a `#` in a field
+/// is escaped like it is in a typed key.
+fn key(settings: &Settings, fields: &[&str], access: FieldAccess) -> TokenStream {
+    let idents = fields.iter().map(|field| settings.field_ident(field));
+
+    if settings.typed_keys {
+        let values = idents.map(|ident| {
+            let reference = access.reference(&ident);
+            quote! { ::core::clone::Clone::clone(#reference) }
+        });
+
+        match fields {
+            [_] => quote! { #(#values)* },
+            _ => quote! { ( #(#values),* ) },
+        }
+    } else {
+        let values = idents.map(|ident| {
+            let value = access.value(&ident);
+            quote! { #value.to_string() }
+        });
+
+        match fields {
+            [_] => quote! { #(#values)* },
+            _ => quote! {
+                cipherstash_dynamodb::traits::KeyPart::to_key_part(&( #(#values),* ))
+            },
+        }
+    }
+}
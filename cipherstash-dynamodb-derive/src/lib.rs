@@ -5,6 +5,7 @@ extern crate syn;
 mod decryptable;
 mod encryptable;
 mod identifiable;
+mod nested;
 mod searchable;
 mod settings;
 
@@ -32,6 +33,13 @@ pub fn derive_decryptable(input: TokenStream) -> TokenStream {
         .into()
 }
 
+#[proc_macro_derive(Nested, attributes(cipherstash))]
+pub fn derive_nested(input: TokenStream) -> TokenStream {
+    nested::derive_nested(parse_macro_input!(input as DeriveInput))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
 #[proc_macro_derive(Searchable, attributes(cipherstash, sort_key, partition_key))]
 pub fn derive_searchable(input: TokenStream) -> TokenStream {
     searchable::derive_searchable(parse_macro_input!(input as DeriveInput))
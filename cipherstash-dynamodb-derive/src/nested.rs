@@ -0,0 +1,212 @@
+use crate::{
+    searchable::{index_bounds, index_impls, IndexImpls},
+    settings::{field_bounds, FieldAccess, NestedAttribute, Settings},
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::DeriveInput;
+
+pub(crate) fn derive_nested(input: DeriveInput) -> Result<TokenStream, syn::Error> {
+    let settings = Settings::builder(&input)
+        .container_attributes(&input)?
+        .field_attributes(&input)?
+        .build()?;
+
+    if settings.get_partition_key().is_some() || settings.sort_key_field.is_some() {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Nested types can't have a #[partition_key] or #[sort_key]",
+        ));
+    }
+
+    if settings.is_enum() {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Nested types must be structs",
+        ));
+    }
+
+    if !settings.encrypt_handlers().is_empty() || !settings.decrypt_handlers().is_empty() {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "Nested types don't support encryptable_with or decryptable_with",
+        ));
+    }
+
+    let protected_attributes = settings.protected_attributes();
+    let plaintext_attributes = settings.plaintext_attributes();
+    let skipped_attributes = settings.skipped_attributes();
+    let nested_attributes = settings.nested_attributes();
+    let ident = settings.ident();
+
+    let generics = settings.generics_with_bounds(
+        field_bounds(
+            protected_attributes.iter().copied(),
+            quote! { Into<cipherstash_dynamodb::traits::Plaintext> + cipherstash_dynamodb::traits::TryFromPlaintext },
+        )
+        .into_iter()
+        .chain(field_bounds(
+            plaintext_attributes.iter().copied(),
+            quote! { Into<cipherstash_dynamodb::traits::TableAttribute> + cipherstash_dynamodb::traits::TryFromTableAttr },
+        ))
+        .chain(field_bounds(skipped_attributes.iter().copied(), quote! { Default }))
+        .chain(field_bounds(settings.default_trait_attributes(), quote! { Default }))
+        .chain(index_bounds(&settings)),
+        None,
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let protected_attributes_impl = attributes_impl(
+        &protected_attributes,
+        nested_attributes,
+        quote! { protected_paths },
+    );
+
+    let plaintext_attributes_impl = attributes_impl(
+        &plaintext_attributes,
+        nested_attributes,
+        quote! { plaintext_paths },
+    );
+
+    let IndexImpls {
+        protected_indexes_impl,
+        index_by_name_impl,
+        attribute_for_index_impl,
+    } = index_impls(&settings, FieldAccess::SelfField)?;
+
+    let write_unsealed_impl = protected_attributes
+        .iter()
+        .map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+
+            quote! {
+                unsealed.add_protected_path(&cipherstash_dynamodb::traits::nested_path(prefix, #attr), self.#attr_ident);
+            }
+        })
+        .chain(plaintext_attributes.iter().map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+
+            quote! {
+                unsealed.add_unprotected(cipherstash_dynamodb::traits::nested_path(prefix, #attr).into_owned(), self.#attr_ident);
+            }
+        }))
+        .chain(nested_attributes.iter().map(|nested| {
+            let attr_ident = settings.field_ident(&nested.name);
+            let prefix = nested.prefix_within_nested();
+
+            quote! {
+                cipherstash_dynamodb::traits::Nested::write_unsealed(self.#attr_ident, #prefix, unsealed);
+            }
+        }));
+
+    let read_unsealed_impl = protected_attributes
+        .iter()
+        .map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+            let value = settings.read_protected(
+                attr,
+                quote! { unsealed.take_protected_path(&cipherstash_dynamodb::traits::nested_path(prefix, #attr)) },
+            );
+
+            quote! {
+                #attr_ident: #value
+            }
+        })
+        .chain(plaintext_attributes.iter().map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+            let value = settings.read_plaintext(
+                attr,
+                quote! { unsealed.take_unprotected(cipherstash_dynamodb::traits::nested_path(prefix, #attr).into_owned()) },
+            );
+
+            quote! {
+                #attr_ident: #value
+            }
+        }))
+        .chain(skipped_attributes.iter().map(|attr| {
+            let attr_ident = settings.field_ident(attr);
+
+            quote! {
+                #attr_ident: Default::default()
+            }
+        }))
+        .chain(nested_attributes.iter().map(|nested| {
+            let attr_ident = settings.field_ident(&nested.name);
+            let ty = &nested.ty;
+            let prefix = nested.prefix_within_nested();
+
+            quote! {
+                #attr_ident: <#ty as cipherstash_dynamodb::traits::Nested>::read_unsealed(#prefix, unsealed)?
+            }
+        }));
+
+    let expanded = quote! {
+        #[automatically_derived]
+        impl #impl_generics cipherstash_dynamodb::traits::Nested for #ident #ty_generics #where_clause {
+            fn protected_attributes() -> std::borrow::Cow<'static, [std::borrow::Cow<'static, str>]> {
+                #protected_attributes_impl
+            }
+
+            fn plaintext_attributes() -> std::borrow::Cow<'static, [std::borrow::Cow<'static, str>]> {
+                #plaintext_attributes_impl
+            }
+
+            fn protected_indexes() -> std::borrow::Cow<'static, [( std::borrow::Cow<'static, str>, cipherstash_dynamodb::IndexType )]> {
+                #protected_indexes_impl
+            }
+
+            fn index_by_name(index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> Option<Box<dyn cipherstash_dynamodb::traits::ComposableIndex + Send>> {
+                #index_by_name_impl
+            }
+
+            fn attribute_for_index(&self, index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> Option<cipherstash_dynamodb::traits::ComposablePlaintext> {
+                #attribute_for_index_impl
+            }
+
+            fn write_unsealed(self, prefix: Option<&str>, unsealed: &mut cipherstash_dynamodb::crypto::Unsealed) {
+                #(#write_unsealed_impl)*
+            }
+
+            fn read_unsealed(prefix: Option<&str>, unsealed: &mut cipherstash_dynamodb::crypto::Unsealed) -> Result<Self, cipherstash_dynamodb::crypto::SealError> {
+                Ok(Self {
+                    #(#read_unsealed_impl,)*
+                })
+            }
+        }
+    };
+
+    Ok(expanded)
+}
+
+/// Generates the body of a function that returns `attributes` followed by the attributes of
+/// each nested attribute as returned by the `Nested` function `nested_fn`.
+pub(crate) fn attributes_impl(
+    attributes: &[&str],
+    nested_attributes: &[NestedAttribute],
+    nested_fn: TokenStream,
+) -> TokenStream {
+    let attributes_cow = attributes
+        .iter()
+        .map(|x| quote! { std::borrow::Cow::Borrowed(#x) });
+
+    if nested_attributes.is_empty() {
+        return quote! {
+            std::borrow::Cow::Borrowed(&[#(#attributes_cow,)*])
+        };
+    }
+
+    let nested_impl = nested_attributes.iter().map(|nested| {
+        let ty = &nested.ty;
+        let prefix = nested.prefix();
+
+        quote! {
+            attributes.extend(<#ty as cipherstash_dynamodb::traits::Nested>::#nested_fn(#prefix));
+        }
+    });
+
+    quote! {
+        let mut attributes = vec![#(#attributes_cow,)*];
+        #(#nested_impl)*
+        std::borrow::Cow::Owned(attributes)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::settings::Settings;
+use crate::settings::{field_bounds, FieldAccess, Settings};
 use proc_macro2::TokenStream;
 use quote::quote;
 use syn::DeriveInput;
@@ -9,10 +9,144 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
         .field_attributes(&input)?
         .build()?;
 
-    let indexes = settings.indexes();
     let ident = settings.ident();
 
-    let protected_indexes_impl = indexes
+    let generics = settings.generics_with_bounds(
+        index_bounds(&settings),
+        Some(quote! { cipherstash_dynamodb::traits::Encryptable }),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let IndexImpls {
+        protected_indexes_impl,
+        index_by_name_impl,
+        attribute_for_index_impl,
+    } = index_impls(&settings, FieldAccess::SelfField)?;
+
+    let attribute_for_index_impl = if settings.is_enum() {
+        let arms = settings
+            .variants()
+            .iter()
+            .map(|variant| {
+                let variant_ident = &variant.ident;
+                let fields = variant.settings.indexed_fields();
+                let IndexImpls {
+                    attribute_for_index_impl,
+                    ..
+                } = index_impls(&variant.settings, FieldAccess::Binding)?;
+
+                Ok::<_, syn::Error>(quote! {
+                    Self::#variant_ident { #(#fields,)* .. } => {
+                        #attribute_for_index_impl
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        quote! {
+            match self {
+                #(#arms)*
+            }
+        }
+    } else {
+        attribute_for_index_impl
+    };
+
+    let partition_key_field_impl = settings.get_partition_key().map(|field| {
+        quote! {
+            schema.partition_key.field = Some(std::borrow::Cow::Borrowed(#field));
+        }
+    });
+
+    let sort_key_field_impl = settings.sort_key_field.as_ref().map(|field| {
+        quote! {
+            if let Some(sort_key) = schema.sort_key.as_mut() {
+                sort_key.field = Some(std::borrow::Cow::Borrowed(#field));
+            }
+        }
+    });
+
+    let skipped_attributes = settings.skipped_attributes();
+
+    let expanded = quote! {
+        #[automatically_derived]
+        impl #impl_generics cipherstash_dynamodb::traits::Searchable for #ident #ty_generics #where_clause {
+            fn protected_indexes() -> std::borrow::Cow<'static, [( std::borrow::Cow<'static, str>, cipherstash_dynamodb::IndexType )]> {
+                #protected_indexes_impl
+            }
+
+            fn index_by_name(index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> Option<Box<dyn cipherstash_dynamodb::traits::ComposableIndex + Send>> {
+                #index_by_name_impl
+            }
+
+            fn attribute_for_index(&self, index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> Option<cipherstash_dynamodb::traits::ComposablePlaintext> {
+                #attribute_for_index_impl
+            }
+
+            fn type_schema() -> cipherstash_dynamodb::traits::TypeSchema {
+                let mut schema = cipherstash_dynamodb::traits::TypeSchema::from_traits::<Self>();
+
+                #partition_key_field_impl
+                #sort_key_field_impl
+
+                schema.skipped = vec![#(std::borrow::Cow::Borrowed(#skipped_attributes),)*];
+                schema
+            }
+        }
+    };
+
+    Ok(expanded)
+}
+
+/// Returns the bounds needed by the index functions for the fields of generic types.
+pub(crate) fn index_bounds(settings: &Settings) -> Vec<(Vec<String>, TokenStream)> {
+    let indexes = settings.indexes();
+
+    // Each field is cloned on its own before the fields of an index are converted together
+    let clone_bounds = field_bounds(
+        indexes.iter().flat_map(|index| index.fields()),
+        quote! { Clone },
+    );
+
+    let plaintext_bounds = indexes.iter().map(|index| {
+        (
+            index.fields().into_iter().map(String::from).collect(),
+            quote! { TryInto<cipherstash_dynamodb::traits::ComposablePlaintext> },
+        )
+    });
+
+    let nested_bounds = field_bounds(
+        settings
+            .nested_attributes()
+            .iter()
+            .map(|nested| nested.name.as_str()),
+        quote! { cipherstash_dynamodb::traits::Nested },
+    );
+
+    clone_bounds
+        .into_iter()
+        .chain(plaintext_bounds)
+        .chain(nested_bounds)
+        .collect()
+}
+
+/// The bodies of the index functions shared by the `Searchable` and `Nested` derives.
+pub(crate) struct IndexImpls {
+    pub(crate) protected_indexes_impl: TokenStream,
+    pub(crate) index_by_name_impl: TokenStream,
+    pub(crate) attribute_for_index_impl: TokenStream,
+}
+
+/// Generates the index functions for the indexes of `settings`.
+/// Indexes of nested attributes are named by their path relative to the type.
+pub(crate) fn index_impls(
+    settings: &Settings,
+    access: FieldAccess,
+) -> Result<IndexImpls, syn::Error> {
+    let indexes = settings.indexes();
+    let nested_attributes = settings.nested_attributes();
+
+    let protected_indexes = indexes
         .iter()
         .map(|index| {
             let index_name = index.index_name();
@@ -41,7 +175,7 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
         .iter()
         .map(|index| {
             let index_name = index.index_name();
-            let field_access = index.to_compound_plaintext_access()?;
+            let field_access = index.to_compound_plaintext_access(settings, access)?;
             let index_type = index.to_cipherstash_dynamodb_type()?;
 
             Ok::<_, syn::Error>(quote! {
@@ -50,28 +184,74 @@ pub(crate) fn derive_searchable(input: DeriveInput) -> Result<TokenStream, syn::
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    let expanded = quote! {
-        #[automatically_derived]
-        impl cipherstash_dynamodb::traits::Searchable for #ident {
-            fn protected_indexes() -> std::borrow::Cow<'static, [( std::borrow::Cow<'static, str>, cipherstash_dynamodb::IndexType )]> {
-                std::borrow::Cow::Borrowed(&[#(#protected_indexes_impl,)*])
-            }
-
-            fn index_by_name(index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> Option<Box<dyn cipherstash_dynamodb::traits::ComposableIndex + Send>> {
+    if nested_attributes.is_empty() {
+        return Ok(IndexImpls {
+            protected_indexes_impl: quote! {
+                std::borrow::Cow::Borrowed(&[#(#protected_indexes,)*])
+            },
+            index_by_name_impl: quote! {
                 match ( index_name, index_type ) {
                     #(#indexes_impl,)*
                     _ => None,
                 }
-            }
-
-            fn attribute_for_index(&self, index_name: &str, index_type: cipherstash_dynamodb::IndexType) -> Option<cipherstash_dynamodb::traits::ComposablePlaintext> {
+            },
+            attribute_for_index_impl: quote! {
                 match ( index_name, index_type ) {
                     #(#attributes_for_index_impl,)*
                     _ => None,
                 }
-            }
+            },
+        });
+    }
+
+    let nested_indexes = nested_attributes.iter().map(|nested| {
+        let ty = &nested.ty;
+        let prefix = nested.prefix();
+
+        quote! {
+            indexes.extend(<#ty as cipherstash_dynamodb::traits::Nested>::indexes_with_prefix(#prefix));
         }
-    };
+    });
 
-    Ok(expanded)
+    let nested_index_by_name = nested_attributes.iter().map(|nested| {
+        let ty = &nested.ty;
+        let prefix = nested.prefix();
+
+        quote! {
+            .or_else(|| <#ty as cipherstash_dynamodb::traits::Nested>::index_by_path(#prefix, index_name, index_type))
+        }
+    });
+
+    let nested_attribute_for_index = nested_attributes.iter().map(|nested| {
+        let attr_ref = access.reference(&settings.field_ident(&nested.name));
+        let prefix = nested.prefix();
+
+        quote! {
+            .or_else(|| cipherstash_dynamodb::traits::Nested::attribute_for_path(#attr_ref, #prefix, index_name, index_type))
+        }
+    });
+
+    Ok(IndexImpls {
+        protected_indexes_impl: quote! {
+            let mut indexes = vec![#(#protected_indexes,)*];
+            #(#nested_indexes)*
+            std::borrow::Cow::Owned(indexes)
+        },
+        index_by_name_impl: quote! {
+            let index: Option<Box<dyn cipherstash_dynamodb::traits::ComposableIndex + Send>> = match ( index_name, index_type ) {
+                #(#indexes_impl,)*
+                _ => None,
+            };
+
+            index #(#nested_index_by_name)*
+        },
+        attribute_for_index_impl: quote! {
+            let plaintext: Option<cipherstash_dynamodb::traits::ComposablePlaintext> = match ( index_name, index_type ) {
+                #(#attributes_for_index_impl,)*
+                _ => None,
+            };
+
+            plaintext #(#nested_attribute_for_index)*
+        },
+    })
 }
@@ -1,7 +1,14 @@
-use super::{index_type::IndexType, AttributeMode, Settings};
+use super::{
+    index_type::IndexType, AttributeMode, FieldDefault, KeyBinding, NestedAttribute, Settings,
+    Variant,
+};
+use itertools::Itertools;
 use proc_macro2::{Ident, Span};
 use std::collections::HashMap;
-use syn::{Data, DeriveInput, ExprPath, Fields, LitStr};
+use syn::{
+    Data, DataEnum, DeriveInput, Expr, ExprPath, Field, Fields, FieldsNamed, Generics, LitInt,
+    LitStr, Token, Type,
+};
 
 enum SortKeyPrefix {
     Default,
@@ -21,8 +28,15 @@ impl SortKeyPrefix {
 
 const RESERVED_FIELD_NAMES: &[&str] = &["term"];
 
+/// The name of the attribute that stores the variant of an enum record if not set with
+/// `#[cipherstash(tag = "...")]`.
+const DEFAULT_TAG: &str = "type";
+
 pub(crate) struct SettingsBuilder {
     ident: Ident,
+    generics: Generics,
+    field_types: HashMap<String, Type>,
+    field_idents: HashMap<String, Ident>,
     type_name: String,
     sort_key_prefix: SortKeyPrefix,
     sort_key_field: Option<String>,
@@ -30,9 +44,21 @@ pub(crate) struct SettingsBuilder {
     protected_attributes: Vec<String>,
     unprotected_attributes: Vec<String>,
     skipped_attributes: Vec<String>,
+    nested_attributes: Vec<NestedAttribute>,
     indexes: Vec<IndexType>,
     encrypt_handlers: HashMap<String, ExprPath>,
     decrypt_handlers: HashMap<String, ExprPath>,
+    field_defaults: HashMap<String, FieldDefault>,
+    deny_unknown_attributes: bool,
+    required_record_mac: bool,
+    version: u32,
+    upcast: bool,
+    legacy_protected_attributes: Vec<String>,
+    typed_keys: bool,
+    key_binding: Option<KeyBinding>,
+    tag: String,
+    plaintext_tag: bool,
+    variants: Vec<Variant>,
 }
 
 impl SettingsBuilder {
@@ -52,6 +78,9 @@ impl SettingsBuilder {
 
         Self {
             ident: input.ident.clone(),
+            generics: input.generics.clone(),
+            field_types: HashMap::new(),
+            field_idents: HashMap::new(),
             type_name,
             sort_key_prefix: SortKeyPrefix::Default,
             sort_key_field: None,
@@ -59,9 +88,21 @@ impl SettingsBuilder {
             protected_attributes: Vec::new(),
             unprotected_attributes: Vec::new(),
             skipped_attributes: Vec::new(),
+            nested_attributes: Vec::new(),
             indexes: Vec::new(),
             encrypt_handlers: HashMap::new(),
             decrypt_handlers: HashMap::new(),
+            field_defaults: HashMap::new(),
+            deny_unknown_attributes: false,
+            required_record_mac: false,
+            version: 0,
+            upcast: false,
+            legacy_protected_attributes: Vec::new(),
+            typed_keys: false,
+            key_binding: None,
+            tag: DEFAULT_TAG.to_string(),
+            plaintext_tag: false,
+            variants: Vec::new(),
         }
     }
 
@@ -98,6 +139,83 @@ impl SettingsBuilder {
                             let t: LitStr = value.parse()?;
                             self.set_partition_key(t.value().to_string())
                         }
+                        Some("sort_key") => {
+                            let value = meta.value()?;
+                            let t: LitStr = value.parse()?;
+                            self.set_sort_key(t.value().to_string())
+                        }
+                        Some("type_name") => {
+                            let value = meta.value()?;
+                            let t: LitStr = value.parse()?;
+                            self.type_name = t.value();
+                            Ok(())
+                        }
+                        Some("tag") => {
+                            let value = meta.value()?;
+                            let t: LitStr = value.parse()?;
+                            self.tag = t.value();
+                            Ok(())
+                        }
+                        Some("plaintext_tag") => {
+                            self.plaintext_tag = true;
+                            Ok(())
+                        }
+                        Some("deny_unknown_attributes") => {
+                            self.deny_unknown_attributes = true;
+                            Ok(())
+                        }
+                        Some("record_mac") => {
+                            let t: LitStr = meta.value()?.parse()?;
+
+                            self.required_record_mac = match t.value().as_str() {
+                                "required" => true,
+                                "optional" => false,
+                                _ => return Err(syn::Error::new_spanned(
+                                    t,
+                                    "Unsupported record MAC: expected \"required\" or \"optional\"",
+                                )),
+                            };
+
+                            Ok(())
+                        }
+                        Some("version") => {
+                            let value = meta.value()?;
+                            let t: LitInt = value.parse()?;
+                            self.version = t.base10_parse()?;
+                            Ok(())
+                        }
+                        Some("upcast") => {
+                            self.upcast = true;
+                            Ok(())
+                        }
+                        Some("legacy_protected") => {
+                            let value = meta.value()?;
+                            let t: LitStr = value.parse()?;
+                            self.legacy_protected_attributes.push(t.value());
+                            Ok(())
+                        }
+                        Some("typed_keys") => {
+                            self.typed_keys = true;
+                            Ok(())
+                        }
+                        Some("key_binding") => {
+                            if meta.input.peek(Token![=]) {
+                                let t: LitStr = meta.value()?.parse()?;
+
+                                if t.value() != "required" {
+                                    return Err(syn::Error::new_spanned(
+                                        t,
+                                        "Unsupported key binding: expected \"required\"",
+                                    ));
+                                }
+
+                                self.key_binding = Some(KeyBinding::Required);
+                            } else {
+                                self.key_binding = Some(KeyBinding::Enabled);
+                            }
+
+                            Ok(())
+                        }
                         _ => Err(meta.error("unsupported attribute")),
                     }
                 })?;
@@ -108,251 +226,532 @@ impl SettingsBuilder {
     }
 
     pub(crate) fn field_attributes(
-        mut self,
+        self,
         DeriveInput { data, .. }: &DeriveInput,
     ) -> Result<Self, syn::Error> {
-        // Only support structs
-        if let Data::Struct(data_struct) = data {
-            if let Fields::Named(fields_named) = &data_struct.fields {
-                let all_field_names: Vec<String> = fields_named
-                    .named
+        match data {
+            Data::Struct(data_struct) => match &data_struct.fields {
+                Fields::Named(fields_named) => self.named_fields(fields_named),
+                _ => Ok(self),
+            },
+            Data::Enum(data_enum) => self.variants(data_enum),
+            Data::Union(_) => Ok(self),
+        }
+    }
+
+    fn named_fields(mut self, fields_named: &FieldsNamed) -> Result<Self, syn::Error> {
+        let all_field_names: Vec<String> = fields_named
+            .named
+            .iter()
+            .map(Self::attribute_name)
+            .collect::<Result<_, _>>()?;
+
+        if let Some(duplicate) = all_field_names.iter().duplicates().next() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!("More than one field is stored as '{duplicate}'"),
+            ));
+        }
+
+        let explicit_pk = all_field_names.contains(&String::from("pk"));
+        let explicit_sk = all_field_names.contains(&String::from("sk"));
+
+        let mut compound_indexes: HashMap<String, Vec<(String, String, Span)>> = Default::default();
+
+        for field in &fields_named.named {
+            let ident = &field.ident;
+            let mut attr_mode = AttributeMode::Protected;
+
+            let field_name = Self::attribute_name(field)?;
+
+            if field_name.starts_with("__") {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("Invalid field '{field_name}': fields must not be prefixed with __"),
+                ));
+            }
+
+            if RESERVED_FIELD_NAMES.contains(&field_name.as_str()) {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("Invalid field '{field_name}': name is reserved for internal use"),
+                ));
+            }
+
+            if field_name == "pk" {
+                let has_partition_key_attr = field
+                    .attrs
                     .iter()
-                    .flat_map(|x| x.ident.as_ref().map(|x| x.to_string()))
-                    .collect();
-
-                let explicit_pk = all_field_names.contains(&String::from("pk"));
-                let explicit_sk = all_field_names.contains(&String::from("sk"));
-
-                let mut compound_indexes: HashMap<String, Vec<(String, String, Span)>> =
-                    Default::default();
-
-                for field in &fields_named.named {
-                    let ident = &field.ident;
-                    let mut attr_mode = AttributeMode::Protected;
-
-                    let field_name = ident
-                        .as_ref()
-                        .ok_or_else(|| {
-                            syn::Error::new_spanned(
-                                field,
-                                "internal error: identifier was not Some",
-                            )
-                        })?
-                        .to_string();
-
-                    if field_name.starts_with("__") {
+                    .any(|x| x.path().is_ident("partition_key"));
+
+                if !has_partition_key_attr {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "field named 'pk' must be annotated with #[partition_key]".to_string(),
+                    ));
+                }
+            }
+
+            if field_name == "sk" {
+                let has_partition_key_attr =
+                    field.attrs.iter().any(|x| x.path().is_ident("sort_key"));
+
+                if !has_partition_key_attr {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "field named 'sk' must be annotated with #[sort_key]".to_string(),
+                    ));
+                }
+            }
+
+            // Parse the meta for the field
+            for attr in &field.attrs {
+                if attr.path().is_ident("sort_key") {
+                    if explicit_sk && field_name != "sk" {
                         return Err(syn::Error::new_spanned(
                             field,
-                            format!(
-                                "Invalid field '{field_name}': fields must not be prefixed with __"
-                            ),
+                            format!("field '{field_name}' cannot be used as sort key as struct contains field named 'sk' which must be used")
                         ));
                     }
 
-                    if RESERVED_FIELD_NAMES.contains(&field_name.as_str()) {
+                    if explicit_sk {
+                        // if the 'sk' field is set then there should be no prefix
+                        // otherwise when deserialising the sk value would be incorrect
+                        self.sort_key_prefix = SortKeyPrefix::None;
+                    }
+
+                    if let Some(f) = &self.sort_key_field {
                         return Err(syn::Error::new_spanned(
                             field,
-                            format!(
-                                "Invalid field '{field_name}': name is reserved for internal use"
-                            ),
+                            format!("sort key was already specified to be '{f}'"),
                         ));
                     }
 
-                    if field_name == "pk" {
-                        let has_partition_key_attr = field
-                            .attrs
-                            .iter()
-                            .any(|x| x.path().is_ident("partition_key"));
-
-                        if !has_partition_key_attr {
-                            return Err(syn::Error::new_spanned(
-                                field,
-                                "field named 'pk' must be annotated with #[partition_key]"
-                                    .to_string(),
-                            ));
-                        }
-                    }
+                    self.sort_key_field = Some(field_name.clone());
+                }
 
-                    if field_name == "sk" {
-                        let has_partition_key_attr =
-                            field.attrs.iter().any(|x| x.path().is_ident("sort_key"));
+                if attr.path().is_ident("partition_key") {
+                    if explicit_pk && field_name != "pk" {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            format!("field '{field_name}' cannot be used as partition key as struct contains field named 'pk' which must be used")
+                        ));
+                    }
 
-                        if !has_partition_key_attr {
-                            return Err(syn::Error::new_spanned(
-                                field,
-                                "field named 'sk' must be annotated with #[sort_key]".to_string(),
-                            ));
-                        }
+                    if let Some(f) = &self.partition_key_field {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            format!("partition key was already specified to be '{f}'"),
+                        ));
                     }
 
-                    // Parse the meta for the field
-                    for attr in &field.attrs {
-                        if attr.path().is_ident("sort_key") {
-                            if explicit_sk && field_name != "sk" {
-                                return Err(syn::Error::new_spanned(
-                                    field,
-                                    format!("field '{field_name}' cannot be used as sort key as struct contains field named 'sk' which must be used")
-                                ));
-                            }
+                    self.partition_key_field = Some(field_name.clone());
+                }
 
-                            if explicit_sk {
-                                // if the 'sk' field is set then there should be no prefix
-                                // otherwise when deserialising the sk value would be incorrect
-                                self.sort_key_prefix = SortKeyPrefix::None;
-                            }
+                if attr.path().is_ident("cipherstash") {
+                    let mut query: Option<(String, String, Span)> = None;
+                    let mut compound_index_name: Option<(String, Span)> = None;
 
-                            if let Some(f) = &self.sort_key_field {
-                                return Err(syn::Error::new_spanned(
-                                    field,
-                                    format!("sort key was already specified to be '{f}'"),
-                                ));
-                            }
+                    attr.parse_nested_meta(|meta| {
+                    let directive = meta.path.get_ident().map(|i| i.to_string());
+                    match directive.as_deref() {
+
+                        Some("plaintext") => {
+                            // Don't encrypt this field
+                            attr_mode = AttributeMode::Plaintext;
+                            Ok(())
+                        }
+                        Some("skip") => {
+                            // Don't even store this field
+                            attr_mode = AttributeMode::Skipped;
+                            Ok(())
+                        }
+                        Some("nested") => {
+                            // Store the fields of this field under its name
+                            attr_mode = AttributeMode::Nested;
+                            Ok(())
+                        }
+                        Some("flatten") => {
+                            // Store the fields of this field as fields of the parent
+                            attr_mode = AttributeMode::Flatten;
+                            Ok(())
+                        }
+                        Some("query") => {
+                            let value = meta.value()?;
+                            let index_type_span = value.span();
+                            let index_type = value.parse::<LitStr>()?.value();
+                            let index_name = field_name.clone();
 
-                            self.sort_key_field = Some(field_name.clone());
+                            query = Some(( index_name, index_type, index_type_span ));
+
+                            Ok(())
                         }
+                        Some("compound") => {
+                            let value = meta.value()?;
+
+                            let index_name = value.parse::<LitStr>()?.value();
+
+                            let is_valid_index = index_name
+                                .split('#')
+                                .all(|x| all_field_names.iter().any(|y| y == x));
 
-                        if attr.path().is_ident("partition_key") {
-                            if explicit_pk && field_name != "pk" {
-                                return Err(syn::Error::new_spanned(
-                                    field,
-                                    format!("field '{field_name}' cannot be used as partition key as struct contains field named 'pk' which must be used")
-                                ));
+                            if !is_valid_index {
+                                return Err(meta.error(format!("Compound index '{index_name}' is not valid. It must be valid fields separated by a '#' character.")));
                             }
 
-                            if let Some(f) = &self.partition_key_field {
-                                return Err(syn::Error::new_spanned(
-                                    field,
-                                    format!("partition key was already specified to be '{f}'"),
-                                ));
+                            let is_field_mentioned = index_name.split('#')
+                                .any(|x| x == field_name);
+
+                            if !is_field_mentioned {
+                                return Err(meta.error(format!("Compound index '{index_name}' does not include current field '{field_name}'.")));
                             }
 
-                            self.partition_key_field = Some(field_name.clone());
+                            compound_index_name = Some(( index_name, meta.input.span() ));
+
+                            Ok(())
+                        }
+                        Some("rename") => {
+                            // Already read by `attribute_name`
+                            meta.value()?.parse::<LitStr>()?;
+                            Ok(())
+                        }
+                        Some("encryptable_with") => {
+                            let value = meta.value()?;
+                            let handler = value.parse::<ExprPath>()?;
+                            self.encrypt_handlers.insert(field_name.clone(), handler);
+                            Ok(())
+                        }
+                        Some("decryptable_with") => {
+                            let value = meta.value()?;
+                            let handler = value.parse::<ExprPath>()?;
+                            self.decrypt_handlers.insert(field_name.clone(), handler);
+                            Ok(())
+                        }
+                        Some("default") => {
+                            // Use a default value when the attribute is missing from a record
+                            let default = if meta.input.peek(Token![=]) {
+                                FieldDefault::Path(meta.value()?.parse::<ExprPath>()?)
+                            } else {
+                                FieldDefault::Default
+                            };
+
+                            self.field_defaults.insert(field_name.clone(), default);
+                            Ok(())
                         }
+                        _ => Err(meta.error("unsupported field attribute")),
+                    }
+                })?;
 
-                        if attr.path().is_ident("cipherstash") {
-                            let mut query: Option<(String, String, Span)> = None;
-                            let mut compound_index_name: Option<(String, Span)> = None;
+                    let is_nested =
+                        matches!(attr_mode, AttributeMode::Nested | AttributeMode::Flatten);
 
-                            attr.parse_nested_meta(|meta| {
-                            let directive = meta.path.get_ident().map(|i| i.to_string());
-                            match directive.as_deref() {
+                    if is_nested && (query.is_some() || compound_index_name.is_some()) {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            format!("Nested field '{field_name}' can't be queried directly. Add indexes to the fields of the nested type instead."),
+                        ));
+                    }
 
-                                Some("plaintext") => {
-                                    // Don't encrypt this field
-                                    attr_mode = AttributeMode::Plaintext;
-                                    Ok(())
-                                }
-                                Some("skip") => {
-                                    // Don't even store this field
-                                    attr_mode = AttributeMode::Skipped;
-                                    Ok(())
-                                }
-                                Some("query") => {
-                                    let value = meta.value()?;
-                                    let index_type_span = value.span();
-                                    let index_type = value.parse::<LitStr>()?.value();
-                                    let index_name = ident
-                                        .as_ref()
-                                        .ok_or(meta.error("no index type specified"))?
-                                        .to_string();
-
-                                    query = Some(( index_name, index_type, index_type_span ));
-
-                                    Ok(())
-                                }
-                                Some("compound") => {
-                                    let value = meta.value()?;
+                    match (query, compound_index_name) {
+                        (Some((index_name, index_type, span)), Some((compound_index_name, _))) => {
+                            compound_indexes
+                                .entry(compound_index_name)
+                                .or_default()
+                                .push((index_name, index_type, span));
+                        }
 
-                                    let field_name = ident
-                                        .as_ref()
-                                        .ok_or(meta.error("no index type specified"))?
-                                        .to_string();
+                        (Some((index_name, index_type, span)), None) => {
+                            self.add_index(index_name, index_type.as_ref(), span)?;
+                        }
 
-                                    let index_name = value.parse::<LitStr>()?.value();
+                        (None, Some((compound_index_name, span))) => {
+                            return Err(syn::Error::new(
+                                span,
+                                format!("Compound attribute was specified but no query options were. Specify how this field should be queried with the attribute #[cipherstash(query = <option>, compound = \"{compound_index_name}\")]"))
+                            );
+                        }
 
-                                    let is_valid_index = index_name
-                                        .split('#')
-                                        .all(|x| all_field_names.iter().any(|y| y == x));
+                        (None, None) => {}
+                    };
+                }
+            }
 
-                                    if !is_valid_index {
-                                        return Err(meta.error(format!("Compound index '{index_name}' is not valid. It must be valid fields separated by a '#' character.")));
-                                    }
+            let is_nested = matches!(attr_mode, AttributeMode::Nested | AttributeMode::Flatten);
 
-                                    let is_field_mentioned = index_name.split('#')
-                                        .any(|x| x == field_name);
+            let is_key = [&self.partition_key_field, &self.sort_key_field]
+                .into_iter()
+                .flatten()
+                .any(|key| key.split('#').any(|part| part == field_name));
 
-                                    if !is_field_mentioned {
-                                        return Err(meta.error(format!("Compound index '{index_name}' does not include current field '{field_name}'.")));
-                                    }
+            if is_nested && is_key {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("Nested field '{field_name}' can't be used as a key"),
+                ));
+            }
 
-                                    compound_index_name = Some(( index_name, meta.input.span() ));
+            if is_nested
+                && (self.encrypt_handlers.contains_key(&field_name)
+                    || self.decrypt_handlers.contains_key(&field_name))
+            {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!("Nested field '{field_name}' can't have custom handlers"),
+                ));
+            }
 
-                                    Ok(())
-                                }
-                                Some("encryptable_with") => {
-                                    let value = meta.value()?;
-                                    let handler = value.parse::<ExprPath>()?;
-                                    self.encrypt_handlers.insert(field_name.clone(), handler);
-                                    Ok(())
-                                }
-                                Some("decryptable_with") => {
-                                    let value = meta.value()?;
-                                    let handler = value.parse::<ExprPath>()?;
-                                    self.decrypt_handlers.insert(field_name.clone(), handler);
-                                    Ok(())
-                                }
-                                _ => Err(meta.error("unsupported field attribute")),
-                            }
-                        })?;
-
-                            match (query, compound_index_name) {
-                                (
-                                    Some((index_name, index_type, span)),
-                                    Some((compound_index_name, _)),
-                                ) => {
-                                    compound_indexes
-                                        .entry(compound_index_name)
-                                        .or_default()
-                                        .push((index_name, index_type, span));
-                                }
+            if self.field_defaults.contains_key(&field_name) {
+                let reason = match attr_mode {
+                    AttributeMode::Nested | AttributeMode::Flatten => {
+                        Some("nested fields are read by their own type")
+                    }
+                    AttributeMode::Skipped => Some("skipped fields always use Default"),
+                    _ if self.decrypt_handlers.contains_key(&field_name) => {
+                        Some("fields with decryptable_with are read by the handler")
+                    }
+                    _ => None,
+                };
+
+                if let Some(reason) = reason {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        format!("Field '{field_name}' can't have a default: {reason}"),
+                    ));
+                }
+            }
 
-                                (Some((index_name, index_type, span)), None) => {
-                                    self.add_index(index_name, index_type.as_ref(), span)?;
-                                }
+            self.add_attribute(
+                field_name,
+                ident
+                    .clone()
+                    .ok_or(syn::Error::new_spanned(field, "missing field"))?,
+                &field.ty,
+                attr_mode,
+            );
+        }
 
-                                (None, Some((compound_index_name, span))) => {
-                                    return Err(syn::Error::new(
-                                        span,
-                                        format!("Compound attribute was specified but no query options were. Specify how this field should be queried with the attribute #[cipherstash(query = <option>, compound = \"{compound_index_name}\")]"))
-                                    );
-                                }
+        for (name, parts) in compound_indexes.into_iter() {
+            self.add_compound_index(name, parts)?;
+        }
 
-                                (None, None) => {}
-                            };
-                        }
-                    }
+        Ok(self)
+    }
 
-                    self.add_attribute(
-                        ident
-                            .as_ref()
-                            .ok_or(syn::Error::new_spanned(field, "missing field"))?
-                            .to_string(),
-                        attr_mode,
-                    );
+    /// Returns the name of the attribute that stores `field`, which is the name of the field
+    /// unless it is set with `#[cipherstash(rename = "...")]`.
+    fn attribute_name(field: &Field) -> Result<String, syn::Error> {
+        let mut name = field
+            .ident
+            .as_ref()
+            .ok_or_else(|| {
+                syn::Error::new_spanned(field, "internal error: identifier was not Some")
+            })?
+            .to_string();
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|x| x.path().is_ident("cipherstash"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.input.peek(Token![=]) {
+                    // Other directives are parsed with the rest of the field attributes
+                    meta.value()?.parse::<Expr>()?;
                 }
 
-                for (name, parts) in compound_indexes.into_iter() {
-                    self.add_compound_index(name, parts)?;
+                Ok(())
+            })?;
+        }
+
+        Ok(name)
+    }
+
+    /// Collects the fields of each variant of an enum record.
+    /// The attributes and indexes of the record are the union of those of its variants.
+    fn variants(mut self, data_enum: &DataEnum) -> Result<Self, syn::Error> {
+        if data_enum.variants.is_empty() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "Enum records must have at least one variant",
+            ));
+        }
+
+        let mut keys: Option<(Option<String>, Option<String>)> = None;
+
+        for variant in &data_enum.variants {
+            let Fields::Named(fields_named) = &variant.fields else {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "Enum records only support variants with named fields",
+                ));
+            };
+
+            let settings = Self::new_variant(&self)
+                .named_fields(fields_named)?
+                .build()?;
+
+            let variant_keys = (
+                settings.partition_key_field.clone(),
+                settings.sort_key_field.clone(),
+            );
+
+            match &keys {
+                Some(keys) if keys != &variant_keys => {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "Every variant must use the same #[partition_key] and #[sort_key] fields",
+                    ));
                 }
+                Some(_) => {}
+                None => keys = Some(variant_keys),
             }
+
+            self.add_variant(Variant {
+                ident: variant.ident.clone(),
+                settings,
+            })?;
+        }
+
+        if let Some((partition_key_field, sort_key_field)) = keys {
+            self.partition_key_field = partition_key_field.or(self.partition_key_field);
+            self.sort_key_field = sort_key_field;
+        }
+
+        if self.plaintext_tag {
+            self.unprotected_attributes.push(self.tag.clone());
+        } else {
+            self.protected_attributes.push(self.tag.clone());
         }
 
         Ok(self)
     }
 
+    /// Returns a builder for the fields of a variant of the enum record built by `parent`.
+    fn new_variant(parent: &Self) -> Self {
+        Self {
+            ident: parent.ident.clone(),
+            generics: parent.generics.clone(),
+            field_types: HashMap::new(),
+            field_idents: HashMap::new(),
+            type_name: parent.type_name.clone(),
+            sort_key_prefix: SortKeyPrefix::Default,
+            // Keys set on the enum are shared by every variant
+            sort_key_field: parent.sort_key_field.clone(),
+            partition_key_field: parent.partition_key_field.clone(),
+            protected_attributes: Vec::new(),
+            unprotected_attributes: Vec::new(),
+            skipped_attributes: Vec::new(),
+            nested_attributes: Vec::new(),
+            indexes: Vec::new(),
+            encrypt_handlers: HashMap::new(),
+            decrypt_handlers: HashMap::new(),
+            field_defaults: HashMap::new(),
+            deny_unknown_attributes: parent.deny_unknown_attributes,
+            required_record_mac: parent.required_record_mac,
+            version: parent.version,
+            upcast: parent.upcast,
+            legacy_protected_attributes: parent.legacy_protected_attributes.clone(),
+            typed_keys: parent.typed_keys,
+            key_binding: parent.key_binding,
+            tag: parent.tag.clone(),
+            plaintext_tag: parent.plaintext_tag,
+            variants: Vec::new(),
+        }
+    }
+
+    fn add_variant(&mut self, variant: Variant) -> Result<(), syn::Error> {
+        let settings = &variant.settings;
+
+        for (name, ty) in &settings.field_types {
+            self.field_types
+                .entry(name.clone())
+                .or_insert_with(|| ty.clone());
+        }
+
+        // Each variant binds its own fields so variants may store different fields as the same
+        // attribute
+        for (name, ident) in &settings.field_idents {
+            self.field_idents
+                .entry(name.clone())
+                .or_insert_with(|| ident.clone());
+        }
+
+        // An explicit `sk` field in any variant means the sort key has no prefix
+        if settings.sort_key_prefix.is_none() {
+            self.sort_key_prefix = SortKeyPrefix::None;
+        }
+
+        let modes = [
+            (&settings.protected_attributes, "protected"),
+            (&settings.unprotected_attributes, "plaintext"),
+            (&settings.skipped_attributes, "skipped"),
+        ];
+
+        for (attributes, mode) in modes {
+            for name in attributes {
+                if name == &self.tag {
+                    return Err(syn::Error::new_spanned(
+                        &variant.ident,
+                        format!("Field '{name}' conflicts with the tag of the enum. Set a different tag with #[cipherstash(tag = \"...\")]"),
+                    ));
+                }
+
+                let existing = [
+                    (&self.protected_attributes, "protected"),
+                    (&self.unprotected_attributes, "plaintext"),
+                    (&self.skipped_attributes, "skipped"),
+                ]
+                .into_iter()
+                .find(|(existing, _)| existing.contains(name));
+
+                match existing {
+                    Some((_, existing_mode)) if existing_mode != mode => {
+                        return Err(syn::Error::new_spanned(
+                            &variant.ident,
+                            format!("Field '{name}' is {mode} but it is {existing_mode} in another variant"),
+                        ));
+                    }
+                    Some(_) => {}
+                    None => match mode {
+                        "protected" => self.protected_attributes.push(name.clone()),
+                        "plaintext" => self.unprotected_attributes.push(name.clone()),
+                        _ => self.skipped_attributes.push(name.clone()),
+                    },
+                }
+            }
+        }
+
+        for (name, default) in &settings.field_defaults {
+            self.field_defaults
+                .entry(name.clone())
+                .or_insert_with(|| default.clone());
+        }
+
+        for nested in &settings.nested_attributes {
+            if !self.nested_attributes.iter().any(|x| x.name == nested.name) {
+                self.nested_attributes.push(nested.clone());
+            }
+        }
+
+        for index in &settings.indexes {
+            if !self.indexes.contains(index) {
+                self.indexes.push(index.clone());
+            }
+        }
+
+        self.variants.push(variant);
+
+        Ok(())
+    }
+
     pub(crate) fn build(self) -> Result<Settings, syn::Error> {
         let SettingsBuilder {
             ident,
+            generics,
+            field_types,
+            field_idents,
             type_name,
             sort_key_prefix,
             sort_key_field,
@@ -360,15 +759,51 @@ impl SettingsBuilder {
             protected_attributes,
             unprotected_attributes,
             skipped_attributes,
+            nested_attributes,
             indexes,
             encrypt_handlers,
             decrypt_handlers,
+            field_defaults,
+            deny_unknown_attributes,
+            required_record_mac,
+            version,
+            upcast,
+            legacy_protected_attributes,
+            typed_keys,
+            key_binding,
+            tag,
+            plaintext_tag,
+            variants,
         } = self;
 
+        if !legacy_protected_attributes.is_empty() && !upcast {
+            return Err(syn::Error::new_spanned(
+                &ident,
+                "legacy_protected attributes can only be read by types with #[cipherstash(upcast)]",
+            ));
+        }
+
         let sort_key_prefix = sort_key_prefix.into_prefix(&type_name);
 
+        // Composite keys are named after their fields separated by `#`
+        for part in [&partition_key_field, &sort_key_field]
+            .into_iter()
+            .flatten()
+            .flat_map(|key| key.split('#'))
+        {
+            if !field_types.contains_key(part) {
+                return Err(syn::Error::new_spanned(
+                    &ident,
+                    format!("Key field '{part}' is not a field of the record"),
+                ));
+            }
+        }
+
         Ok(Settings {
             ident,
+            generics,
+            field_types,
+            field_idents,
             sort_key_prefix,
             type_name,
             sort_key_field,
@@ -376,9 +811,21 @@ impl SettingsBuilder {
             protected_attributes,
             unprotected_attributes,
             skipped_attributes,
+            nested_attributes,
             indexes,
             encrypt_handlers,
             decrypt_handlers,
+            field_defaults,
+            deny_unknown_attributes,
+            required_record_mac,
+            version,
+            upcast,
+            legacy_protected_attributes,
+            typed_keys,
+            key_binding,
+            tag,
+            plaintext_tag,
+            variants,
         })
     }
 
@@ -392,11 +839,26 @@ impl SettingsBuilder {
         Ok(())
     }
 
-    fn add_attribute(&mut self, value: String, mode: AttributeMode) {
+    pub(crate) fn set_sort_key(&mut self, value: String) -> Result<(), syn::Error> {
+        self.sort_key_field = Some(value);
+        Ok(())
+    }
+
+    fn add_attribute(&mut self, value: String, ident: Ident, ty: &Type, mode: AttributeMode) {
+        self.field_types.insert(value.clone(), ty.clone());
+        self.field_idents.insert(value.clone(), ident);
+
         match mode {
             AttributeMode::Protected => self.protected_attributes.push(value),
             AttributeMode::Plaintext => self.unprotected_attributes.push(value),
             AttributeMode::Skipped => self.skipped_attributes.push(value),
+            AttributeMode::Nested | AttributeMode::Flatten => {
+                self.nested_attributes.push(NestedAttribute {
+                    name: value,
+                    ty: ty.clone(),
+                    flatten: matches!(mode, AttributeMode::Flatten),
+                })
+            }
         }
     }
 
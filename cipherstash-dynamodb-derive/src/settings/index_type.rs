@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+use super::{FieldAccess, Settings};
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 
@@ -73,27 +74,39 @@ impl IndexType {
         }
     }
 
-    pub(crate) fn to_compound_plaintext_access(&self) -> Result<TokenStream, syn::Error> {
+    pub(crate) fn to_compound_plaintext_access(
+        &self,
+        settings: &Settings,
+        access: FieldAccess,
+    ) -> Result<TokenStream, syn::Error> {
         match self {
             Self::Single(field, _) => {
-                let field = format_ident!("{field}");
+                let field = access.value(&settings.field_ident(field));
 
                 Ok(quote! {
-                    self.#field.clone().try_into().ok()
+                    #field.clone().try_into().ok()
                 })
             }
 
             Self::Compound2((field_a, _), (field_b, _)) => {
-                let field_a = format_ident!("{field_a}");
-                let field_b = format_ident!("{field_b}");
+                let field_a = access.value(&settings.field_ident(field_a));
+                let field_b = access.value(&settings.field_ident(field_b));
 
                 Ok(quote! {
-                    ( self.#field_a.clone(), self.#field_b.clone() ).try_into().ok()
+                    ( #field_a.clone(), #field_b.clone() ).try_into().ok()
                 })
             }
         }
     }
 
+    /// Returns the names of the fields used by this index.
+    pub(crate) fn fields(&self) -> Vec<&str> {
+        match self {
+            Self::Single(field, _) => vec![field],
+            Self::Compound2((field_a, _), (field_b, _)) => vec![field_a, field_b],
+        }
+    }
+
     pub(crate) fn to_cipherstash_dynamodb_indexer(&self) -> Result<TokenStream, syn::Error> {
         match self {
             Self::Single(_, index_type) => {
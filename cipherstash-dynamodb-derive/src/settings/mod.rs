@@ -4,17 +4,120 @@ use std::collections::HashMap;
 
 use self::{builder::SettingsBuilder, index_type::IndexType};
 use itertools::Itertools;
-use proc_macro2::Ident;
-use syn::{DeriveInput, ExprPath};
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{parse_quote, DeriveInput, ExprPath, Generics, Type, WherePredicate};
 
 pub(crate) enum AttributeMode {
     Protected,
     Plaintext,
     Skipped,
+    Nested,
+    Flatten,
+}
+
+/// A field whose type implements `Nested`.
+#[derive(Clone)]
+pub(crate) struct NestedAttribute {
+    pub(crate) name: String,
+    pub(crate) ty: Type,
+    /// Flattened fields are stored as if they were fields of the parent.
+    pub(crate) flatten: bool,
+}
+
+impl NestedAttribute {
+    /// Returns the path prefix of the fields of a nested attribute of a record.
+    pub(crate) fn prefix(&self) -> TokenStream {
+        if self.flatten {
+            quote! { None }
+        } else {
+            let name = &self.name;
+            quote! { Some(#name) }
+        }
+    }
+
+    /// Returns the path prefix of the fields of a nested attribute of a `Nested` struct which
+    /// is itself stored under `prefix`.
+    pub(crate) fn prefix_within_nested(&self) -> TokenStream {
+        if self.flatten {
+            quote! { prefix }
+        } else {
+            let name = &self.name;
+            quote! { Some(cipherstash_dynamodb::traits::nested_path(prefix, #name).as_ref()) }
+        }
+    }
+}
+
+/// The value of a field that is missing from a stored record, set with `#[cipherstash(default)]`.
+#[derive(Clone)]
+pub(crate) enum FieldDefault {
+    /// Use `Default::default()`.
+    Default,
+    /// Call the function at the path given with `#[cipherstash(default = path)]`.
+    Path(ExprPath),
+}
+
+/// Whether the encrypted attributes of a record are bound to its primary key, set with
+/// `#[cipherstash(key_binding)]`.
+#[derive(Clone, Copy)]
+pub(crate) enum KeyBinding {
+    /// Bind attributes when they are written.
+    Enabled,
+    /// Bind attributes when they are written and reject unbound attributes when they are read.
+    Required,
+}
+
+impl ToTokens for KeyBinding {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Self::Enabled => quote! { cipherstash_dynamodb::traits::KeyBinding::Enabled },
+            Self::Required => quote! { cipherstash_dynamodb::traits::KeyBinding::Required },
+        });
+    }
+}
+
+/// A variant of an enum record and the settings of its fields.
+pub(crate) struct Variant {
+    pub(crate) ident: Ident,
+    pub(crate) settings: Settings,
+}
+
+/// How generated code refers to the fields of a record.
+#[derive(Clone, Copy)]
+pub(crate) enum FieldAccess {
+    /// Fields are accessed through `self`.
+    SelfField,
+    /// Fields are bound by a pattern that matches a variant of an enum.
+    Binding,
+}
+
+impl FieldAccess {
+    /// Returns the field as a value, moving it out of `self` if it isn't `Copy`.
+    pub(crate) fn value(&self, field: &Ident) -> TokenStream {
+        match self {
+            Self::SelfField => quote! { self.#field },
+            Self::Binding => quote! { #field },
+        }
+    }
+
+    /// Returns a reference to the field.
+    pub(crate) fn reference(&self, field: &Ident) -> TokenStream {
+        match self {
+            Self::SelfField => quote! { &self.#field },
+            Self::Binding => quote! { #field },
+        }
+    }
 }
 
 pub(crate) struct Settings {
     ident: Ident,
+    generics: Generics,
+
+    /// The types of the fields of the record, including the fields of every variant of an enum.
+    field_types: HashMap<String, Type>,
+
+    /// Map of attribute names to the fields that are stored under them when they differ.
+    field_idents: HashMap<String, Ident>,
     pub(crate) sort_key_prefix: Option<String>,
     pub(crate) type_name: String,
     pub(crate) sort_key_field: Option<String>,
@@ -31,7 +134,42 @@ pub(crate) struct Settings {
     /// Skipped attributes are never encrypted by the `DecryptedRecord` trait will
     /// use these to reconstruct the struct via `Default` (like serde).
     skipped_attributes: Vec<String>,
+
+    /// Attributes whose fields are stored using the `Nested` trait of their type.
+    nested_attributes: Vec<NestedAttribute>,
     indexes: Vec<IndexType>,
+
+    /// Map of attribute names to the value used when the attribute is missing from a record.
+    field_defaults: HashMap<String, FieldDefault>,
+
+    /// Whether decrypting fails when a record has attributes that aren't fields of the type.
+    pub(crate) deny_unknown_attributes: bool,
+
+    /// Whether records without a MAC, such as those stored before MACs were added, are rejected.
+    pub(crate) required_record_mac: bool,
+
+    /// The schema version stored with every record.
+    pub(crate) version: u32,
+
+    /// Whether records stored with an older version are migrated with the `Upcast` trait.
+    pub(crate) upcast: bool,
+
+    /// Protected attributes of older versions which are decrypted so they can be upcast.
+    pub(crate) legacy_protected_attributes: Vec<String>,
+
+    /// Whether the primary key keeps the types of the key fields instead of converting them to
+    /// strings.
+    pub(crate) typed_keys: bool,
+
+    /// Whether the encrypted attributes are bound to the primary key of the record.
+    pub(crate) key_binding: Option<KeyBinding>,
+
+    /// The name of the attribute that stores the variant of an enum record.
+    pub(crate) tag: String,
+    pub(crate) plaintext_tag: bool,
+
+    /// The variants of an enum record. Empty for structs.
+    variants: Vec<Variant>,
 }
 
 impl Settings {
@@ -43,6 +181,72 @@ impl Settings {
         &self.ident
     }
 
+    /// Returns the field that is stored as the attribute `name`.
+    pub(crate) fn field_ident(&self, name: &str) -> Ident {
+        self.field_idents
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| format_ident!("{name}"))
+    }
+
+    /// Returns the type of the field that is stored as the attribute `name`.
+    pub(crate) fn field_type(&self, name: &str) -> Option<&Type> {
+        self.field_types.get(name)
+    }
+
+    /// Returns the generics of the record with a `where` predicate for each `(fields, bound)`
+    /// whose field types use a type parameter of the record. If the record has type parameters
+    /// then the record itself is also bound by `self_bound`.
+    pub(crate) fn generics_with_bounds(
+        &self,
+        bounds: impl IntoIterator<Item = (Vec<String>, TokenStream)>,
+        self_bound: Option<TokenStream>,
+    ) -> Generics {
+        let mut generics = self.generics.clone();
+
+        let params = generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect::<Vec<_>>();
+
+        if params.is_empty() {
+            return generics;
+        }
+
+        let ident = &self.ident;
+        let (_, ty_generics, _) = self.generics.split_for_impl();
+
+        let mut predicates: Vec<WherePredicate> = self_bound
+            .into_iter()
+            .map(|bound| parse_quote! { #ident #ty_generics: #bound })
+            .collect();
+
+        for (fields, bound) in bounds {
+            let Some(types) = fields
+                .iter()
+                .map(|field| self.field_types.get(field))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            if !types.iter().any(|ty| uses_type_params(ty, &params)) {
+                continue;
+            }
+
+            // Compound indexes are bound on a tuple of their field types
+            let predicate = match types.as_slice() {
+                [ty] => parse_quote! { #ty: #bound },
+                types => parse_quote! { ( #(#types),* ): #bound },
+            };
+
+            predicates.push(predicate);
+        }
+
+        generics.make_where_clause().predicates.extend(predicates);
+        generics
+    }
+
     pub(crate) fn protected_attributes(&self) -> Vec<&str> {
         self.protected_attributes
             .iter()
@@ -85,6 +289,94 @@ impl Settings {
             .collect::<Vec<_>>()
     }
 
+    /// Returns the attributes that use `Default::default()` when they are missing.
+    pub(crate) fn default_trait_attributes(&self) -> Vec<&str> {
+        self.field_defaults
+            .iter()
+            .filter(|(_, default)| matches!(default, FieldDefault::Default))
+            .map(|(name, _)| name.as_str())
+            .sorted()
+            .collect()
+    }
+
+    /// Returns the expression that reads the protected attribute `name` from the
+    /// `Option<Plaintext>` returned by `take`.
+    pub(crate) fn read_protected(&self, name: &str, take: TokenStream) -> TokenStream {
+        match self.default_value(name) {
+            Some(default) => quote! {
+                match #take {
+                    Some(plaintext) => ::cipherstash_dynamodb::traits::TryFromPlaintext::try_from_plaintext(plaintext)?,
+                    None => #default,
+                }
+            },
+            None => quote! {
+                ::cipherstash_dynamodb::traits::TryFromPlaintext::try_from_optional_plaintext(#take)?
+            },
+        }
+    }
+
+    /// Returns the expression that reads the plaintext attribute `name` from the
+    /// `TableAttribute` returned by `take`.
+    pub(crate) fn read_plaintext(&self, name: &str, take: TokenStream) -> TokenStream {
+        match self.default_value(name) {
+            Some(default) => quote! {
+                match #take {
+                    ::cipherstash_dynamodb::traits::TableAttribute::Null => #default,
+                    attribute => ::cipherstash_dynamodb::traits::TryFromTableAttr::try_from_table_attr(attribute)?,
+                }
+            },
+            None => quote! {
+                ::cipherstash_dynamodb::traits::TryFromTableAttr::try_from_table_attr(#take)?
+            },
+        }
+    }
+
+    fn default_value(&self, name: &str) -> Option<TokenStream> {
+        self.field_defaults.get(name).map(|default| match default {
+            FieldDefault::Default => quote! { Default::default() },
+            FieldDefault::Path(path) => quote! { #path() },
+        })
+    }
+
+    pub(crate) fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    pub(crate) fn is_enum(&self) -> bool {
+        !self.variants.is_empty()
+    }
+
+    /// Returns the fields that are stored, excluding skipped fields.
+    pub(crate) fn stored_fields(&self) -> Vec<Ident> {
+        self.protected_attributes
+            .iter()
+            .chain(self.unprotected_attributes.iter())
+            .chain(self.nested_attributes.iter().map(|nested| &nested.name))
+            .sorted()
+            .map(|name| self.field_ident(name))
+            .collect()
+    }
+
+    /// Returns the fields used by indexes, including nested fields which may have indexes.
+    pub(crate) fn indexed_fields(&self) -> Vec<Ident> {
+        self.indexes
+            .iter()
+            .flat_map(|index| index.fields())
+            .chain(
+                self.nested_attributes
+                    .iter()
+                    .map(|nested| nested.name.as_str()),
+            )
+            .sorted()
+            .dedup()
+            .map(|name| self.field_ident(name))
+            .collect()
+    }
+
+    pub(crate) fn nested_attributes(&self) -> &[NestedAttribute] {
+        &self.nested_attributes
+    }
+
     /// Return the indexes defined for this struct as a vector sorted by index name.
     /// This is to make downstream functions and tests simpler.
     pub(crate) fn indexes(&self) -> Vec<IndexType> {
@@ -98,4 +390,45 @@ impl Settings {
     pub(crate) fn get_partition_key(&self) -> Option<String> {
         self.partition_key_field.clone()
     }
+
+    /// Returns the fields that make up the partition key in order.
+    /// Composite keys are named after their fields separated by `#`.
+    pub(crate) fn partition_key_fields(&self) -> Vec<&str> {
+        self.partition_key_field
+            .iter()
+            .flat_map(|key| key.split('#'))
+            .collect()
+    }
+
+    /// Returns the fields that make up the sort key in order.
+    pub(crate) fn sort_key_fields(&self) -> Vec<&str> {
+        self.sort_key_field
+            .iter()
+            .flat_map(|key| key.split('#'))
+            .collect()
+    }
+}
+
+/// Returns a `(fields, bound)` pair for [`Settings::generics_with_bounds`] for each field.
+pub(crate) fn field_bounds<'a>(
+    fields: impl IntoIterator<Item = &'a str>,
+    bound: TokenStream,
+) -> Vec<(Vec<String>, TokenStream)> {
+    fields
+        .into_iter()
+        .map(|field| (vec![field.to_string()], bound.clone()))
+        .collect()
+}
+
+/// Returns true if `ty` refers to any of the type parameters `params`.
+fn uses_type_params(ty: &Type, params: &[Ident]) -> bool {
+    fn visit(tokens: TokenStream, params: &[Ident]) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => params.contains(&ident),
+            TokenTree::Group(group) => visit(group.stream(), params),
+            _ => false,
+        })
+    }
+
+    visit(ty.to_token_stream(), params)
 }
@@ -0,0 +1,32 @@
+mod common;
+use crate::common::{License, User};
+use cipherstash_dynamodb::encrypted_table::{TemplateArgs, TypeRegistry};
+use std::process::ExitCode;
+
+/// Print an infrastructure template for a table storing the `User` and `License` types, e.g.
+///
+/// ```bash
+/// cargo run --example schema -- --table-name users --format terraform
+/// ```
+fn main() -> ExitCode {
+    let mut registry = TypeRegistry::new();
+
+    if let Err(e) = registry
+        .register::<User>()
+        .and_then(|_| registry.register::<License>())
+    {
+        eprintln!("{e}");
+        return ExitCode::FAILURE;
+    }
+
+    match TemplateArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => {
+            print!("{}", args.registry(&registry).render());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{}", TemplateArgs::USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    crypto::{attrs::flattened_protected_attributes::FlattenedAttrName, SealError},
+    crypto::{
+        attrs::flattened_protected_attributes::FlattenedAttrName, key_binding::split_descriptor,
+        ExpectedBinding, SealError,
+    },
     encrypted_table::{TableAttributes, ZeroKmsCipher},
     traits::TableAttribute,
 };
@@ -28,6 +31,11 @@ impl FlattenedEncryptedAttributes {
         self.attrs.len()
     }
 
+    /// Move the records of `other` to the end of `self`.
+    pub(crate) fn append(&mut self, mut other: Self) {
+        self.attrs.append(&mut other.attrs);
+    }
+
     // TODO: Test this
     /// Decrypt self, returning a [FlattenedProtectedAttributes].
     pub(crate) async fn decrypt_all(
@@ -40,19 +48,30 @@ impl FlattenedEncryptedAttributes {
             .map(|record| record.descriptor.clone())
             .collect_vec();
 
-        cipher
-            .decrypt(self.attrs.into_iter(), None, None, None)
+        // ZeroKMS decrypts the attributes in one request so a failure can't be attributed to a
+        // single attribute
+        let records = cipher
+            .decrypt(self.attrs, None, None, None)
             .await
-            .map(|records| {
-                records
-                    .into_iter()
-                    // FIXME: We should change the decrypt method to return a plaintext and/or make a Plaintext::from_bytes method which consumes the bytes
-                    .map(|bytes| Plaintext::from_slice(&bytes).unwrap())
-                    .zip(descriptors.into_iter())
-                    .collect()
+            .map_err(|source| SealError::DecryptionFailed {
+                attribute: descriptors
+                    .iter()
+                    .map(|descriptor| split_descriptor(descriptor).0)
+                    .unique()
+                    .join(", "),
+                source,
+            })?;
+
+        records
+            .into_iter()
+            .zip_eq(descriptors)
+            .map(|(bytes, descriptor)| match Plaintext::from_slice(&bytes) {
+                Ok(plaintext) => Ok((plaintext, descriptor)),
+                Err(_) => Err(SealError::MalformedCiphertext(
+                    split_descriptor(&descriptor).0.to_string(),
+                )),
             })
-            // FIXME: EncryptedRecord should return an error exposed in cipherstash_client
-            .map_err(|_| SealError::AssertionFailed("FIXME".to_string()))
+            .collect()
     }
 
     /// Denormalize the encrypted records into a TableAttributes.
@@ -65,7 +84,11 @@ impl FlattenedEncryptedAttributes {
                 record
                     .to_mp_bytes()
                     .map(|data| (FlattenedAttrName::parse(&record.descriptor), data))
-                    .map_err(|_| SealError::AssertionFailed("Decryption failed".to_string()))
+                    .map_err(|_| {
+                        SealError::MalformedCiphertext(
+                            split_descriptor(&record.descriptor).0.to_string(),
+                        )
+                    })
             })
             .fold_ok(
                 Ok(TableAttributes::new()),
@@ -89,13 +112,15 @@ impl FlattenedEncryptedAttributes {
     /// (only `Bytes` and `Map` are currently supported).
     ///
     /// Bytes data is converted to an [EncryptedRecord] using [TableAttribute::as_encrypted_record]
-    /// which validates that the descriptor matches the key and subkey.
+    /// which validates that the descriptor matches the key and subkey and that the record is bound
+    /// to the item it was read from, see [ExpectedBinding].
     ///
     /// This method is used during decrypt and load operations.
     pub(crate) fn try_extend(
         &mut self,
         attributes: TableAttributes,
         prefix: String,
+        binding: &ExpectedBinding,
     ) -> Result<(), SealError> {
         for (name, value) in attributes.into_iter() {
             match value {
@@ -104,20 +129,21 @@ impl FlattenedEncryptedAttributes {
                         let attr_key = FlattenedAttrName::new(Some(prefix.clone()), name.clone())
                             .with_subkey(subkey);
                         // Load the bytes and check for a confused deputy attack
-                        let record = value.as_encrypted_record(&attr_key.descriptor())?;
+                        let record = value.as_encrypted_record(&attr_key.descriptor(), binding)?;
                         self.attrs.push(record);
                     }
                 }
                 TableAttribute::Bytes(_) => {
                     let attr_key = FlattenedAttrName::new(Some(prefix.clone()), name);
                     // Load the bytes and check for a confused deputy attack
-                    let record = value.as_encrypted_record(&attr_key.descriptor())?;
+                    let record = value.as_encrypted_record(&attr_key.descriptor(), binding)?;
                     self.attrs.push(record);
                 }
-                _ => {
-                    Err(SealError::AssertionFailed(
-                        "Unsupported attribute type".to_string(),
-                    ))?;
+                other => {
+                    return Err(SealError::UnsupportedStoredType {
+                        attribute: FlattenedAttrName::new(Some(prefix.clone()), name).descriptor(),
+                        found: other.type_name(),
+                    });
                 }
             }
         }
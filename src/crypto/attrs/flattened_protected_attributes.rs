@@ -3,7 +3,10 @@ use super::{
     normalized_protected_attributes::NormalizedKey,
 };
 use crate::{
-    crypto::SealError,
+    crypto::{
+        key_binding::{bind_descriptor, split_descriptor},
+        SealError,
+    },
     encrypted_table::{AttributeName, ScopedZeroKmsCipher},
 };
 use cipherstash_client::{
@@ -30,6 +33,15 @@ impl FlattenedProtectedAttributes {
         self.0.into_iter()
     }
 
+    /// Bind every attribute to the record with the given `binding`, see [`crate::traits::KeyBinding`].
+    pub(crate) fn bind_to(mut self, binding: &str) -> Self {
+        for attribute in self.0.iter_mut() {
+            attribute.binding = Some(binding.to_string());
+        }
+
+        self
+    }
+
     // TODO: Do some more testing with the chunking
     /// Encrypt all attributes in the set and return a list of [FlattenedEncryptedAttributes] objects.
     /// The output is a vec of `chunk_into` [FlattenedEncryptedAttributes] objects.
@@ -76,6 +88,8 @@ impl FromIterator<(Plaintext, String)> for FlattenedProtectedAttributes {
 pub(crate) struct FlattenedProtectedAttribute {
     plaintext: Plaintext,
     key: FlattenedAttrName,
+    /// The binding to the record the attribute is stored in, if any.
+    binding: Option<String>,
 }
 
 impl FlattenedProtectedAttribute {
@@ -83,6 +97,7 @@ impl FlattenedProtectedAttribute {
         Self {
             plaintext: plaintext.into(),
             key: key.into(),
+            binding: None,
         }
     }
 
@@ -96,7 +111,10 @@ impl FlattenedProtectedAttribute {
     /// This is used as the descriptor when encrypting the attribute and
     /// is intended to tie the encrypted value to its location in a table.
     fn storage_descriptor(&self) -> String {
-        self.key.descriptor()
+        match &self.binding {
+            Some(binding) => bind_descriptor(&self.key.descriptor(), binding),
+            None => self.key.descriptor(),
+        }
     }
 }
 
@@ -150,6 +168,7 @@ impl FlattenedAttrName {
 
     // TODO: Rename this to try_parse
     /// Parse a descriptor into a [FlattenedKey].
+    /// The binding of a bound descriptor is ignored.
     pub(super) fn parse(descriptor: &str) -> Self {
         fn split_subkey(prefix: Option<String>, key: &str) -> FlattenedAttrName {
             match key.split_once('.') {
@@ -157,6 +176,8 @@ impl FlattenedAttrName {
                 Some((key, subkey)) => FlattenedAttrName::new(prefix, key).with_subkey(subkey),
             }
         }
+        let (descriptor, _) = split_descriptor(descriptor);
+
         match descriptor.split_once('/') {
             None => split_subkey(None, descriptor),
             Some((prefix, key)) => split_subkey(Some(prefix.to_string()), key),
@@ -231,6 +252,7 @@ mod tests {
             f.debug_struct("FlattenedProtectedAttribute")
                 .field("plaintext", &self.plaintext)
                 .field("key", &self.key)
+                .field("binding", &self.binding)
                 .finish()
         }
     }
@@ -374,5 +396,9 @@ mod tests {
             FlattenedAttrName::parse("prefix/key.subkey"),
             FlattenedAttrName::from(("prefix", "key")).with_subkey("subkey")
         );
+        assert_eq!(
+            FlattenedAttrName::parse(&bind_descriptor("prefix/key.subkey", "binding")),
+            FlattenedAttrName::from(("prefix", "key")).with_subkey("subkey")
+        );
     }
 }
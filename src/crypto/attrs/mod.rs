@@ -3,4 +3,4 @@ mod flattened_protected_attributes;
 mod normalized_protected_attributes;
 pub(crate) use flattened_encrypted_attributes::FlattenedEncryptedAttributes;
 pub(crate) use flattened_protected_attributes::FlattenedProtectedAttributes;
-pub(crate) use normalized_protected_attributes::NormalizedProtectedAttributes;
+pub(crate) use normalized_protected_attributes::{NormalizedProtectedAttributes, NormalizedValue};
@@ -73,6 +73,31 @@ impl NormalizedProtectedAttributes {
             .and_then(|v| v.into_map())
     }
 
+    /// Remove and return a single field of a protected *map* value.
+    /// The map is removed once its last field has been taken.
+    pub fn take_map_field(&mut self, name: &str, subkey: &str) -> Option<Plaintext> {
+        let key = NormalizedKey::Map(name.to_string());
+
+        let NormalizedValue::Map(map) = self.values.get_mut(&key)? else {
+            return None;
+        };
+
+        let value = map.remove(subkey);
+
+        if map.is_empty() {
+            self.values.remove(&key);
+        }
+
+        value
+    }
+
+    /// Iterate over the names and values of the protected attributes.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &NormalizedValue)> {
+        self.values.iter().map(|(key, value)| match key {
+            NormalizedKey::Scalar(name) | NormalizedKey::Map(name) => (name.as_str(), value),
+        })
+    }
+
     pub(crate) fn flatten(self) -> FlattenedProtectedAttributes {
         let inner: Vec<FlattenedProtectedAttribute> = self
             .values
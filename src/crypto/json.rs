@@ -0,0 +1,170 @@
+//! Conversions between decrypted values and [`serde_json::Value`].
+//!
+//! JSON has fewer types than [`Plaintext`] and [`TableAttribute`] so the conversions are lossy:
+//! dates, timestamps and decimals become strings, bytes become base64 strings and all numbers
+//! are read back as `BigInt`, `BigUInt` or `Float` plaintexts.
+
+use super::b64_encode;
+use crate::encrypted_table::TableAttribute;
+use cipherstash_client::encryption::Plaintext;
+use serde_json::{json, Map, Number, Value};
+use std::collections::HashMap;
+
+pub(crate) fn plaintext_to_json(plaintext: &Plaintext) -> Value {
+    match plaintext {
+        Plaintext::BigInt(x) => json!(x),
+        Plaintext::BigUInt(x) => json!(x),
+        Plaintext::Boolean(x) => json!(x),
+        Plaintext::Decimal(x) => json!(x.as_ref().map(ToString::to_string)),
+        Plaintext::Float(x) => json!(x),
+        Plaintext::Int(x) => json!(x),
+        Plaintext::NaiveDate(x) => json!(x.as_ref().map(ToString::to_string)),
+        Plaintext::SmallInt(x) => json!(x),
+        Plaintext::Timestamp(x) => json!(x.as_ref().map(|x| x.to_rfc3339())),
+        Plaintext::Utf8Str(x) => json!(x),
+        Plaintext::JsonB(x) => x.clone().unwrap_or(Value::Null),
+    }
+}
+
+/// Convert a JSON value to a plaintext.
+///
+/// Returns `None` for `null` as there is no way to tell which type of plaintext it should be.
+/// Arrays and objects are stored as JSON plaintexts.
+pub(crate) fn json_to_plaintext(value: Value) -> Option<Plaintext> {
+    match value {
+        Value::Null => None,
+        Value::Bool(x) => Some(Plaintext::Boolean(Some(x))),
+        Value::Number(x) => Some(number_to_plaintext(&x)),
+        Value::String(x) => Some(Plaintext::Utf8Str(Some(x))),
+        value @ (Value::Array(_) | Value::Object(_)) => Some(Plaintext::JsonB(Some(value))),
+    }
+}
+
+fn number_to_plaintext(number: &Number) -> Plaintext {
+    if let Some(x) = number.as_i64() {
+        Plaintext::BigInt(Some(x))
+    } else if let Some(x) = number.as_u64() {
+        Plaintext::BigUInt(Some(x))
+    } else {
+        Plaintext::Float(number.as_f64())
+    }
+}
+
+pub(crate) fn plaintext_map_to_json(map: &HashMap<String, Plaintext>) -> Value {
+    Value::Object(
+        map.iter()
+            .map(|(key, plaintext)| (key.clone(), plaintext_to_json(plaintext)))
+            .collect(),
+    )
+}
+
+/// Convert a JSON object to a map of plaintexts, skipping `null` values.
+pub(crate) fn json_to_plaintext_map(map: Map<String, Value>) -> HashMap<String, Plaintext> {
+    map.into_iter()
+        .filter_map(|(key, value)| json_to_plaintext(value).map(|plaintext| (key, plaintext)))
+        .collect()
+}
+
+pub(crate) fn table_attribute_to_json(attribute: &TableAttribute) -> Value {
+    match attribute {
+        TableAttribute::String(x) => Value::String(x.clone()),
+        TableAttribute::Number(x) => number_to_json(x),
+        TableAttribute::Bool(x) => Value::Bool(*x),
+        TableAttribute::Bytes(x) => Value::String(b64_encode(x)),
+        TableAttribute::StringVec(x) => json!(x),
+        TableAttribute::ByteVec(x) => Value::Array(
+            x.iter()
+                .map(|bytes| Value::String(b64_encode(bytes)))
+                .collect(),
+        ),
+        TableAttribute::NumberVec(x) => {
+            Value::Array(x.iter().map(String::as_str).map(number_to_json).collect())
+        }
+        TableAttribute::Map(x) => Value::Object(
+            x.iter()
+                .map(|(key, attribute)| (key.clone(), table_attribute_to_json(attribute)))
+                .collect(),
+        ),
+        TableAttribute::List(x) => Value::Array(x.iter().map(table_attribute_to_json).collect()),
+        TableAttribute::Null => Value::Null,
+    }
+}
+
+/// DynamoDB numbers are strings which are usually, but not always, valid JSON numbers.
+fn number_to_json(number: &str) -> Value {
+    number
+        .parse::<Number>()
+        .map(Value::Number)
+        .unwrap_or_else(|_| Value::String(number.to_string()))
+}
+
+pub(crate) fn json_to_table_attribute(value: Value) -> TableAttribute {
+    match value {
+        Value::Null => TableAttribute::Null,
+        Value::Bool(x) => TableAttribute::Bool(x),
+        Value::Number(x) => TableAttribute::Number(x.to_string()),
+        Value::String(x) => TableAttribute::String(x),
+        Value::Array(x) => {
+            TableAttribute::List(x.into_iter().map(json_to_table_attribute).collect())
+        }
+        Value::Object(x) => TableAttribute::Map(
+            x.into_iter()
+                .map(|(key, value)| (key, json_to_table_attribute(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plaintext_round_trip() {
+        for value in [
+            json!("value"),
+            json!(true),
+            json!(-42),
+            json!(1.5),
+            json!({ "a": [1, 2] }),
+        ] {
+            assert_eq!(
+                plaintext_to_json(&json_to_plaintext(value.clone()).unwrap()),
+                value
+            );
+        }
+
+        assert!(json_to_plaintext(Value::Null).is_none());
+    }
+
+    #[test]
+    fn test_table_attribute_round_trip() {
+        let value = json!({
+            "name": "value",
+            "count": 42,
+            "enabled": false,
+            "tags": ["a", "b"],
+            "nothing": null,
+        });
+
+        assert_eq!(
+            table_attribute_to_json(&json_to_table_attribute(value.clone())),
+            value
+        );
+    }
+
+    #[test]
+    fn test_table_attribute_to_json() {
+        assert_eq!(
+            table_attribute_to_json(&TableAttribute::NumberVec(vec![
+                "1".to_string(),
+                "NaN".to_string()
+            ])),
+            json!([1, "NaN"])
+        );
+        assert_eq!(
+            table_attribute_to_json(&TableAttribute::Bytes(vec![1, 2, 3])),
+            json!("AQID")
+        );
+    }
+}
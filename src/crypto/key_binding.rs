@@ -0,0 +1,112 @@
+use super::{b64_encode, SealError};
+use crate::traits::KeyBinding;
+use sha2::{Digest, Sha256};
+
+/// Separates the descriptor of an attribute from the binding to its record.
+/// `k1` is the version of the binding format.
+const KEY_BINDING_MARKER: &str = "@k1:";
+
+/// Returns the binding of the encrypted attributes of the record stored with `pk` and `sk`.
+pub(crate) fn key_binding(pk: &str, sk: &str) -> String {
+    let mut hasher = Sha256::new();
+    // Prefix the partition key with its length so the boundary between the keys is unambiguous
+    hasher.update((pk.len() as u64).to_be_bytes());
+    hasher.update(pk);
+    hasher.update(sk);
+    b64_encode(hasher.finalize())
+}
+
+/// Returns `descriptor` bound to the record with the given `binding`.
+pub(crate) fn bind_descriptor(descriptor: &str, binding: &str) -> String {
+    format!("{descriptor}{KEY_BINDING_MARKER}{binding}")
+}
+
+/// Splits a descriptor into the descriptor of the attribute and the binding, if any.
+pub(crate) fn split_descriptor(descriptor: &str) -> (&str, Option<&str>) {
+    match descriptor.rsplit_once(KEY_BINDING_MARKER) {
+        Some((descriptor, binding)) => (descriptor, Some(binding)),
+        None => (descriptor, None),
+    }
+}
+
+/// The binding expected for the encrypted attributes of a stored item.
+pub(crate) struct ExpectedBinding {
+    binding: String,
+    required: bool,
+}
+
+impl ExpectedBinding {
+    pub(crate) fn new(pk: &str, sk: &str, mode: KeyBinding) -> Self {
+        Self {
+            binding: key_binding(pk, sk),
+            required: mode == KeyBinding::Required,
+        }
+    }
+
+    /// Check that the `descriptor` of an encrypted attribute is the `expected` descriptor and that
+    /// the attribute is bound to this record if it is bound at all.
+    pub(crate) fn verify(&self, descriptor: &str, expected: &str) -> Result<(), SealError> {
+        let (unbound, binding) = split_descriptor(descriptor);
+
+        if unbound != expected {
+            return Err(SealError::DescriptorMismatch {
+                expected: expected.to_string(),
+                found: unbound.to_string(),
+            });
+        }
+
+        match binding {
+            Some(binding) if binding == self.binding => Ok(()),
+            Some(_) => Err(SealError::KeyBindingMismatch(expected.to_string())),
+            None if self.required => Err(SealError::UnboundAttribute(expected.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_descriptor() {
+        let binding = key_binding("pk", "sk");
+        let descriptor = bind_descriptor("user/name.first", &binding);
+
+        assert_eq!(
+            split_descriptor(&descriptor),
+            ("user/name.first", Some(binding.as_str()))
+        );
+        assert_eq!(split_descriptor("user/name"), ("user/name", None));
+    }
+
+    #[test]
+    fn test_key_binding_is_unambiguous() {
+        assert_ne!(key_binding("ab", "c"), key_binding("a", "bc"));
+    }
+
+    #[test]
+    fn test_verify() {
+        let expected = ExpectedBinding::new("pk", "sk", KeyBinding::Disabled);
+        let bound = bind_descriptor("user/name", &key_binding("pk", "sk"));
+        let other = bind_descriptor("user/name", &key_binding("pk", "other"));
+
+        assert!(expected.verify(&bound, "user/name").is_ok());
+        assert!(expected.verify("user/name", "user/name").is_ok());
+        assert!(matches!(
+            expected.verify(&bound, "user/email"),
+            Err(SealError::DescriptorMismatch { found, .. }) if found == "user/name"
+        ));
+        assert!(matches!(
+            expected.verify(&other, "user/name"),
+            Err(SealError::KeyBindingMismatch(_))
+        ));
+
+        let required = ExpectedBinding::new("pk", "sk", KeyBinding::Required);
+        assert!(required.verify(&bound, "user/name").is_ok());
+        assert!(matches!(
+            required.verify("user/name", "user/name"),
+            Err(SealError::UnboundAttribute(_))
+        ));
+    }
+}
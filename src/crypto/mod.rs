@@ -1,5 +1,8 @@
 mod attrs;
 mod b64_encode;
+mod json;
+mod key_binding;
+mod record_mac;
 mod sealed;
 mod sealer;
 mod unsealed;
@@ -17,6 +20,9 @@ use thiserror::Error;
 
 // Re-exports
 pub use b64_encode::*;
+pub(crate) use json::{json_to_table_attribute, plaintext_map_to_json, table_attribute_to_json};
+pub(crate) use key_binding::{key_binding, ExpectedBinding};
+pub(crate) use record_mac::{record_mac, verify_record_mac};
 pub use sealed::{SealedTableEntry, UnsealSpec};
 pub use sealer::{Sealer, UnsealedIndex};
 pub use unsealed::Unsealed;
@@ -26,6 +32,20 @@ pub use unsealed::Unsealed;
 /// delete all index terms for a particular record.
 const MAX_TERMS_PER_INDEX: usize = 25;
 
+/// The plaintext attribute written to every root and term item that holds the schema version of
+/// the record, see [`crate::Encryptable::schema_version`].
+pub const VERSION_ATTRIBUTE: &str = "__v";
+
+/// The plaintext attribute written to term items that holds the sort key of the root item of their
+/// record, which their encrypted attributes are bound to, see [`crate::traits::KeyBinding`]. It is
+/// covered by the [`MAC_ATTRIBUTE`] of the term item and ignored on items without a valid MAC.
+pub const RECORD_SORT_KEY_ATTRIBUTE: &str = "__rsk";
+
+/// The plaintext attribute that holds the MAC of the other attributes and the primary key of a root
+/// or term item. It is checked when the item is decrypted so that changes to plaintext attributes
+/// and moves to another key are detected.
+pub const MAC_ATTRIBUTE: &str = "__mac";
+
 #[derive(Debug, Error, Diagnostic)]
 pub enum SealError {
     #[error("Error when creating primary key: {0}")]
@@ -38,11 +58,50 @@ pub enum SealError {
     TypeParseError(#[from] TypeParseError),
     #[error("Missing attribute: {0}")]
     MissingAttribute(String),
+    #[error("Unknown attribute: {0}")]
+    UnknownAttribute(String),
     #[error("Invalid ciphertext value: {0}")]
     InvalidCiphertext(String),
     #[error("Assertion failed: {0}")]
     AssertionFailed(String),
 
+    // Diagnostic codes are grouped so that errors can be told apart when alerting:
+    // `tampering` errors mean a stored record was changed outside of this crate, `crypto` errors
+    // are failures of ZeroKMS and `storage` errors are records that can't be read as expected.
+    /// ZeroKMS failed to decrypt the attributes, for example because it couldn't be reached or
+    /// the client isn't allowed to use the key of the dataset.
+    #[error("Failed to decrypt {attribute}: {source}")]
+    #[diagnostic(code(cipherstash_dynamodb::crypto::decryption_failed))]
+    DecryptionFailed {
+        /// The descriptors of the attributes being decrypted.
+        attribute: String,
+        #[source]
+        source: zerokms::Error,
+    },
+    #[error("Expected descriptor {expected}, found {found} - WARNING: record may have been tampered with")]
+    #[diagnostic(code(cipherstash_dynamodb::tampering::descriptor_mismatch))]
+    DescriptorMismatch { expected: String, found: String },
+    #[error("Attribute {0} is bound to a different record - WARNING: record may have been tampered with")]
+    #[diagnostic(code(cipherstash_dynamodb::tampering::key_binding_mismatch))]
+    KeyBindingMismatch(String),
+    #[error("Attribute {0} is not bound to its record but the type requires a key binding")]
+    #[diagnostic(code(cipherstash_dynamodb::tampering::unbound_attribute))]
+    UnboundAttribute(String),
+    #[error("Plaintext attributes failed the integrity check - WARNING: record may have been tampered with")]
+    #[diagnostic(code(cipherstash_dynamodb::tampering::plaintext_tampered))]
+    PlaintextTampered,
+    /// An encrypted attribute couldn't be parsed, either before or after it was decrypted.
+    #[error("Malformed ciphertext in attribute {0}")]
+    #[diagnostic(code(cipherstash_dynamodb::storage::malformed_ciphertext))]
+    MalformedCiphertext(String),
+    /// An encrypted attribute is stored as a type that can't hold ciphertext.
+    #[error("Attribute {attribute} is stored as {found} but encrypted attributes must be bytes or a map")]
+    #[diagnostic(code(cipherstash_dynamodb::storage::unsupported_stored_type))]
+    UnsupportedStoredType {
+        attribute: String,
+        found: &'static str,
+    },
+
     #[error(transparent)]
     CryptoError(#[from] zerokms::Error),
 
@@ -0,0 +1,223 @@
+use super::{b64_encode, SealError};
+use crate::encrypted_table::{ScopedZeroKmsCipher, TableAttribute, TableAttributes};
+use std::fmt::Write;
+
+/// Prefixes the input of the MAC so that it can't collide with the other values MACed with the
+/// scoped cipher, such as sort keys and index terms. `v1` is the version of the canonical format.
+const RECORD_MAC_CONTEXT: &str = "record-mac/v1";
+
+/// Returns the MAC of the `attributes` of the item stored with `pk` and `sk`.
+///
+/// The MAC covers the encrypted attributes as well as the plaintext ones so that it can be checked
+/// without knowing which attributes were encrypted when the record was written, which changes as
+/// fields are added to and removed from a type.
+///
+/// The attributes are canonicalised first so that the MAC doesn't depend on the order of map
+/// entries or sets, or on how DynamoDB formats numbers.
+pub(crate) fn record_mac(
+    cipher: &ScopedZeroKmsCipher,
+    pk: &str,
+    sk: &str,
+    attributes: &TableAttributes,
+) -> String {
+    b64_encode(cipher.mac::<32>(&canonicalise(pk, sk, attributes), Some(pk)))
+}
+
+/// Check that `mac` is the MAC of the `attributes` of the item stored with `pk` and `sk`.
+pub(crate) fn verify_record_mac(
+    cipher: &ScopedZeroKmsCipher,
+    pk: &str,
+    sk: &str,
+    attributes: &TableAttributes,
+    mac: &str,
+) -> Result<(), SealError> {
+    let expected = record_mac(cipher, pk, sk, attributes);
+
+    // Compare every byte so that the time taken doesn't reveal how much of the MAC matched
+    let matches = expected.len() == mac.len()
+        && expected
+            .bytes()
+            .zip(mac.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+
+    if matches {
+        Ok(())
+    } else {
+        Err(SealError::PlaintextTampered)
+    }
+}
+
+fn canonicalise(pk: &str, sk: &str, attributes: &TableAttributes) -> String {
+    let mut out = String::from(RECORD_MAC_CONTEXT);
+    write_str(&mut out, pk);
+    write_str(&mut out, sk);
+
+    let attributes = attributes
+        .iter()
+        .map(|(name, value)| (name.as_stored_name(), value))
+        .collect::<std::collections::BTreeMap<_, _>>();
+
+    write!(out, "{}", attributes.len()).unwrap();
+    for (name, value) in attributes {
+        write_str(&mut out, name);
+        write_attribute(&mut out, value);
+    }
+
+    out
+}
+
+/// Strings are prefixed with their length so that the boundaries between values are unambiguous.
+fn write_str(out: &mut String, value: &str) {
+    write!(out, "{}:{}", value.len(), value).unwrap();
+}
+
+fn write_attribute(out: &mut String, value: &TableAttribute) {
+    // Sets are unordered so their members are sorted
+    let write_set = |out: &mut String, tag: &str, mut members: Vec<String>| {
+        members.sort();
+        write!(out, "{tag}{}", members.len()).unwrap();
+        members.iter().for_each(|member| write_str(out, member));
+    };
+
+    match value {
+        TableAttribute::String(value) => {
+            out.push('S');
+            write_str(out, value);
+        }
+        TableAttribute::Number(value) => {
+            out.push('N');
+            write_str(out, &canonical_number(value));
+        }
+        TableAttribute::Bool(value) => out.push_str(if *value { "T" } else { "F" }),
+        TableAttribute::Bytes(value) => {
+            out.push('B');
+            write_str(out, &b64_encode(value));
+        }
+        TableAttribute::StringVec(values) => write_set(out, "SS", values.clone()),
+        TableAttribute::NumberVec(values) => write_set(
+            out,
+            "NS",
+            values.iter().map(|value| canonical_number(value)).collect(),
+        ),
+        TableAttribute::ByteVec(values) => {
+            write_set(out, "BS", values.iter().map(b64_encode).collect())
+        }
+        TableAttribute::Map(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+
+            write!(out, "M{}", entries.len()).unwrap();
+            for (key, value) in entries {
+                write_str(out, key);
+                write_attribute(out, value);
+            }
+        }
+        TableAttribute::List(values) => {
+            write!(out, "L{}", values.len()).unwrap();
+            values.iter().for_each(|value| write_attribute(out, value));
+        }
+        TableAttribute::Null => out.push('0'),
+    }
+}
+
+/// Returns `value` in the form `<sign><digits>E<exponent>` without leading or trailing zeros, so
+/// that numbers DynamoDB reformats, such as `1.50` and `1.5`, have the same canonical form.
+///
+/// Values that aren't numbers are returned unchanged.
+fn canonical_number(value: &str) -> String {
+    let (negative, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => match exponent.parse::<i64>() {
+            Ok(exponent) => (mantissa, exponent),
+            Err(_) => return value.to_string(),
+        },
+        None => (unsigned, 0),
+    };
+
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    if (integer.is_empty() && fraction.is_empty())
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return value.to_string();
+    }
+
+    let digits = format!("{integer}{fraction}");
+    let exponent = exponent - fraction.len() as i64;
+
+    let trimmed = digits.trim_start_matches('0');
+    if trimmed.is_empty() {
+        return "0".to_string();
+    }
+
+    let significant = trimmed.trim_end_matches('0');
+    let exponent = exponent + (trimmed.len() - significant.len()) as i64;
+
+    format!(
+        "{}{significant}E{exponent}",
+        if negative { "-" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_canonical_number() {
+        assert_eq!(canonical_number("1.5"), canonical_number("1.50"));
+        assert_eq!(canonical_number("150"), canonical_number("1.5E2"));
+        assert_eq!(canonical_number("0.0000001"), canonical_number("1e-7"));
+        assert_eq!(canonical_number("-0.0"), "0");
+        assert_eq!(canonical_number("+42"), canonical_number("42"));
+        assert_ne!(canonical_number("15"), canonical_number("1.5"));
+        assert_ne!(canonical_number("-1"), canonical_number("1"));
+        assert_eq!(canonical_number("not a number"), "not a number");
+    }
+
+    #[test]
+    fn test_canonicalise_is_order_independent() {
+        let map = |entries: &[(&str, &str)]| {
+            TableAttribute::Map(
+                entries
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), TableAttribute::String(v.to_string())))
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+
+        let mut a = TableAttributes::new();
+        a.insert("tag", "admin");
+        a.insert("address", map(&[("city", "Sydney"), ("country", "AU")]));
+        a.insert(
+            "roles",
+            TableAttribute::StringVec(vec!["a".to_string(), "b".to_string()]),
+        );
+
+        let mut b = TableAttributes::new();
+        b.insert(
+            "roles",
+            TableAttribute::StringVec(vec!["b".to_string(), "a".to_string()]),
+        );
+        b.insert("address", map(&[("country", "AU"), ("city", "Sydney")]));
+        b.insert("tag", "admin");
+
+        assert_eq!(canonicalise("pk", "sk", &a), canonicalise("pk", "sk", &b));
+        assert_ne!(
+            canonicalise("pk", "sk", &a),
+            canonicalise("pk", "other", &a)
+        );
+
+        b.insert("tag", "user");
+        assert_ne!(canonicalise("pk", "sk", &a), canonicalise("pk", "sk", &b));
+    }
+}
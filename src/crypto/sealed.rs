@@ -1,14 +1,20 @@
 use crate::{
     crypto::attrs::FlattenedEncryptedAttributes,
-    encrypted_table::{TableEntry, ZeroKmsCipher},
-    traits::{ReadConversionError, WriteConversionError},
+    encrypted_table::{
+        ScopedZeroKmsCipher, TableAttributes, TableEntry, TableLayout, TryFromTableAttr,
+        ZeroKmsCipher,
+    },
+    traits::{KeyBinding, ReadConversionError, RecordMac, UnknownAttributes, WriteConversionError},
     Decryptable, Identifiable,
 };
 use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
 use itertools::Itertools;
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, future::Future, pin::Pin};
 
-use super::{attrs::NormalizedProtectedAttributes, SealError, Unsealed};
+use super::{
+    attrs::NormalizedProtectedAttributes, verify_record_mac, ExpectedBinding, SealError, Unsealed,
+    MAC_ATTRIBUTE, RECORD_SORT_KEY_ATTRIBUTE, VERSION_ATTRIBUTE,
+};
 
 // FIXME: Move this to a separate file
 /// Wrapped to indicate that the value is encrypted
@@ -17,6 +23,18 @@ pub struct SealedTableEntry(pub(super) TableEntry);
 pub struct UnsealSpec<'a> {
     pub(crate) protected_attributes: Cow<'a, [Cow<'a, str>]>,
 
+    /// The plaintext attributes, used to find unknown attributes.
+    pub(crate) plaintext_attributes: Cow<'a, [Cow<'a, str>]>,
+
+    /// How attributes that are neither protected nor plaintext are handled.
+    pub(crate) unknown_attributes: UnknownAttributes,
+
+    /// Whether encrypted attributes must be bound to the primary key of their record.
+    pub(crate) key_binding: KeyBinding,
+
+    /// Whether items must have a MAC.
+    pub(crate) record_mac: RecordMac,
+
     /// The prefix used for sort keys.
     /// If None, the type name will be used.
     /// This *must* be the same as the value used when encrypting the data
@@ -30,8 +48,25 @@ impl UnsealSpec<'static> {
     where
         D: Decryptable + Identifiable,
     {
+        let legacy_protected_attributes = D::legacy_protected_attributes();
+
+        // Attributes of older versions are decrypted so that they can be upcast
+        let protected_attributes = if legacy_protected_attributes.is_empty() {
+            D::protected_attributes()
+        } else {
+            D::protected_attributes()
+                .iter()
+                .chain(legacy_protected_attributes.iter())
+                .cloned()
+                .collect()
+        };
+
         Self {
-            protected_attributes: D::protected_attributes(),
+            protected_attributes,
+            plaintext_attributes: D::plaintext_attributes(),
+            unknown_attributes: D::unknown_attributes(),
+            key_binding: D::key_binding(),
+            record_mac: D::record_mac(),
             sort_key_prefix: D::sort_key_prefix()
                 .as_deref()
                 .map(ToOwned::to_owned)
@@ -40,6 +75,14 @@ impl UnsealSpec<'static> {
     }
 }
 
+impl<'a> UnsealSpec<'a> {
+    /// Set whether items must have a MAC, in place of the setting of the type.
+    pub(crate) fn with_record_mac(mut self, record_mac: RecordMac) -> Self {
+        self.record_mac = record_mac;
+        self
+    }
+}
+
 impl SealedTableEntry {
     pub fn vec_from<O: TryInto<Self>>(
         items: impl IntoIterator<Item = O>,
@@ -61,6 +104,24 @@ impl SealedTableEntry {
         self.0
     }
 
+    /// Returns true if the entry has a MAC computed with `scoped_cipher`, which means that it was
+    /// written into the dataset of `scoped_cipher`.
+    pub(crate) fn is_sealed_with(&self, scoped_cipher: &ScopedZeroKmsCipher) -> bool {
+        let TableEntry {
+            pk, sk, attributes, ..
+        } = self.inner();
+
+        let mut attributes = attributes.clone();
+
+        match attributes
+            .remove(MAC_ATTRIBUTE)
+            .map(String::try_from_table_attr)
+        {
+            Some(Ok(mac)) => verify_record_mac(scoped_cipher, pk, sk, &attributes, &mac).is_ok(),
+            _ => false,
+        }
+    }
+
     /// Unseal a list of [`Sealed`] values in an efficient manner that optimizes for bulk
     /// decryptions
     ///
@@ -69,59 +130,108 @@ impl SealedTableEntry {
         items: Vec<Self>,
         spec: UnsealSpec<'_>,
         cipher: &ZeroKmsCipher,
+        scoped_cipher: &ScopedZeroKmsCipher,
     ) -> Result<Vec<Unsealed>, SealError> {
-        let UnsealSpec {
-            protected_attributes,
-            sort_key_prefix,
-        } = spec;
+        let split = items
+            .into_iter()
+            .map(|item| item.check(&spec, scoped_cipher)?.split(&spec))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        decrypt_split(split, cipher).await
+    }
+
+    /// Unseal a list of [`Sealed`] values, returning a result for each of them so that an item
+    /// that fails to unseal doesn't prevent the others from being read.
+    ///
+    /// The items are decrypted in one request, see [`unseal_bisect`] for how failures are
+    /// attributed to the items that caused them.
+    pub(crate) async fn unseal_each(
+        items: Vec<Self>,
+        spec: UnsealSpec<'_>,
+        cipher: &ZeroKmsCipher,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Vec<Result<Unsealed, SealError>> {
+        let mut results = Vec::with_capacity(items.len());
+        let mut checked = Vec::with_capacity(items.len());
+
+        for (index, item) in items.into_iter().enumerate() {
+            match item.check(&spec, scoped_cipher) {
+                Ok(entry) => checked.push((index, entry)),
+                Err(error) => results.push((index, Err(error))),
+            }
+        }
+
+        let (indexes, entries): (Vec<_>, Vec<_>) = checked.into_iter().unzip();
+        let unsealed = unseal_bisect(entries, &spec, cipher).await;
+        results.extend(indexes.into_iter().zip_eq(unsealed));
+
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
 
-        let items_len = items.len();
-        if items_len == 0 {
-            return Ok(Vec::new());
+    /// Verify the MAC of the entry and split its attributes into the protected and unprotected
+    /// attributes of `spec`.
+    fn check(
+        self,
+        spec: &UnsealSpec<'_>,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<CheckedEntry, SealError> {
+        let TableEntry {
+            pk,
+            sk,
+            mut attributes,
+            ..
+        } = self.into_inner();
+
+        let mac = attributes
+            .remove(MAC_ATTRIBUTE)
+            .map(String::try_from_table_attr)
+            .transpose()?;
+
+        // Every item has a MAC of its own key and attributes, except for records written before
+        // MACs were added
+        match &mac {
+            Some(mac) => verify_record_mac(scoped_cipher, &pk, &sk, &attributes, mac)?,
+            None if spec.record_mac == RecordMac::Required => {
+                return Err(SealError::PlaintextTampered)
+            }
+            None => {}
         }
 
-        let mut protected_items = {
-            let capacity = items.len() * protected_attributes.len();
-            FlattenedEncryptedAttributes::with_capacity(capacity)
+        let record_sk = attributes
+            .remove(RECORD_SORT_KEY_ATTRIBUTE)
+            .map(String::try_from_table_attr)
+            .transpose()?;
+
+        // Term items are bound to the sort key of the root item of their record, which is only
+        // trusted if the MAC shows it was written with the term item. Any other item is checked
+        // against its own key.
+        let record_sk = match (mac, record_sk) {
+            (Some(_), Some(record_sk)) => record_sk,
+            _ => sk,
         };
-        let mut unprotected_items = Vec::with_capacity(items.len());
 
-        for item in items.into_iter() {
-            let (protected, unprotected) = item
-                .into_inner()
-                .attributes
-                .partition(protected_attributes.as_ref());
+        let (protected, mut unprotected) = attributes.partition(spec.protected_attributes.as_ref());
 
-            protected_items.try_extend(protected, sort_key_prefix.clone())?;
-            unprotected_items.push(unprotected);
+        if spec.unknown_attributes == UnknownAttributes::Deny {
+            if let Some(name) = unprotected.find_unknown(spec.plaintext_attributes.as_ref()) {
+                return Err(SealError::UnknownAttribute(name.to_string()));
+            }
         }
 
-        if protected_items.is_empty() {
-            unprotected_items
-                .into_iter()
-                .map(|unprotected| Ok(Unsealed::new_from_unprotected(unprotected)))
-                .collect()
-        } else {
-            let chunk_size =
-                protected_items
-                    .len()
-                    .checked_div(items_len)
-                    .ok_or(SealError::AssertionFailed(
-                        "Division by zero when calculating chunk size".to_string(),
-                    ))?;
-
-            protected_items
-                .decrypt_all(cipher)
-                .await?
-                .into_iter()
-                // TODO: Can we make decrypt_all return a Vec of FlattenedProtectedAttributes? (like the mirror of encrypt_all)
-                .chunks(chunk_size)
-                .into_iter()
-                .map(|fpa| fpa.into_iter().collect::<NormalizedProtectedAttributes>())
-                .zip_eq(unprotected_items.into_iter())
-                .map(|(fpa, unprotected)| Ok(Unsealed::new_from_parts(fpa, unprotected)))
-                .collect()
-        }
+        let version = unprotected
+            .remove(VERSION_ATTRIBUTE)
+            .map(u32::try_from_table_attr)
+            .transpose()?
+            .unwrap_or(0);
+
+        Ok(CheckedEntry {
+            pk,
+            record_sk,
+            protected,
+            unprotected,
+            version,
+        })
     }
 
     /// Unseal the current value and return it's plaintext representation
@@ -131,8 +241,9 @@ impl SealedTableEntry {
         self,
         spec: UnsealSpec<'_>,
         cipher: &ZeroKmsCipher,
+        scoped_cipher: &ScopedZeroKmsCipher,
     ) -> Result<Unsealed, SealError> {
-        let mut vec = Self::unseal_all(vec![self], spec, cipher).await?;
+        let mut vec = Self::unseal_all(vec![self], spec, cipher, scoped_cipher).await?;
 
         if vec.len() != 1 {
             let actual = vec.len();
@@ -146,64 +257,218 @@ impl SealedTableEntry {
     }
 }
 
-impl TryFrom<HashMap<String, AttributeValue>> for SealedTableEntry {
-    type Error = ReadConversionError;
+/// An entry whose MAC has been verified, with its attributes split into the protected and
+/// unprotected attributes of an [`UnsealSpec`].
+#[derive(Clone)]
+struct CheckedEntry {
+    pk: String,
+    record_sk: String,
+    protected: TableAttributes,
+    unprotected: TableAttributes,
+    version: u32,
+}
 
-    fn try_from(item: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
-        let pk = item
-            .get("pk")
-            .ok_or(ReadConversionError::NoSuchAttribute("pk".to_string()))?
-            .as_s()
-            .map_err(|_| ReadConversionError::InvalidFormat("pk".to_string()))?
-            .to_string();
-
-        let sk = item
-            .get("sk")
-            .ok_or(ReadConversionError::NoSuchAttribute("sk".to_string()))?
-            .as_s()
-            .map_err(|_| ReadConversionError::InvalidFormat("sk".to_string()))?
-            .to_string();
+/// The encrypted attributes of an entry, and its version and unprotected attributes.
+type SplitEntry = (FlattenedEncryptedAttributes, u32, TableAttributes);
+
+impl CheckedEntry {
+    /// Read the encrypted records of the protected attributes, checking that their descriptors
+    /// match and that they are bound to the key of the entry.
+    fn split(self, spec: &UnsealSpec<'_>) -> Result<SplitEntry, SealError> {
+        let binding = ExpectedBinding::new(&self.pk, &self.record_sk, spec.key_binding);
+
+        let mut encrypted =
+            FlattenedEncryptedAttributes::with_capacity(spec.protected_attributes.len());
+        encrypted.try_extend(self.protected, spec.sort_key_prefix.clone(), &binding)?;
+
+        Ok((encrypted, self.version, self.unprotected))
+    }
+}
+
+/// The boxed future of [`unseal_bisect`], which is recursive.
+type UnsealEach<'a> = Pin<Box<dyn Future<Output = Vec<Result<Unsealed, SealError>>> + Send + 'a>>;
+
+/// Unseal checked entries in one request. If it fails, the entries are split in half and each
+/// half is unsealed again until the entries that caused the failure are found, so that every entry
+/// gets its own result without making a request per entry.
+fn unseal_bisect<'a, 'b: 'a>(
+    entries: Vec<CheckedEntry>,
+    spec: &'a UnsealSpec<'b>,
+    cipher: &'a ZeroKmsCipher,
+) -> UnsealEach<'a> {
+    Box::pin(async move {
+        let split = entries
+            .iter()
+            .cloned()
+            .map(|entry| entry.split(spec))
+            .collect::<Result<Vec<_>, _>>();
+
+        let error = match split {
+            Ok(split) => match decrypt_split(split, cipher).await {
+                Ok(unsealed) => return unsealed.into_iter().map(Ok).collect(),
+                Err(error) => error,
+            },
+            Err(error) => error,
+        };
+
+        match entries.len() {
+            0 => vec![],
+            1 => vec![Err(error)],
+            len => {
+                let mut entries = entries;
+                let second_half = entries.split_off(len / 2);
+
+                let mut results = unseal_bisect(entries, spec, cipher).await;
+                results.extend(unseal_bisect(second_half, spec, cipher).await);
+                results
+            }
+        }
+    })
+}
+
+/// Decrypt the encrypted attributes of all entries in one request.
+async fn decrypt_split(
+    items: Vec<SplitEntry>,
+    cipher: &ZeroKmsCipher,
+) -> Result<Vec<Unsealed>, SealError> {
+    let mut protected_items = {
+        let capacity = items.iter().map(|(encrypted, ..)| encrypted.len()).sum();
+        FlattenedEncryptedAttributes::with_capacity(capacity)
+    };
+    let mut unprotected_items = Vec::with_capacity(items.len());
+
+    // Records written before a field was added have fewer protected attributes so keep
+    // track of how many attributes belong to each item.
+    let mut protected_counts = Vec::with_capacity(items.len());
+
+    for (encrypted, version, unprotected) in items {
+        protected_counts.push(encrypted.len());
+        protected_items.append(encrypted);
+        unprotected_items.push((version, unprotected));
+    }
+
+    if protected_items.is_empty() {
+        unprotected_items
+            .into_iter()
+            .map(|(version, unprotected)| {
+                Ok(Unsealed::new_from_unprotected(unprotected).with_version(version))
+            })
+            .collect()
+    } else {
+        // TODO: Can we make decrypt_all return a Vec of FlattenedProtectedAttributes? (like the mirror of encrypt_all)
+        let mut decrypted = protected_items.decrypt_all(cipher).await?.into_iter();
+
+        protected_counts
+            .into_iter()
+            .zip_eq(unprotected_items)
+            .map(|(count, (version, unprotected))| {
+                let protected = decrypted
+                    .by_ref()
+                    .take(count)
+                    .collect::<NormalizedProtectedAttributes>();
+
+                Ok(Unsealed::new_from_parts(protected, unprotected).with_version(version))
+            })
+            .collect()
+    }
+}
+
+impl SealedTableEntry {
+    /// Read a [`SealedTableEntry`] from a DynamoDB item stored using the given [`TableLayout`].
+    pub fn from_item(
+        item: HashMap<String, AttributeValue>,
+        layout: &TableLayout,
+    ) -> Result<Self, ReadConversionError> {
+        let read_key = |name: &str| {
+            item.get(name)
+                .ok_or(ReadConversionError::NoSuchAttribute(name.to_string()))?
+                .as_s()
+                .map_err(|_| ReadConversionError::InvalidFormat(name.to_string()))
+                .map(ToString::to_string)
+        };
+
+        let pk = read_key(&layout.partition_key)?;
+        let sk = read_key(&layout.sort_key)?;
 
         let mut table_entry = TableEntry::new(pk, sk);
 
+        // Only term items are bound to the sort key of another item
+        let is_term_item = item.contains_key(&layout.term);
+
         // This prevents loading special columns when retrieving records
-        // pk/sk are handled specially or will be called __sk and __pk
+        // Record attributes named like a key attribute are stored with a `__` prefix
         // We never want to read term during queries
         item.into_iter()
-            .filter(|(k, _)| k != "pk" && k != "sk" && k != "term")
+            .filter(|(k, _)| !layout.is_reserved(k))
+            .filter(|(k, _)| is_term_item || k != RECORD_SORT_KEY_ATTRIBUTE)
             .for_each(|(k, v)| {
-                table_entry.add_attribute(k, v.into());
+                table_entry.add_attribute(layout.attribute_name(&k), v.into());
             });
 
         Ok(SealedTableEntry(table_entry))
     }
-}
 
-// TODO: Test this conversion
-impl TryFrom<SealedTableEntry> for HashMap<String, AttributeValue> {
-    type Error = WriteConversionError;
+    /// Read a list of DynamoDB items stored using the given [`TableLayout`].
+    pub fn vec_from_items(
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        layout: &TableLayout,
+    ) -> Result<Vec<Self>, ReadConversionError> {
+        items
+            .into_iter()
+            .map(|item| Self::from_item(item, layout))
+            .collect()
+    }
 
-    fn try_from(item: SealedTableEntry) -> Result<Self, Self::Error> {
+    /// Convert this entry into a DynamoDB item using the given [`TableLayout`].
+    pub fn into_item(
+        self,
+        layout: &TableLayout,
+    ) -> Result<HashMap<String, AttributeValue>, WriteConversionError> {
         let mut map = HashMap::new();
 
-        map.insert("pk".to_string(), AttributeValue::S(item.0.pk));
-        map.insert("sk".to_string(), AttributeValue::S(item.0.sk));
+        map.insert(layout.partition_key.clone(), AttributeValue::S(self.0.pk));
+        map.insert(layout.sort_key.clone(), AttributeValue::S(self.0.sk));
 
-        if let Some(term) = item.0.term {
-            map.insert("term".to_string(), AttributeValue::B(Blob::new(term)));
+        if let Some(term) = self.0.term {
+            map.insert(layout.term.clone(), AttributeValue::B(Blob::new(term)));
         }
 
-        item.0.attributes.into_iter().for_each(|(k, v)| {
-            map.insert(k.into_stored_name(), v.into());
-        });
+        for (k, v) in self.0.attributes.into_iter() {
+            let name = layout.stored_attribute_name(k.as_external_name());
+
+            if layout.is_reserved(&name) {
+                return Err(WriteConversionError::ReservedAttribute(name));
+            }
+
+            map.insert(name, v.into());
+        }
 
         Ok(map)
     }
 }
 
+impl TryFrom<HashMap<String, AttributeValue>> for SealedTableEntry {
+    type Error = ReadConversionError;
+
+    fn try_from(item: HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        Self::from_item(item, &TableLayout::default())
+    }
+}
+
+// TODO: Test this conversion
+impl TryFrom<SealedTableEntry> for HashMap<String, AttributeValue> {
+    type Error = WriteConversionError;
+
+    fn try_from(item: SealedTableEntry) -> Result<Self, Self::Error> {
+        item.into_item(&TableLayout::default())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::encrypted_table::ZeroKmsCipher;
+    use crate::encrypted_table::{
+        ScopedZeroKmsCipher, TableAttribute, TableEntry, TableLayout, ZeroKmsCipher,
+    };
 
     use super::SealedTableEntry;
     use cipherstash_client::{
@@ -232,14 +497,50 @@ mod tests {
         Ok(Arc::new(cipher))
     }
 
+    #[test]
+    fn test_attributes_named_like_keys_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let layout = TableLayout::new("PK", "SK", "GSI1PK", "GSI1");
+
+        let mut entry = TableEntry::new("pk".to_string(), "sk".to_string());
+        entry.add_attribute("PK", TableAttribute::String("field".to_string()));
+        entry.add_attribute("pk", TableAttribute::String("other field".to_string()));
+
+        let item = SealedTableEntry(entry).into_item(&layout)?;
+        assert_eq!(item["PK"].as_s().ok().map(String::as_str), Some("pk"));
+        assert_eq!(item["__PK"].as_s().ok().map(String::as_str), Some("field"));
+        assert_eq!(
+            item["pk"].as_s().ok().map(String::as_str),
+            Some("other field")
+        );
+
+        let attributes = SealedTableEntry::from_item(item, &layout)?
+            .into_inner()
+            .attributes;
+        assert_eq!(
+            attributes.get("PK"),
+            Some(&TableAttribute::String("field".to_string()))
+        );
+        assert_eq!(
+            attributes.get("pk"),
+            Some(&TableAttribute::String("other field".to_string()))
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_unseal_all_empty() -> Result<(), Box<dyn std::error::Error>> {
         let spec = super::UnsealSpec {
             protected_attributes: Cow::Borrowed(&[]),
+            plaintext_attributes: Cow::Borrowed(&[]),
+            unknown_attributes: Default::default(),
+            key_binding: Default::default(),
+            record_mac: Default::default(),
             sort_key_prefix: "test".to_string(),
         };
         let cipher = get_cipher().await?;
-        let results = SealedTableEntry::unseal_all(vec![], spec, &cipher)
+        let scoped_cipher = ScopedZeroKmsCipher::init(cipher.clone(), None).await?;
+        let results = SealedTableEntry::unseal_all(vec![], spec, &cipher, &scoped_cipher)
             .await
             .into_diagnostic()?;
 
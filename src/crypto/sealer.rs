@@ -1,12 +1,13 @@
 use super::{
-    attrs::FlattenedProtectedAttributes, b64_encode, format_term_key, SealError, SealedTableEntry,
-    Unsealed, MAX_TERMS_PER_INDEX,
+    attrs::FlattenedProtectedAttributes, b64_encode, format_term_key, key_binding, record_mac,
+    SealError, SealedTableEntry, Unsealed, MAC_ATTRIBUTE, MAX_TERMS_PER_INDEX,
+    RECORD_SORT_KEY_ATTRIBUTE, VERSION_ATTRIBUTE,
 };
 use crate::{
     encrypted_table::{
         AttributeName, ScopedZeroKmsCipher, TableAttribute, TableAttributes, TableEntry,
     },
-    traits::PrimaryKeyParts,
+    traits::{KeyBinding, PrimaryKeyParts},
     IndexType,
 };
 use cipherstash_client::encryption::{
@@ -34,6 +35,12 @@ pub struct Sealer {
 
     pub(crate) type_name: Cow<'static, str>,
 
+    /// The schema version stored with the record in [`VERSION_ATTRIBUTE`].
+    pub(crate) version: u32,
+
+    /// Whether the encrypted attributes are bound to the primary key of the record.
+    pub(crate) key_binding: KeyBinding,
+
     pub(crate) unsealed_indexes: Vec<UnsealedIndex>,
 
     pub(crate) unsealed: Unsealed,
@@ -74,16 +81,11 @@ impl RecordsWithTerms {
         if protected.is_empty() {
             unprotecteds
                 .into_iter()
-                .zip_eq(record_terms.into_iter())
-                .zip_eq(pksks.into_iter())
+                .zip_eq(record_terms)
+                .zip_eq(pksks)
                 .map(|record| {
                     let (attributes, terms, pksk) = flatten_tuple_3(record);
-                    Ok(Sealed {
-                        pk: pksk.pk,
-                        sk: pksk.sk,
-                        attributes,
-                        terms,
-                    })
+                    Ok(Sealed::new(pksk, attributes, terms, cipher))
                 })
                 .collect()
         } else {
@@ -91,16 +93,14 @@ impl RecordsWithTerms {
 
             encrypted
                 .into_iter()
-                .zip_eq(unprotecteds.into_iter())
-                .zip_eq(record_terms.into_iter())
-                .zip_eq(pksks.into_iter())
+                .zip_eq(unprotecteds)
+                .zip_eq(record_terms)
+                .zip_eq(pksks)
                 .map(|record| {
                     let (enc_attrs, unprotecteds, terms, pksk) = flatten_tuple_4(record);
-                    enc_attrs.denormalize().map(|protected_attrs| Sealed {
-                        pk: pksk.pk,
-                        sk: pksk.sk,
-                        attributes: unprotecteds.merge(protected_attrs),
-                        terms,
+                    enc_attrs.denormalize().map(|protected_attrs| {
+                        let attributes = unprotecteds.merge(protected_attrs);
+                        Sealed::new(pksk, attributes, terms, cipher)
                     })
                 })
                 .collect()
@@ -112,6 +112,8 @@ struct RecordWithTerms {
     pksk: PrimaryKeyParts,
     unsealed: Unsealed,
     terms: Vec<Term>,
+    /// Whether the encrypted attributes are bound to `pksk`.
+    is_bound: bool,
 }
 
 impl RecordWithTerms {
@@ -123,7 +125,13 @@ impl RecordWithTerms {
         FlattenedProtectedAttributes,
         TableAttributes,
     ) {
-        let (flattened_protected, unprotected) = self.unsealed.flatten_into_parts();
+        let (mut flattened_protected, unprotected) = self.unsealed.flatten_into_parts();
+
+        if self.is_bound {
+            flattened_protected =
+                flattened_protected.bind_to(&key_binding(&self.pksk.pk, &self.pksk.sk));
+        }
+
         (self.pksk, self.terms, flattened_protected, unprotected)
     }
 }
@@ -193,10 +201,14 @@ impl Sealer {
                     })
                     .collect::<Result<Vec<Term>, _>>()?;
 
+                let mut unsealed = sealer.unsealed;
+                unsealed.add_unprotected(VERSION_ATTRIBUTE, sealer.version);
+
                 Ok(RecordWithTerms {
                     pksk: PrimaryKeyParts { pk, sk },
-                    unsealed: sealer.unsealed,
+                    unsealed,
                     terms,
+                    is_bound: sealer.key_binding != KeyBinding::Disabled,
                 })
             })
             .try_collect()
@@ -247,6 +259,24 @@ pub struct Sealed {
 }
 
 impl Sealed {
+    /// Create a sealed record and add the [`MAC_ATTRIBUTE`] of its attributes.
+    fn new(
+        PrimaryKeyParts { pk, sk }: PrimaryKeyParts,
+        mut attributes: TableAttributes,
+        terms: Vec<Term>,
+        cipher: &ScopedZeroKmsCipher,
+    ) -> Self {
+        let mac = record_mac(cipher, &pk, &sk, &attributes);
+        attributes.insert(MAC_ATTRIBUTE, mac);
+
+        Self {
+            pk,
+            sk,
+            attributes,
+            terms,
+        }
+    }
+
     pub fn len(&self) -> usize {
         // the length of the terms plus the root entry
         self.terms.len() + 1
@@ -261,29 +291,47 @@ impl Sealed {
 
     /// Returns the root entry and the term entries for this record.
     /// `index_predicate` is used to... TODO!!!.
+    ///
+    /// The [`VERSION_ATTRIBUTE`] is always included in the term entries, along with the sort key of
+    /// the root entry in [`RECORD_SORT_KEY_ATTRIBUTE`]. Each term entry has its own
+    /// [`MAC_ATTRIBUTE`] of its key and attributes, so the sort key of the root entry can only be
+    /// trusted by an entry that was written as a term entry of the record.
     pub fn into_table_entries(
         self,
         mut index_predicate: impl FnMut(&AttributeName, &TableAttribute) -> bool,
+        cipher: &ScopedZeroKmsCipher,
     ) -> (SealedTableEntry, Vec<SealedTableEntry>) {
         let root_attributes = self.attributes;
 
-        let index_attributes: TableAttributes = root_attributes
+        let mut index_attributes: TableAttributes = root_attributes
             .clone()
             .into_iter()
-            .filter(|(name, value)| index_predicate(name, value))
+            .filter(|(name, value)| match name.as_stored_name() {
+                VERSION_ATTRIBUTE => true,
+                MAC_ATTRIBUTE => false,
+                _ => index_predicate(name, value),
+            })
             .map(|(name, value)| (name, value.clone()))
             .collect::<HashMap<_, _>>()
             .into();
 
+        index_attributes.insert(RECORD_SORT_KEY_ATTRIBUTE, self.sk.clone());
+
         let term_entries = self
             .terms
             .into_iter()
             .map(|Term { sk, value }| {
+                let mut attributes = index_attributes.clone();
+                attributes.insert(
+                    MAC_ATTRIBUTE,
+                    record_mac(cipher, &self.pk, &sk, &index_attributes),
+                );
+
                 SealedTableEntry(TableEntry::new_with_attributes(
                     self.pk.clone(),
                     sk,
                     Some(value),
-                    index_attributes.clone(),
+                    attributes,
                 ))
             })
             .collect();
@@ -1,12 +1,14 @@
 use super::{
-    attrs::{FlattenedProtectedAttributes, NormalizedProtectedAttributes},
-    SealError,
+    attrs::{FlattenedProtectedAttributes, NormalizedProtectedAttributes, NormalizedValue},
+    json, SealError,
 };
 use crate::{
-    encrypted_table::{AttributeName, TableAttribute, TableAttributes},
+    encrypted_table::{AttributeName, DynamicSchema, TableAttribute, TableAttributes},
+    traits::ReadConversionError,
     Decryptable,
 };
 use cipherstash_client::encryption::Plaintext;
+use serde_json::Value;
 use std::collections::HashMap;
 
 /// Wrapper to which values are added prior to being encrypted.
@@ -16,6 +18,9 @@ pub struct Unsealed {
     /// Protected plaintexts with their descriptors
     protected: NormalizedProtectedAttributes,
     unprotected: TableAttributes,
+
+    /// The schema version the record was stored with
+    version: u32,
 }
 
 impl Default for Unsealed {
@@ -29,6 +34,7 @@ impl Unsealed {
         Self {
             protected: NormalizedProtectedAttributes::new(),
             unprotected: Default::default(),
+            version: 0,
         }
     }
 
@@ -37,6 +43,7 @@ impl Unsealed {
         Self {
             protected: NormalizedProtectedAttributes::new_with_prefix(descriptor),
             unprotected: Default::default(),
+            version: 0,
         }
     }
 
@@ -58,6 +65,19 @@ impl Unsealed {
         unsealed
     }
 
+    /// Set the schema version the record was stored with.
+    pub(crate) fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// The schema version the record was stored with, see [`Decryptable::schema_version`].
+    ///
+    /// Records stored before versions were recorded have version 0.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     #[deprecated(since = "0.7.3", note = "Use `Unsealed::take_unprotected` instead")]
     pub fn get_plaintext(&self, name: impl Into<AttributeName>) -> TableAttribute {
         self.unprotected
@@ -90,6 +110,18 @@ impl Unsealed {
             .insert_and_update_map(name, subkey, value.into());
     }
 
+    /// Add a new protected attribute at the descriptor `path`.
+    ///
+    /// A path like `address.city` is stored as the field `city` of the protected map `address`.
+    /// Paths with more than one `.` keep the rest of the path as the map field so
+    /// `address.geo.lat` is stored as the field `geo.lat` of `address`.
+    pub fn add_protected_path(&mut self, path: &str, plaintext: impl Into<Plaintext>) {
+        match path.split_once('.') {
+            Some((name, subkey)) => self.add_protected_map_field(name, subkey, plaintext),
+            None => self.add_protected(path, plaintext),
+        }
+    }
+
     /// Add a new unprotected attribute, `name`, with the given plaintext.
     pub fn add_unprotected(
         &mut self,
@@ -114,6 +146,15 @@ impl Unsealed {
         self.protected.take(name)
     }
 
+    /// Removes and returns the protected attribute at the descriptor `path`.
+    /// See [Unsealed::add_protected_path].
+    pub fn take_protected_path(&mut self, path: &str) -> Option<Plaintext> {
+        match path.split_once('.') {
+            Some((name, subkey)) => self.protected.take_map_field(name, subkey),
+            None => self.protected.take(path),
+        }
+    }
+
     /// Removes and returns the map stored in the protected attributes, `name`.
     /// The caller can convert to whatever type they need.
     pub fn take_protected_map(&mut self, name: &str) -> Option<HashMap<String, Plaintext>> {
@@ -125,10 +166,80 @@ impl Unsealed {
         (self.protected.flatten(), self.unprotected)
     }
 
+    /// Convert the protected and unprotected attributes into a JSON object.
+    ///
+    /// Protected maps become nested objects. Dates, timestamps and decimals are converted to
+    /// strings and bytes to base64 strings.
+    pub fn to_json(&self) -> Value {
+        let protected = self.protected.iter().map(|(name, value)| {
+            let value = match value {
+                NormalizedValue::Scalar(plaintext) => json::plaintext_to_json(plaintext),
+                NormalizedValue::Map(map) => json::plaintext_map_to_json(map),
+            };
+
+            (name.to_string(), value)
+        });
+
+        let unprotected = self.unprotected.iter().map(|(name, attribute)| {
+            (
+                name.as_external_name().to_string(),
+                json::table_attribute_to_json(attribute),
+            )
+        });
+
+        Value::Object(protected.chain(unprotected).collect())
+    }
+
+    /// Create an [`Unsealed`] for the type described by `schema` from a JSON object.
+    ///
+    /// Objects in protected attributes become protected maps, `null` protected values are
+    /// skipped and attributes that aren't part of the schema are ignored.
+    pub fn from_json(value: Value, schema: &DynamicSchema) -> Result<Self, SealError> {
+        let Value::Object(mut object) = value else {
+            return Err(
+                ReadConversionError::InvalidFormat("Expected a JSON object".to_string()).into(),
+            );
+        };
+
+        let mut unsealed = Self::new_with_descriptor(schema.descriptor_prefix());
+
+        for name in schema.protected_attributes() {
+            match object.remove(name.as_ref()) {
+                Some(Value::Object(map)) => {
+                    unsealed.add_protected_map(name, json::json_to_plaintext_map(map))
+                }
+                Some(value) => {
+                    if let Some(plaintext) = json::json_to_plaintext(value) {
+                        unsealed.add_protected(name, plaintext);
+                    }
+                }
+                None => {}
+            }
+        }
+
+        for name in schema.plaintext_attributes() {
+            if let Some(value) = object.remove(name.as_ref()) {
+                unsealed.add_unprotected(name.as_ref(), json::json_to_table_attribute(value));
+            }
+        }
+
+        Ok(unsealed)
+    }
+
     /// Convert `self` into `T` using the attributes stored in `self`.
     /// The [Decryptable] trait must be implemented for `T` and this method calls [Decryptable::from_unsealed].
+    ///
+    /// Records stored with an older schema version are first passed to [Decryptable::upcast_from].
     pub fn into_value<T: Decryptable>(self) -> Result<T, SealError> {
-        T::from_unsealed(self)
+        let version = self.version;
+
+        let unsealed = if version < T::schema_version() {
+            T::upcast_from(version, self)?
+        } else {
+            self
+        };
+
+        T::from_unsealed(unsealed)
     }
 }
 
@@ -209,6 +320,31 @@ mod tests {
         assert_eq!(nested["c"], Plaintext::from("value-c"));
     }
 
+    #[test]
+    fn test_protected_path() {
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected_path("name", "value");
+        unsealed.add_protected_path("address.city", "Sydney");
+        unsealed.add_protected_path("address.geo.lat", "-33.8");
+
+        assert_eq!(
+            unsealed.take_protected_path("name"),
+            Some(Plaintext::from("value"))
+        );
+        assert_eq!(
+            unsealed.take_protected_path("address.geo.lat"),
+            Some(Plaintext::from("-33.8"))
+        );
+        assert_eq!(unsealed.take_protected_path("address.geo.lng"), None);
+        assert_eq!(
+            unsealed.take_protected_path("address.city"),
+            Some(Plaintext::from("Sydney"))
+        );
+
+        // The map is removed once all of its fields have been taken
+        assert!(unsealed.take_protected_map("address").is_none());
+    }
+
     #[test]
     #[should_panic]
     fn test_protected_map_override() {
@@ -226,4 +362,59 @@ mod tests {
         let attribute = unsealed.take_unprotected("test");
         assert!(attribute == "value".into(), "values do not match");
     }
+
+    #[test]
+    fn test_to_json() {
+        let mut unsealed = Unsealed::new_with_descriptor("test");
+        unsealed.add_protected("name", "Dan");
+        unsealed.add_protected("age", 42i32);
+        unsealed.add_protected_map_field("attrs", "a", "value-a");
+        unsealed.add_unprotected("tag", "admin");
+        unsealed.add_unprotected("pk", "123");
+
+        assert_eq!(
+            unsealed.to_json(),
+            serde_json::json!({
+                "name": "Dan",
+                "age": 42,
+                "attrs": { "a": "value-a" },
+                "tag": "admin",
+                "pk": "123",
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_json() {
+        let schema = DynamicSchema::new("user", "email")
+            .protected("attrs")
+            .plaintext("tag");
+
+        let value = serde_json::json!({
+            "email": "dan@coderdan.co",
+            "attrs": { "a": "value-a" },
+            "tag": "admin",
+            "unknown": "ignored",
+        });
+
+        let mut unsealed = Unsealed::from_json(value, &schema).unwrap();
+
+        assert_eq!(
+            unsealed.take_protected("email"),
+            Some(Plaintext::from("dan@coderdan.co"))
+        );
+        assert_eq!(
+            unsealed.take_protected_map("attrs").unwrap()["a"],
+            Plaintext::from("value-a")
+        );
+        assert!(unsealed.take_unprotected("tag") == "admin".into());
+        assert!(unsealed.take_unprotected("unknown") == TableAttribute::Null);
+    }
+
+    #[test]
+    fn test_from_json_requires_object() {
+        let schema = DynamicSchema::new("user", "email");
+
+        assert!(Unsealed::from_json(serde_json::json!(["a"]), &schema).is_err());
+    }
 }
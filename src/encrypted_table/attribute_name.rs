@@ -1,6 +1,10 @@
-/// Represents the name of an attribute for storage in the database.
-/// For the most part, this is just a `String`, but it ensures that special columns
-/// like `pk` and `sk` are stored in the database as `__pk` and `__sk` respectively.
+/// Represents the name of an attribute of a record.
+/// For the most part, this is just a `String`, but it ensures that attributes named `pk` and `sk`
+/// are known as `__pk` and `__sk` when encrypted, so that their ciphertexts don't depend on the
+/// [`TableLayout`](super::TableLayout) of the table.
+///
+/// The name of the DynamoDB attribute a record attribute is stored under is given by
+/// [`TableLayout::stored_attribute_name`](super::TableLayout::stored_attribute_name).
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AttributeName(String);
 
@@ -10,14 +14,14 @@ impl AttributeName {
         Self(to_inner_pksk(name.into()))
     }
 
-    /// Returns the name of the attribute as it should be stored in the database.
-    /// For example, `pk` will be stored as `__pk`.
+    /// Returns the name of the attribute used when it is encrypted.
+    /// For example, `pk` will be encrypted as `__pk`.
     pub fn as_stored_name(&self) -> &str {
         to_inner_pksk_ref(&self.0)
     }
 
-    /// Returns the name of the attribute as it should be stored in the database.
-    /// For example, `pk` will be stored as `__pk`.
+    /// Returns the name of the attribute used when it is encrypted.
+    /// For example, `pk` will be encrypted as `__pk`.
     pub fn into_stored_name(self) -> String {
         to_inner_pksk(self.0)
     }
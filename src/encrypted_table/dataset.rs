@@ -0,0 +1,80 @@
+use super::{DatasetId, Dynamo, EncryptedTable, QueryBuilder};
+use crate::{
+    errors::{DeleteError, GetError, PutError},
+    Decryptable, Error, Identifiable, Searchable,
+};
+
+/// A handle to the records of one dataset of a table, created with [`EncryptedTable::dataset`].
+///
+/// Each operation gets the cipher of the dataset from the cache of the table, the same as the
+/// `_via` methods of [`EncryptedTable`], so the handle follows the TTL of the cache and
+/// [`EncryptedTable::invalidate_scoped_cipher`].
+pub struct Dataset<'a> {
+    table: &'a EncryptedTable<Dynamo>,
+    dataset_id: DatasetId,
+}
+
+impl EncryptedTable<Dynamo> {
+    /// Get a handle to the records of a dataset, creating the cipher of the dataset if it isn't
+    /// cached by the table.
+    ///
+    /// ```no_run
+    /// # use cipherstash_dynamodb::*;
+    /// # #[derive(Debug, Encryptable, Decryptable, Identifiable, Searchable)]
+    /// # struct User { #[partition_key] email: String, #[cipherstash(query = "exact")] name: String }
+    /// # async fn example(table: EncryptedTable, tenant_id: uuid::Uuid) -> Result<(), Box<dyn std::error::Error>> {
+    /// let tenant = table.dataset(tenant_id).await?;
+    ///
+    /// let user: Option<User> = tenant.get("dan@coderdan.co").await?;
+    /// let users: Vec<User> = tenant.query().eq("name", "Dan Draper").send().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn dataset(&self, dataset_id: DatasetId) -> Result<Dataset<'_>, Error> {
+        self.scoped_cipher(Some(dataset_id)).await?;
+
+        Ok(Dataset {
+            table: self,
+            dataset_id,
+        })
+    }
+}
+
+impl<'a> Dataset<'a> {
+    /// The id of the dataset.
+    pub fn id(&self) -> DatasetId {
+        self.dataset_id
+    }
+
+    /// Get a record from the dataset by primary key.
+    pub async fn get<T>(&self, k: impl Into<T::PrimaryKey>) -> Result<Option<T>, GetError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.table.get_via(k, self.dataset_id).await
+    }
+
+    /// Put a record into the dataset.
+    pub async fn put<T>(&self, record: T) -> Result<(), PutError>
+    where
+        T: Searchable + Identifiable,
+    {
+        self.table.put_via(record, self.dataset_id).await
+    }
+
+    /// Delete a record from the dataset by primary key.
+    pub async fn delete<E: Searchable + Identifiable>(
+        &self,
+        k: impl Into<E::PrimaryKey>,
+    ) -> Result<(), DeleteError> {
+        self.table.delete_via::<E>(k, self.dataset_id).await
+    }
+
+    /// Query records in the dataset.
+    pub fn query<S>(&self) -> QueryBuilder<S, &'a EncryptedTable<Dynamo>>
+    where
+        S: Searchable,
+    {
+        QueryBuilder::with_backend(self.table).via(self.dataset_id)
+    }
+}
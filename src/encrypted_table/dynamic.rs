@@ -0,0 +1,1016 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use cipherstash_client::encryption::{
+    compound_indexer::{ComposableIndex, ComposablePlaintext},
+    Plaintext, TryFromPlaintext,
+};
+
+use super::{
+    query::PreparedQueryBuilder, DatasetId, Dynamo, EncryptedTable, PreparedDelete, PreparedRecord,
+    TableAttribute, TryFromTableAttr,
+};
+use crate::{
+    crypto::{PreparedPrimaryKey, SealError, Sealer, UnsealSpec, Unsealed},
+    errors::{DeleteError, GetError, PutError, QueryError},
+    traits::{
+        AttributeMode, AttributeSchema, IndexSchema, KeyBinding, KeySchema, PrimaryKeyError,
+        PrimaryKeyParts, RecordMac, TypeSchema, UnknownAttributes,
+    },
+    IndexType, Pk, PkSk, SingleIndex,
+};
+
+/// A description of a record type that is only known at runtime, for example from config.
+///
+/// A [`DynamicSchema`] provides the information that the [`crate::Identifiable`],
+/// [`crate::Encryptable`], [`crate::Searchable`] and [`crate::Decryptable`] traits provide for a
+/// derived type, and is used with [`DynamicRecord`] in place of those traits.
+///
+/// Key fields are stored as protected attributes unless they are added with
+/// [`DynamicSchema::plaintext`], and fields used in an index are always protected.
+///
+/// The schema version, key binding and handling of unknown attributes default to those of a
+/// derived type without container attributes. Set them to match a derived type that uses
+/// `version`, `key_binding` or `deny_unknown_attributes` so that both can read each other's
+/// records.
+///
+/// ```
+/// # use cipherstash_dynamodb::{encrypted_table::DynamicSchema, SingleIndex};
+/// let schema = DynamicSchema::new("user", "email")
+///     .sort_key("created_at")
+///     .plaintext("created_at")
+///     .query("name", SingleIndex::Prefix)
+///     .compound_query(("email", SingleIndex::Exact), ("name", SingleIndex::Prefix))
+///     .protected("phone");
+///
+/// assert!(schema.is_pk_encrypted());
+/// assert!(!schema.is_sk_encrypted());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicSchema {
+    type_name: String,
+    sort_key_prefix: Option<String>,
+    partition_key: String,
+    sort_key: Option<String>,
+    protected_attributes: Vec<Cow<'static, str>>,
+    plaintext_attributes: Vec<Cow<'static, str>>,
+    indexes: Vec<(Cow<'static, str>, IndexType)>,
+    version: u32,
+    key_binding: KeyBinding,
+    unknown_attributes: UnknownAttributes,
+    record_mac: RecordMac,
+}
+
+impl DynamicSchema {
+    /// Describe a type named `type_name` which uses the field `partition_key` as its partition
+    /// key.
+    ///
+    /// The sort key prefix defaults to the type name, as it does for derived types.
+    pub fn new(type_name: impl Into<String>, partition_key: impl Into<String>) -> Self {
+        let type_name = type_name.into();
+
+        Self {
+            sort_key_prefix: Some(type_name.clone()),
+            type_name,
+            partition_key: partition_key.into(),
+            sort_key: None,
+            protected_attributes: vec![],
+            plaintext_attributes: vec![],
+            indexes: vec![],
+            version: 0,
+            key_binding: KeyBinding::default(),
+            unknown_attributes: UnknownAttributes::default(),
+            record_mac: RecordMac::Optional,
+        }
+    }
+
+    /// Use the field `field` as the sort key.
+    pub fn sort_key(mut self, field: impl Into<String>) -> Self {
+        self.sort_key = Some(field.into());
+        self
+    }
+
+    /// Set the prefix added to sort keys.
+    pub fn sort_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.sort_key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Store sort keys without a prefix.
+    pub fn without_sort_key_prefix(mut self) -> Self {
+        self.sort_key_prefix = None;
+        self
+    }
+
+    /// Set the schema version stored with every record, like `#[cipherstash(version = N)]`.
+    ///
+    /// Records are read whatever version they were stored with.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set whether encrypted attributes are bound to the primary key of their record, see
+    /// [`KeyBinding`].
+    pub fn key_binding(mut self, key_binding: KeyBinding) -> Self {
+        self.key_binding = key_binding;
+        self
+    }
+
+    /// Set how attributes that aren't part of the schema are handled when a record is
+    /// decrypted, see [`UnknownAttributes`].
+    pub fn unknown_attributes(mut self, unknown_attributes: UnknownAttributes) -> Self {
+        self.unknown_attributes = unknown_attributes;
+        self
+    }
+
+    /// Set whether items must have a MAC when they are decrypted, see [`RecordMac`].
+    pub fn record_mac(mut self, record_mac: RecordMac) -> Self {
+        self.record_mac = record_mac;
+        self
+    }
+
+    /// Add an encrypted attribute.
+    pub fn protected(mut self, name: impl Into<String>) -> Self {
+        self.add_attribute(name.into(), AttributeMode::Protected);
+        self
+    }
+
+    /// Add an attribute that is stored in plaintext.
+    pub fn plaintext(mut self, name: impl Into<String>) -> Self {
+        self.add_attribute(name.into(), AttributeMode::Plaintext);
+        self
+    }
+
+    /// Add an index on a single field.
+    pub fn query(mut self, field: impl Into<String>, index: SingleIndex) -> Self {
+        let field = field.into();
+
+        self.add_attribute(field.clone(), AttributeMode::Protected);
+        self.indexes
+            .push((Cow::Owned(field), IndexType::Single(index)));
+        self
+    }
+
+    /// Add a compound index on two fields.
+    pub fn compound_query(
+        mut self,
+        (field_a, index_a): (impl Into<String>, SingleIndex),
+        (field_b, index_b): (impl Into<String>, SingleIndex),
+    ) -> Self {
+        let field_a = field_a.into();
+        let field_b = field_b.into();
+        let index_name = format!("{field_a}#{field_b}");
+
+        self.add_attribute(field_a, AttributeMode::Protected);
+        self.add_attribute(field_b, AttributeMode::Protected);
+        self.indexes.push((
+            Cow::Owned(index_name),
+            IndexType::Compound2((index_a, index_b)),
+        ));
+        self
+    }
+
+    fn add_attribute(&mut self, name: String, mode: AttributeMode) {
+        let (attributes, others) = match mode {
+            AttributeMode::Protected => (
+                &mut self.protected_attributes,
+                &mut self.plaintext_attributes,
+            ),
+            AttributeMode::Plaintext => (
+                &mut self.plaintext_attributes,
+                &mut self.protected_attributes,
+            ),
+        };
+
+        // An attribute can only have one mode so the last one wins
+        others.retain(|x| x != &name);
+
+        if !attributes.iter().any(|x| x == &name) {
+            attributes.push(Cow::Owned(name));
+        }
+    }
+
+    fn has_index(&self, index_name: &str, index_type: IndexType) -> bool {
+        self.indexes
+            .iter()
+            .any(|(name, t)| name == index_name && *t == index_type)
+    }
+
+    fn is_protected(&self, name: &str) -> bool {
+        self.protected_attributes.iter().any(|x| x == name)
+    }
+
+    /// The mode of a key field. Key fields that weren't added as attributes are protected.
+    fn key_mode(&self, field: &str) -> AttributeMode {
+        if self.plaintext_attributes.iter().any(|x| x == field) {
+            AttributeMode::Plaintext
+        } else {
+            AttributeMode::Protected
+        }
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn get_sort_key_prefix(&self) -> Option<&str> {
+        self.sort_key_prefix.as_deref()
+    }
+
+    pub fn partition_key(&self) -> &str {
+        &self.partition_key
+    }
+
+    pub fn get_sort_key(&self) -> Option<&str> {
+        self.sort_key.as_deref()
+    }
+
+    pub fn is_pk_encrypted(&self) -> bool {
+        self.key_mode(&self.partition_key) == AttributeMode::Protected
+    }
+
+    pub fn is_sk_encrypted(&self) -> bool {
+        self.sort_key
+            .as_deref()
+            .map(|field| self.key_mode(field) == AttributeMode::Protected)
+            .unwrap_or(true)
+    }
+
+    /// The encrypted attributes, including protected key fields.
+    pub fn protected_attributes(&self) -> Vec<Cow<'static, str>> {
+        let mut attributes = self.protected_attributes.clone();
+
+        for field in self.key_fields() {
+            if self.key_mode(field) == AttributeMode::Protected && !self.is_protected(field) {
+                attributes.push(Cow::Owned(field.to_string()));
+            }
+        }
+
+        attributes
+    }
+
+    pub fn plaintext_attributes(&self) -> &[Cow<'static, str>] {
+        &self.plaintext_attributes
+    }
+
+    pub fn protected_indexes(&self) -> &[(Cow<'static, str>, IndexType)] {
+        &self.indexes
+    }
+
+    /// Returns the indexer for an index of this type.
+    pub fn index_by_name(
+        &self,
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Option<Box<dyn ComposableIndex + Send>> {
+        self.has_index(index_name, index_type)
+            .then(|| index_type.indexer())
+    }
+
+    /// Returns a [`TypeSchema`] that can be registered with a [`super::TypeRegistry`] or
+    /// published like the schema of a derived type.
+    pub fn type_schema(&self) -> TypeSchema {
+        let attributes = self
+            .protected_attributes()
+            .into_iter()
+            .map(|name| AttributeSchema {
+                name,
+                mode: AttributeMode::Protected,
+            })
+            .chain(
+                self.plaintext_attributes
+                    .iter()
+                    .map(|name| AttributeSchema {
+                        name: name.clone(),
+                        mode: AttributeMode::Plaintext,
+                    }),
+            )
+            .collect();
+
+        TypeSchema {
+            type_name: Cow::Owned(self.type_name.clone()),
+            sort_key_prefix: self.sort_key_prefix.clone().map(Cow::Owned),
+            partition_key: KeySchema {
+                field: Some(Cow::Owned(self.partition_key.clone())),
+                encrypted: self.is_pk_encrypted(),
+            },
+            sort_key: self.sort_key.as_ref().map(|field| KeySchema {
+                field: Some(Cow::Owned(field.clone())),
+                encrypted: self.is_sk_encrypted(),
+            }),
+            attributes,
+            indexes: self
+                .indexes
+                .iter()
+                .map(|(name, index_type)| IndexSchema::new(name.clone(), *index_type))
+                .collect(),
+            skipped: vec![],
+        }
+    }
+
+    /// Convert a key into [`PrimaryKeyParts`].
+    ///
+    /// Returns an error if the key doesn't include a sort key when the schema has one, or the
+    /// other way around.
+    pub fn primary_key_parts(
+        &self,
+        key: impl Into<DynamicKey>,
+    ) -> Result<PrimaryKeyParts, PrimaryKeyError> {
+        match (key.into(), &self.sort_key) {
+            (DynamicKey::Pk(pk), None) => Ok(PrimaryKeyParts {
+                pk,
+                sk: self.type_name.clone(),
+            }),
+            (DynamicKey::PkSk(pk, sk), Some(_)) => Ok(PrimaryKeyParts {
+                pk,
+                sk: match &self.sort_key_prefix {
+                    Some(prefix) => format!("{prefix}#{sk}"),
+                    None => sk,
+                },
+            }),
+            (DynamicKey::Pk(_), Some(field)) => Err(PrimaryKeyError::Unknown(format!(
+                "Type '{}' requires a value for sort key '{field}'",
+                self.type_name
+            ))),
+            (DynamicKey::PkSk(..), None) => Err(PrimaryKeyError::Unknown(format!(
+                "Type '{}' does not have a sort key",
+                self.type_name
+            ))),
+        }
+    }
+
+    fn prepared_primary_key(
+        &self,
+        key: impl Into<DynamicKey>,
+    ) -> Result<PreparedPrimaryKey, PrimaryKeyError> {
+        Ok(PreparedPrimaryKey {
+            primary_key_parts: self.primary_key_parts(key)?,
+            is_pk_encrypted: self.is_pk_encrypted(),
+            is_sk_encrypted: self.is_sk_encrypted(),
+        })
+    }
+
+    /// The prefix of the descriptors of encrypted attributes.
+    ///
+    /// The same value is used when encrypting and decrypting so that descriptors always match.
+    pub(crate) fn descriptor_prefix(&self) -> &str {
+        self.sort_key_prefix.as_deref().unwrap_or(&self.type_name)
+    }
+
+    pub(crate) fn unseal_spec(&self) -> UnsealSpec<'static> {
+        UnsealSpec {
+            protected_attributes: Cow::Owned(self.protected_attributes()),
+            plaintext_attributes: Cow::Owned(self.plaintext_attributes.clone()),
+            unknown_attributes: self.unknown_attributes,
+            key_binding: self.key_binding,
+            record_mac: self.record_mac,
+            sort_key_prefix: self.descriptor_prefix().to_string(),
+        }
+    }
+
+    fn key_fields(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.partition_key.as_str()).chain(self.sort_key.as_deref())
+    }
+}
+
+/// The primary key of a [`DynamicRecord`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynamicKey {
+    Pk(String),
+    PkSk(String, String),
+}
+
+impl From<Pk> for DynamicKey {
+    fn from(Pk(pk): Pk) -> Self {
+        Self::Pk(pk)
+    }
+}
+
+impl From<PkSk> for DynamicKey {
+    fn from(PkSk(pk, sk): PkSk) -> Self {
+        Self::PkSk(pk, sk)
+    }
+}
+
+impl From<&str> for DynamicKey {
+    fn from(pk: &str) -> Self {
+        Self::Pk(pk.to_string())
+    }
+}
+
+impl From<String> for DynamicKey {
+    fn from(pk: String) -> Self {
+        Self::Pk(pk)
+    }
+}
+
+impl<P: Into<String>, S: Into<String>> From<(P, S)> for DynamicKey {
+    fn from((pk, sk): (P, S)) -> Self {
+        Self::PkSk(pk.into(), sk.into())
+    }
+}
+
+/// A record of a type described by a [`DynamicSchema`].
+///
+/// Protected attributes are held as [`Plaintext`] and plaintext attributes as
+/// [`TableAttribute`].
+///
+/// ```
+/// # use cipherstash_dynamodb::{encrypted_table::{DynamicRecord, DynamicSchema}, SingleIndex};
+/// # use std::sync::Arc;
+/// let schema = Arc::new(DynamicSchema::new("user", "email").query("name", SingleIndex::Prefix));
+///
+/// let mut user = DynamicRecord::new(schema);
+/// user.add_protected("email", "dan@coderdan.co");
+/// user.add_protected("name", "Dan Draper");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicRecord {
+    schema: Arc<DynamicSchema>,
+    protected: HashMap<String, Plaintext>,
+    plaintext: HashMap<String, TableAttribute>,
+}
+
+impl DynamicRecord {
+    pub fn new(schema: Arc<DynamicSchema>) -> Self {
+        Self {
+            schema,
+            protected: HashMap::new(),
+            plaintext: HashMap::new(),
+        }
+    }
+
+    pub fn schema(&self) -> &Arc<DynamicSchema> {
+        &self.schema
+    }
+
+    /// Set the protected attribute, `name`.
+    pub fn add_protected(&mut self, name: impl Into<String>, plaintext: impl Into<Plaintext>) {
+        self.protected.insert(name.into(), plaintext.into());
+    }
+
+    /// Set the plaintext attribute, `name`.
+    pub fn add_plaintext(&mut self, name: impl Into<String>, attribute: impl Into<TableAttribute>) {
+        self.plaintext.insert(name.into(), attribute.into());
+    }
+
+    pub fn get_protected(&self, name: &str) -> Option<&Plaintext> {
+        self.protected.get(name)
+    }
+
+    pub fn get_plaintext(&self, name: &str) -> Option<&TableAttribute> {
+        self.plaintext.get(name)
+    }
+
+    /// Read the value of a key field as a string.
+    fn key_value(&self, field: &str) -> Result<String, SealError> {
+        let missing = || SealError::MissingAttribute(field.to_string());
+
+        match self.schema.key_mode(field) {
+            AttributeMode::Protected => Ok(String::try_from_plaintext(
+                self.protected.get(field).cloned().ok_or_else(missing)?,
+            )?),
+            AttributeMode::Plaintext => Ok(String::try_from_table_attr(
+                self.plaintext.get(field).cloned().ok_or_else(missing)?,
+            )?),
+        }
+    }
+
+    pub fn get_primary_key(&self) -> Result<DynamicKey, SealError> {
+        let pk = self.key_value(&self.schema.partition_key)?;
+
+        match &self.schema.sort_key {
+            Some(field) => Ok(DynamicKey::PkSk(pk, self.key_value(field)?)),
+            None => Ok(DynamicKey::Pk(pk)),
+        }
+    }
+
+    /// Returns the plaintext for an index of the record's type.
+    pub fn attribute_for_index(
+        &self,
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Option<ComposablePlaintext> {
+        if !self.schema.has_index(index_name, index_type) {
+            return None;
+        }
+
+        let mut fields = index_name.split('#');
+        let first = self.protected.get(fields.next()?)?.clone();
+
+        fields.try_fold(ComposablePlaintext::new(first), |plaintext, field| {
+            plaintext
+                .try_compose(self.protected.get(field)?.clone())
+                .ok()
+        })
+    }
+
+    /// Convert the record into an [`Unsealed`] ready to be encrypted.
+    ///
+    /// Only attributes that are part of the schema are included.
+    pub fn into_unsealed(mut self) -> Unsealed {
+        let mut unsealed = Unsealed::new_with_descriptor(self.schema.descriptor_prefix());
+
+        for name in self.schema.protected_attributes() {
+            if let Some(plaintext) = self.protected.remove(name.as_ref()) {
+                unsealed.add_protected(name, plaintext);
+            }
+        }
+
+        for name in self.schema.plaintext_attributes() {
+            if let Some(attribute) = self.plaintext.remove(name.as_ref()) {
+                unsealed.add_unprotected(name.as_ref(), attribute);
+            }
+        }
+
+        unsealed
+    }
+
+    /// Create a record of the type described by `schema` from a decrypted [`Unsealed`].
+    pub fn from_unsealed(schema: Arc<DynamicSchema>, mut unsealed: Unsealed) -> Self {
+        let mut record = Self::new(schema.clone());
+
+        for name in schema.protected_attributes() {
+            if let Some(plaintext) = unsealed.take_protected(&name) {
+                record.add_protected(name, plaintext);
+            }
+        }
+
+        for name in schema.plaintext_attributes() {
+            match unsealed.take_unprotected(name.as_ref()) {
+                TableAttribute::Null => {}
+                attribute => record.add_plaintext(name.as_ref(), attribute),
+            }
+        }
+
+        record
+    }
+
+    /// Convert the record into a JSON object, see [`Unsealed::to_json`].
+    pub fn to_json(&self) -> serde_json::Value {
+        self.clone().into_unsealed().to_json()
+    }
+
+    /// Create a record of the type described by `schema` from a JSON object, see
+    /// [`Unsealed::from_json`].
+    pub fn from_json(
+        schema: Arc<DynamicSchema>,
+        value: serde_json::Value,
+    ) -> Result<Self, SealError> {
+        let unsealed = Unsealed::from_json(value, &schema)?;
+
+        Ok(Self::from_unsealed(schema, unsealed))
+    }
+}
+
+impl PreparedRecord {
+    /// Prepare a [`DynamicRecord`] to be encrypted and stored.
+    pub fn prepare_dynamic(record: DynamicRecord) -> Result<Self, SealError> {
+        let schema = record.schema.clone();
+
+        let PrimaryKeyParts { pk, sk } = schema.primary_key_parts(record.get_primary_key()?)?;
+
+        let unsealed_indexes = schema
+            .indexes
+            .iter()
+            .map(|(index_name, index_type)| {
+                record
+                    .attribute_for_index(index_name, *index_type)
+                    .map(|attr| (attr, index_type.indexer(), index_name.clone(), *index_type))
+                    .ok_or(SealError::MissingAttribute(index_name.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let sealer = Sealer {
+            pk,
+            sk,
+
+            is_sk_encrypted: schema.is_sk_encrypted(),
+            is_pk_encrypted: schema.is_pk_encrypted(),
+
+            type_name: Cow::Owned(schema.type_name.clone()),
+
+            version: schema.version,
+
+            key_binding: schema.key_binding,
+
+            unsealed_indexes,
+
+            unsealed: record.into_unsealed(),
+        };
+
+        Ok(PreparedRecord::new(
+            Cow::Owned(schema.indexes.clone()),
+            Cow::Owned(schema.protected_attributes()),
+            sealer,
+        ))
+    }
+}
+
+impl PreparedDelete {
+    /// Prepare the deletion of a record of the type described by `schema`.
+    pub fn new_dynamic(
+        schema: &DynamicSchema,
+        k: impl Into<DynamicKey>,
+    ) -> Result<Self, PrimaryKeyError> {
+        Ok(Self {
+            primary_key: schema.prepared_primary_key(k)?,
+            protected_indexes: Cow::Owned(schema.indexes.clone()),
+        })
+    }
+}
+
+/// A builder for a query operation which returns [`DynamicRecord`]s, or JSON objects when
+/// created with [`EncryptedTable::query_json`].
+///
+/// Created with [`EncryptedTable::query_dynamic`].
+pub struct DynamicQueryBuilder<'a, T = DynamicRecord> {
+    schema: Arc<DynamicSchema>,
+    parts: Vec<(String, SingleIndex, Plaintext)>,
+    table: &'a EncryptedTable<Dynamo>,
+    dataset_id: Option<DatasetId>,
+    decode: fn(&Arc<DynamicSchema>, Unsealed) -> T,
+}
+
+impl<'a, T> DynamicQueryBuilder<'a, T> {
+    /// Specify the dataset to query against.
+    pub fn via(mut self, dataset_id: DatasetId) -> Self {
+        self.dataset_id = Some(dataset_id);
+        self
+    }
+
+    pub fn eq(mut self, name: impl Into<String>, plaintext: impl Into<Plaintext>) -> Self {
+        self.parts
+            .push((name.into(), SingleIndex::Exact, plaintext.into()));
+        self
+    }
+
+    pub fn starts_with(mut self, name: impl Into<String>, plaintext: impl Into<Plaintext>) -> Self {
+        self.parts
+            .push((name.into(), SingleIndex::Prefix, plaintext.into()));
+        self
+    }
+
+    pub async fn send(self) -> Result<Vec<T>, QueryError> {
+        let scoped_cipher = self.table.scoped_cipher(self.dataset_id).await?;
+
+        let query = PreparedQueryBuilder::new_dynamic(self.schema.clone()).build(self.parts)?;
+        let items = query.send(self.table, &scoped_cipher).await?;
+
+        let unsealed = super::unseal_all(
+            &self.table.cipher,
+            &scoped_cipher,
+            &self.table.layout,
+            self.schema.unseal_spec(),
+            items,
+        )
+        .await?;
+
+        Ok(unsealed
+            .into_iter()
+            .map(|unsealed| (self.decode)(&self.schema, unsealed))
+            .collect())
+    }
+}
+
+impl PreparedQueryBuilder {
+    /// Create a query builder for the type described by `schema`.
+    pub fn new_dynamic(schema: Arc<DynamicSchema>) -> Self {
+        Self {
+            type_name: Cow::Owned(schema.type_name.clone()),
+            index_by_name: Box::new(move |index_name, index_type| {
+                schema.index_by_name(index_name, index_type)
+            }),
+        }
+    }
+}
+
+impl EncryptedTable<Dynamo> {
+    /// Put a [`DynamicRecord`] into the table using the default dataset.
+    pub async fn put_dynamic(&self, record: DynamicRecord) -> Result<(), PutError> {
+        self.put_prepared(PreparedRecord::prepare_dynamic(record)?, None)
+            .await
+    }
+
+    /// Put a [`DynamicRecord`] into the table using a specific dataset.
+    pub async fn put_dynamic_via(
+        &self,
+        record: DynamicRecord,
+        dataset_id: DatasetId,
+    ) -> Result<(), PutError> {
+        self.put_prepared(PreparedRecord::prepare_dynamic(record)?, Some(dataset_id))
+            .await
+    }
+
+    /// Get a record of the type described by `schema` by primary key from the default dataset.
+    pub async fn get_dynamic(
+        &self,
+        schema: &Arc<DynamicSchema>,
+        k: impl Into<DynamicKey>,
+    ) -> Result<Option<DynamicRecord>, GetError> {
+        self.get_dynamic_inner(schema, k.into(), None).await
+    }
+
+    /// Get a record of the type described by `schema` by primary key from a specific dataset.
+    pub async fn get_dynamic_via(
+        &self,
+        schema: &Arc<DynamicSchema>,
+        k: impl Into<DynamicKey>,
+        dataset_id: DatasetId,
+    ) -> Result<Option<DynamicRecord>, GetError> {
+        self.get_dynamic_inner(schema, k.into(), Some(dataset_id))
+            .await
+    }
+
+    async fn get_dynamic_inner(
+        &self,
+        schema: &Arc<DynamicSchema>,
+        k: DynamicKey,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Option<DynamicRecord>, GetError> {
+        Ok(self
+            .get_unsealed(schema, k, dataset_id)
+            .await?
+            .map(|unsealed| DynamicRecord::from_unsealed(schema.clone(), unsealed)))
+    }
+
+    /// Get a record of the type described by `schema` by primary key from the default dataset
+    /// and convert it to JSON, see [`Unsealed::to_json`].
+    pub async fn get_json(
+        &self,
+        schema: &DynamicSchema,
+        k: impl Into<DynamicKey>,
+    ) -> Result<Option<serde_json::Value>, GetError> {
+        Ok(self
+            .get_unsealed(schema, k.into(), None)
+            .await?
+            .map(|unsealed| unsealed.to_json()))
+    }
+
+    /// Get a record of the type described by `schema` by primary key from a specific dataset
+    /// and convert it to JSON, see [`Unsealed::to_json`].
+    pub async fn get_json_via(
+        &self,
+        schema: &DynamicSchema,
+        k: impl Into<DynamicKey>,
+        dataset_id: DatasetId,
+    ) -> Result<Option<serde_json::Value>, GetError> {
+        Ok(self
+            .get_unsealed(schema, k.into(), Some(dataset_id))
+            .await?
+            .map(|unsealed| unsealed.to_json()))
+    }
+
+    async fn get_unsealed(
+        &self,
+        schema: &DynamicSchema,
+        k: DynamicKey,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Option<Unsealed>, GetError> {
+        let scoped_cipher = self.scoped_cipher(dataset_id).await?;
+
+        let Some(item) = self
+            .get_item(schema.prepared_primary_key(k)?, &scoped_cipher)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let unsealed = super::unseal(
+            &self.cipher,
+            &scoped_cipher,
+            &self.layout,
+            schema.unseal_spec(),
+            item,
+        )
+        .await?;
+
+        Ok(Some(unsealed))
+    }
+
+    /// Delete a record of the type described by `schema` by primary key from the default
+    /// dataset.
+    pub async fn delete_dynamic(
+        &self,
+        schema: &DynamicSchema,
+        k: impl Into<DynamicKey>,
+    ) -> Result<(), DeleteError> {
+        self.delete_prepared(PreparedDelete::new_dynamic(schema, k)?, None)
+            .await
+    }
+
+    /// Delete a record of the type described by `schema` by primary key from a specific
+    /// dataset.
+    pub async fn delete_dynamic_via(
+        &self,
+        schema: &DynamicSchema,
+        k: impl Into<DynamicKey>,
+        dataset_id: DatasetId,
+    ) -> Result<(), DeleteError> {
+        self.delete_prepared(PreparedDelete::new_dynamic(schema, k)?, Some(dataset_id))
+            .await
+    }
+
+    /// Query records of the type described by `schema`.
+    pub fn query_dynamic(&self, schema: &Arc<DynamicSchema>) -> DynamicQueryBuilder<'_> {
+        DynamicQueryBuilder {
+            schema: schema.clone(),
+            parts: vec![],
+            table: self,
+            dataset_id: None,
+            decode: |schema, unsealed| DynamicRecord::from_unsealed(schema.clone(), unsealed),
+        }
+    }
+
+    /// Query records of the type described by `schema` and convert them to JSON, see
+    /// [`Unsealed::to_json`].
+    pub fn query_json(
+        &self,
+        schema: &Arc<DynamicSchema>,
+    ) -> DynamicQueryBuilder<'_, serde_json::Value> {
+        DynamicQueryBuilder {
+            schema: schema.clone(),
+            parts: vec![],
+            table: self,
+            dataset_id: None,
+            decode: |_, unsealed| unsealed.to_json(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> DynamicSchema {
+        DynamicSchema::new("user", "email")
+            .sort_key("created_at")
+            .plaintext("created_at")
+            .query("name", SingleIndex::Prefix)
+            .compound_query(("email", SingleIndex::Exact), ("name", SingleIndex::Prefix))
+            .plaintext("tag")
+    }
+
+    #[test]
+    fn test_key_encryption() {
+        let schema = schema();
+
+        assert!(schema.is_pk_encrypted());
+        assert!(!schema.is_sk_encrypted());
+        assert!(DynamicSchema::new("user", "email").is_sk_encrypted());
+        assert!(!DynamicSchema::new("user", "email")
+            .plaintext("email")
+            .is_pk_encrypted());
+    }
+
+    #[test]
+    fn test_protected_attributes_include_keys() {
+        let schema = schema();
+
+        assert_eq!(
+            schema.protected_attributes(),
+            vec![Cow::Borrowed("name"), Cow::Borrowed("email")]
+        );
+        assert_eq!(
+            schema.plaintext_attributes(),
+            &[Cow::Borrowed("created_at"), Cow::Borrowed("tag")]
+        );
+    }
+
+    #[test]
+    fn test_index_by_name() {
+        let schema = schema();
+
+        assert!(schema
+            .index_by_name("name", IndexType::Single(SingleIndex::Prefix))
+            .is_some());
+        assert!(schema
+            .index_by_name("name", IndexType::Single(SingleIndex::Exact))
+            .is_none());
+        assert!(schema
+            .index_by_name(
+                "email#name",
+                IndexType::Compound2((SingleIndex::Exact, SingleIndex::Prefix))
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn test_primary_key_parts() {
+        let schema = schema();
+
+        let PrimaryKeyParts { pk, sk } = schema
+            .primary_key_parts(("dan@coderdan.co", "2024"))
+            .unwrap();
+
+        assert_eq!(pk, "dan@coderdan.co");
+        assert_eq!(sk, "user#2024");
+
+        assert!(schema.primary_key_parts("dan@coderdan.co").is_err());
+        assert_eq!(
+            DynamicSchema::new("user", "email")
+                .primary_key_parts("dan@coderdan.co")
+                .unwrap()
+                .sk,
+            "user"
+        );
+    }
+
+    #[test]
+    fn test_record_primary_key() {
+        let mut record = DynamicRecord::new(Arc::new(schema()));
+        record.add_protected("email", "dan@coderdan.co");
+
+        assert!(matches!(
+            record.get_primary_key(),
+            Err(SealError::MissingAttribute(field)) if field == "created_at"
+        ));
+
+        record.add_plaintext("created_at", "2024");
+
+        assert_eq!(
+            record.get_primary_key().unwrap(),
+            DynamicKey::PkSk("dan@coderdan.co".to_string(), "2024".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unsealed_round_trip() {
+        let schema = Arc::new(schema());
+
+        let mut record = DynamicRecord::new(schema.clone());
+        record.add_protected("email", "dan@coderdan.co");
+        record.add_protected("name", "Dan");
+        record.add_plaintext("created_at", "2024");
+        record.add_plaintext("tag", "admin");
+
+        assert!(record
+            .attribute_for_index(
+                "email#name",
+                IndexType::Compound2((SingleIndex::Exact, SingleIndex::Prefix))
+            )
+            .is_some());
+
+        let unsealed = record.clone().into_unsealed();
+
+        assert_eq!(DynamicRecord::from_unsealed(schema, unsealed), record);
+    }
+
+    #[test]
+    fn test_prepare_with_schema_settings() {
+        let schema = Arc::new(
+            DynamicSchema::new("user", "email")
+                .version(2)
+                .key_binding(KeyBinding::Required)
+                .unknown_attributes(UnknownAttributes::Deny),
+        );
+
+        let mut record = DynamicRecord::new(schema.clone());
+        record.add_protected("email", "dan@coderdan.co");
+
+        let prepared = PreparedRecord::prepare_dynamic(record).unwrap();
+        assert_eq!(prepared.sealer.version, 2);
+        assert_eq!(prepared.sealer.key_binding, KeyBinding::Required);
+
+        let spec = schema.unseal_spec();
+        assert_eq!(spec.key_binding, KeyBinding::Required);
+        assert_eq!(spec.unknown_attributes, UnknownAttributes::Deny);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let schema = Arc::new(schema());
+
+        let mut record = DynamicRecord::new(schema.clone());
+        record.add_protected("email", "dan@coderdan.co");
+        record.add_protected("name", "Dan");
+        record.add_plaintext("created_at", "2024");
+
+        let value = record.to_json();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "email": "dan@coderdan.co",
+                "name": "Dan",
+                "created_at": "2024",
+            })
+        );
+        assert_eq!(DynamicRecord::from_json(schema, value).unwrap(), record);
+    }
+
+    #[test]
+    fn test_type_schema() {
+        let type_schema = schema().type_schema();
+
+        assert_eq!(type_schema.type_name, "user");
+        assert_eq!(
+            type_schema.sort_key,
+            Some(KeySchema {
+                field: Some(Cow::Borrowed("created_at")),
+                encrypted: false,
+            })
+        );
+        assert_eq!(type_schema.indexes.len(), 2);
+    }
+}
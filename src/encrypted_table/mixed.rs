@@ -0,0 +1,199 @@
+use std::borrow::Cow;
+
+use super::{ScopedZeroKmsCipher, TableEntry, TypeRegistry, ZeroKmsCipher};
+use crate::{
+    crypto::{SealError, SealedTableEntry, UnsealSpec, Unsealed},
+    errors::{DecryptError, RegistryError},
+    Decryptable, Searchable,
+};
+
+type DecodeFn<E> = Box<dyn Fn(Unsealed) -> Result<E, SealError> + Send + Sync>;
+
+struct Variant<E> {
+    type_name: Cow<'static, str>,
+    protected_attributes: Cow<'static, [Cow<'static, str>]>,
+    /// The prefix of the descriptors of encrypted attributes, see [`UnsealSpec`].
+    descriptor_prefix: String,
+    spec: fn() -> UnsealSpec<'static>,
+    decode: DecodeFn<E>,
+}
+
+/// Decrypts a list of items that contains records of several types into a single type `E`,
+/// usually an enum with a variant for each type.
+///
+/// The type of each item is identified from the descriptors of its encrypted attributes or,
+/// for types without encrypted attributes, from its sort key using the types registered with the
+/// table, see [`super::EncryptedTable::register`]. Each type added to the decoder must be
+/// registered with the table.
+/// Items are grouped by type so that each type is decrypted in one bulk operation and the
+/// results are returned in the order of the original items.
+///
+/// ```no_run
+/// # use cipherstash_dynamodb::{*, encrypted_table::MixedDecoder};
+/// # use std::collections::HashMap;
+/// # use aws_sdk_dynamodb::types::AttributeValue;
+/// # #[derive(Debug, Encryptable, Decryptable, Identifiable, Searchable)]
+/// # struct User { #[partition_key] email: String }
+/// # #[derive(Debug, Encryptable, Decryptable, Identifiable, Searchable)]
+/// # struct License { #[partition_key] email: String, #[sort_key] id: String }
+/// enum Item {
+///     User(User),
+///     License(License),
+/// }
+///
+/// # async fn example(client: aws_sdk_dynamodb::Client, items: Vec<HashMap<String, AttributeValue>>) -> Result<(), Box<dyn std::error::Error>> {
+/// let table = EncryptedTable::init(client, "users")
+///     .await?
+///     .register::<User>()?
+///     .register::<License>()?;
+///
+/// let decoder = MixedDecoder::new()
+///     .variant(Item::User)?
+///     .variant(Item::License)?;
+///
+/// let records: Vec<Item> = table.decrypt_mixed(items, &decoder).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MixedDecoder<E> {
+    variants: Vec<Variant<E>>,
+    skip_unknown: bool,
+}
+
+impl<E> Default for MixedDecoder<E> {
+    fn default() -> Self {
+        Self {
+            variants: vec![],
+            skip_unknown: false,
+        }
+    }
+}
+
+impl<E> MixedDecoder<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode items of type `T` and convert them into `E` with `f`.
+    ///
+    /// Returns an error if `T` has already been added.
+    pub fn variant<T>(
+        mut self,
+        f: impl Fn(T) -> E + Send + Sync + 'static,
+    ) -> Result<Self, RegistryError>
+    where
+        T: Searchable + Decryptable,
+    {
+        if self
+            .variants
+            .iter()
+            .any(|variant| variant.type_name == T::type_name())
+        {
+            return Err(RegistryError::DuplicateTypeName(T::type_name().to_string()));
+        }
+
+        let spec = UnsealSpec::new_for_decryptable::<T>();
+
+        self.variants.push(Variant {
+            type_name: T::type_name(),
+            protected_attributes: spec.protected_attributes,
+            descriptor_prefix: spec.sort_key_prefix,
+            spec: UnsealSpec::new_for_decryptable::<T>,
+            decode: Box::new(move |unsealed| unsealed.into_value::<T>().map(&f)),
+        });
+
+        Ok(self)
+    }
+
+    /// Skip items that don't belong to any of the types added to the decoder instead of
+    /// returning an error.
+    pub fn skip_unknown(mut self) -> Self {
+        self.skip_unknown = true;
+        self
+    }
+
+    /// Returns the index of the variant that an entry belongs to.
+    fn identify(&self, entry: &TableEntry, registry: &TypeRegistry) -> Option<usize> {
+        let by_descriptor = self.variants.iter().position(|variant| {
+            has_descriptor_prefix(
+                entry,
+                &variant.protected_attributes,
+                &variant.descriptor_prefix,
+            )
+        });
+
+        by_descriptor.or_else(|| {
+            let schema = registry.type_for_sort_key(&entry.sk)?;
+
+            self.variants
+                .iter()
+                .position(|variant| variant.type_name == schema.type_name)
+        })
+    }
+
+    pub(crate) async fn decode(
+        &self,
+        entries: Vec<SealedTableEntry>,
+        registry: &TypeRegistry,
+        cipher: &ZeroKmsCipher,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<Vec<E>, DecryptError> {
+        if let Some(variant) = self
+            .variants
+            .iter()
+            .find(|variant| !registry.contains(&variant.type_name))
+        {
+            return Err(RegistryError::UnregisteredType(variant.type_name.to_string()).into());
+        }
+
+        let len = entries.len();
+        let mut groups: Vec<(Vec<usize>, Vec<SealedTableEntry>)> =
+            self.variants.iter().map(|_| (vec![], vec![])).collect();
+
+        for (i, entry) in entries.into_iter().enumerate() {
+            match self.identify(entry.inner(), registry) {
+                Some(variant) => {
+                    groups[variant].0.push(i);
+                    groups[variant].1.push(entry);
+                }
+                None if self.skip_unknown => {}
+                None => return Err(DecryptError::UnknownType(entry.inner().sk.clone())),
+            }
+        }
+
+        let mut results: Vec<Option<E>> = (0..len).map(|_| None).collect();
+
+        for (variant, (indexes, entries)) in self.variants.iter().zip(groups) {
+            if entries.is_empty() {
+                continue;
+            }
+
+            let unsealed =
+                SealedTableEntry::unseal_all(entries, (variant.spec)(), cipher, scoped_cipher)
+                    .await?;
+
+            for (i, unsealed) in indexes.into_iter().zip(unsealed) {
+                results[i] = Some((variant.decode)(unsealed)?);
+            }
+        }
+
+        Ok(results.into_iter().flatten().collect())
+    }
+}
+
+/// Returns true if any of the `protected_attributes` of the entry is encrypted with a descriptor
+/// that starts with `prefix`, which identifies the type the entry was written as.
+pub(super) fn has_descriptor_prefix(
+    entry: &TableEntry,
+    protected_attributes: &[Cow<'_, str>],
+    prefix: &str,
+) -> bool {
+    protected_attributes.iter().any(|name| {
+        entry
+            .attributes
+            .get(name.as_ref())
+            .and_then(|value| value.encrypted_descriptor())
+            .and_then(|descriptor| descriptor.split_once('/').map(|(found, _)| found == prefix))
+            .unwrap_or(false)
+    })
+}
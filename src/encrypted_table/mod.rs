@@ -1,22 +1,45 @@
 mod attribute_name;
+mod dataset;
+mod dynamic;
+mod mixed;
+mod move_dataset;
+mod provisioning;
 pub mod query;
+mod reencrypt;
+mod registry;
+mod scoped_cipher_cache;
 mod table_attribute;
 mod table_attributes;
 mod table_entry;
+mod table_layout;
+mod template;
 pub use self::{
     attribute_name::AttributeName,
+    dataset::Dataset,
+    dynamic::{DynamicKey, DynamicQueryBuilder, DynamicRecord, DynamicSchema},
+    mixed::MixedDecoder,
+    provisioning::{CreateTableOptions, Throughput, VerifyOptions, DEFAULT_TERM_INDEX_NAME},
     query::QueryBuilder,
+    reencrypt::{ReencryptJob, ReencryptProgress},
+    registry::TypeRegistry,
+    scoped_cipher_cache::DEFAULT_SCOPED_CIPHER_TTL,
     table_attribute::{TableAttribute, TryFromTableAttr},
     table_attributes::TableAttributes,
     table_entry::TableEntry,
+    table_layout::TableLayout,
+    template::{TableTemplate, TemplateArgs, TemplateFormat},
 };
 use crate::{
     crypto::*,
     errors::*,
-    traits::{Decryptable, PrimaryKey, PrimaryKeyError, PrimaryKeyParts, Searchable},
+    traits::{Decryptable, Encryptable, PrimaryKey, PrimaryKeyError, PrimaryKeyParts, Searchable},
     Identifiable, IndexType,
 };
-use aws_sdk_dynamodb::types::{AttributeValue, Delete, Put, TransactWriteItem};
+use aws_sdk_dynamodb::{
+    error::SdkError,
+    operation::transact_write_items::TransactWriteItemsError,
+    types::{AttributeValue, Delete, Put, TransactWriteItem},
+};
 use cipherstash_client::{
     config::{
         console_config::ConsoleConfig, cts_config::CtsConfig, zero_kms_config::ZeroKMSConfig,
@@ -24,15 +47,16 @@ use cipherstash_client::{
     },
     credentials::{auto_refresh::AutoRefresh, ServiceCredentials},
     encryption::ScopedCipher,
-    zerokms::{ClientKey, ZeroKMSWithClientKey},
-    IdentifiedBy,
+    zerokms::{self, ClientKey, ZeroKMSWithClientKey},
 };
 use log::info;
+use scoped_cipher_cache::ScopedCipherCache;
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     ops::Deref,
     sync::Arc,
+    time::Duration,
 };
 use uuid::Uuid;
 
@@ -59,12 +83,70 @@ pub type ScopedZeroKmsCipher = ScopedCipher<AutoRefresh<ServiceCredentials>>;
 pub struct EncryptedTable<D = Dynamo> {
     db: D,
     cipher: Arc<ZeroKmsCipher>,
+    layout: TableLayout,
+    registry: TypeRegistry,
+    scoped_ciphers: ScopedCipherCache,
 }
 
 impl<D> EncryptedTable<D> {
     pub fn cipher(&self) -> Arc<ZeroKmsCipher> {
         self.cipher.clone()
     }
+
+    /// The [`TableLayout`] used to read and write items.
+    pub fn layout(&self) -> &TableLayout {
+        &self.layout
+    }
+
+    /// Register a type stored in this table.
+    ///
+    /// Returns an error if the type name or sort keys of `T` conflict with a type that has
+    /// already been registered.
+    ///
+    /// ```no_run
+    /// # use cipherstash_dynamodb::{EncryptedTable, Encryptable, Decryptable, Identifiable, Searchable};
+    /// # #[derive(Debug, Encryptable, Decryptable, Identifiable, Searchable)]
+    /// # struct User { #[partition_key] email: String }
+    /// # #[derive(Debug, Encryptable, Decryptable, Identifiable, Searchable)]
+    /// # struct License { #[partition_key] email: String, #[sort_key] id: String }
+    /// # async fn example(client: aws_sdk_dynamodb::Client) -> Result<(), cipherstash_dynamodb::Error> {
+    /// let table = EncryptedTable::init(client, "users")
+    ///     .await?
+    ///     .register::<User>()?
+    ///     .register::<License>()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register<T: Searchable>(mut self) -> Result<Self, RegistryError> {
+        self.registry.register::<T>()?;
+        Ok(self)
+    }
+
+    /// The types registered with this table.
+    pub fn registry(&self) -> &TypeRegistry {
+        &self.registry
+    }
+
+    /// Set how long the cipher of each dataset is cached, [`DEFAULT_SCOPED_CIPHER_TTL`] by
+    /// default.
+    ///
+    /// The cipher of a dataset holds the keys used for its index terms, which are loaded from
+    /// ZeroKMS when it is created. A TTL of zero disables the cache.
+    pub fn with_scoped_cipher_ttl(mut self, ttl: Duration) -> Self {
+        self.scoped_ciphers = ScopedCipherCache::new(ttl);
+        self
+    }
+
+    /// Remove the cached cipher of a dataset, or of the default dataset if `dataset_id` is
+    /// `None`, so that it is created again the next time the dataset is used.
+    pub fn invalidate_scoped_cipher(&self, dataset_id: Option<DatasetId>) {
+        self.scoped_ciphers.invalidate(dataset_id);
+    }
+
+    /// Remove the cached ciphers of all datasets.
+    pub fn clear_scoped_ciphers(&self) {
+        self.scoped_ciphers.clear();
+    }
 }
 
 impl EncryptedTable<Headless> {
@@ -96,8 +178,17 @@ impl EncryptedTable<Headless> {
         Ok(Self {
             db: Headless,
             cipher: Arc::new(cipher),
+            layout: TableLayout::default(),
+            registry: TypeRegistry::default(),
+            scoped_ciphers: ScopedCipherCache::new(DEFAULT_SCOPED_CIPHER_TTL),
         })
     }
+
+    /// Use a custom [`TableLayout`] when converting items to and from DynamoDB.
+    pub fn with_layout(mut self, layout: TableLayout) -> Self {
+        self.layout = layout;
+        self
+    }
 }
 
 /// A patch of records to insert and delete based on an operation
@@ -188,9 +279,13 @@ impl PreparedRecord {
                     })
                     .ok_or(SealError::MissingAttribute(index_name.to_string()))
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<Vec<_>>();
 
-        let unsealed = record.into_unsealed();
+        // A record that can't be converted is reported rather than the indexes missing from it
+        let unsealed = record.try_into_unsealed()?;
+        let unsealed_indexes = unsealed_indexes
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
 
         let sealer = Sealer {
             pk,
@@ -201,6 +296,10 @@ impl PreparedRecord {
 
             type_name,
 
+            version: <R as Encryptable>::schema_version(),
+
+            key_binding: R::key_binding(),
+
             unsealed_indexes,
 
             unsealed,
@@ -237,6 +336,16 @@ impl DynamoRecordPatch {
     pub fn into_transact_write_items(
         self,
         table_name: &str,
+    ) -> Result<Vec<TransactWriteItem>, BuildError> {
+        self.into_transact_write_items_with_layout(table_name, &TableLayout::default())
+    }
+
+    /// Consume the [`DynamoRecordPatch`] and create a list of [`TransactWriteItem`] for a table
+    /// using a custom [`TableLayout`].
+    pub fn into_transact_write_items_with_layout(
+        self,
+        table_name: &str,
+        layout: &TableLayout,
     ) -> Result<Vec<TransactWriteItem>, BuildError> {
         let mut items = Vec::with_capacity(self.put_records.len() + self.delete_records.len());
 
@@ -259,8 +368,8 @@ impl DynamoRecordPatch {
                     .delete(
                         Delete::builder()
                             .table_name(table_name)
-                            .key("pk", AttributeValue::S(pk))
-                            .key("sk", AttributeValue::S(sk))
+                            .key(&layout.partition_key, AttributeValue::S(pk))
+                            .key(&layout.sort_key, AttributeValue::S(sk))
                             .build()?,
                     )
                     .build(),
@@ -279,6 +388,10 @@ impl<D> EncryptedTable<D> {
         QueryBuilder::with_backend(self)
     }
 
+    /// Decrypt items that were stored in the default dataset.
+    ///
+    /// The MAC of each item is checked with the key of the dataset, so items from other datasets
+    /// must be decrypted with [`EncryptedTable::decrypt_all_via`].
     pub async fn decrypt_all<T>(
         &self,
         items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
@@ -286,23 +399,234 @@ impl<D> EncryptedTable<D> {
     where
         T: Decryptable + Identifiable,
     {
-        Ok(decrypt_all(&self.cipher, items).await?)
+        self.decrypt_all_inner(items, None).await
+    }
+
+    /// Decrypt items that were stored in a specific dataset.
+    pub async fn decrypt_all_via<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: DatasetId,
+    ) -> Result<Vec<T>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.decrypt_all_inner(items, Some(dataset_id)).await
+    }
+
+    async fn decrypt_all_inner<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Vec<T>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        let scoped_cipher = self
+            .scoped_cipher(dataset_id)
+            .await
+            .map_err(SealError::from)?;
+
+        Ok(decrypt_all(&self.cipher, &scoped_cipher, &self.layout, items).await?)
+    }
+
+    /// Decrypt items that were stored in the default dataset, returning a result for each item
+    /// paired with its primary key, so that items that are malformed, have been tampered with or
+    /// can't be decrypted don't prevent the others from being read.
+    ///
+    /// The results are in the same order as the items. The key is `None` when the item doesn't
+    /// have a primary key, in which case its result is a [`ReadConversionError`]. An error is
+    /// only returned when none of the items can be decrypted, for example when the cipher of the
+    /// dataset can't be loaded.
+    pub async fn decrypt_each<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+    ) -> Result<Vec<(Option<PrimaryKeyParts>, Result<T, DecryptError>)>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.decrypt_each_inner(items, None).await
+    }
+
+    /// Decrypt items that were stored in a specific dataset, see [`EncryptedTable::decrypt_each`].
+    pub async fn decrypt_each_via<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: DatasetId,
+    ) -> Result<Vec<(Option<PrimaryKeyParts>, Result<T, DecryptError>)>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        self.decrypt_each_inner(items, Some(dataset_id)).await
+    }
+
+    async fn decrypt_each_inner<T>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Vec<(Option<PrimaryKeyParts>, Result<T, DecryptError>)>, DecryptError>
+    where
+        T: Decryptable + Identifiable,
+    {
+        let scoped_cipher = self
+            .scoped_cipher(dataset_id)
+            .await
+            .map_err(SealError::from)?;
+
+        Ok(decrypt_each(&self.cipher, &scoped_cipher, &self.layout, items).await)
+    }
+
+    /// Decrypt items that contain records of several types and were stored in the default
+    /// dataset, see [`MixedDecoder`].
+    ///
+    /// Note that querying a partition also returns the index term items of its records, which
+    /// can be excluded with the filter expression `attribute_not_exists(term)`.
+    pub async fn decrypt_mixed<E>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        decoder: &MixedDecoder<E>,
+    ) -> Result<Vec<E>, DecryptError> {
+        self.decrypt_mixed_inner(items, decoder, None).await
+    }
+
+    /// Decrypt items that contain records of several types and were stored in a specific
+    /// dataset, see [`EncryptedTable::decrypt_mixed`].
+    pub async fn decrypt_mixed_via<E>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        decoder: &MixedDecoder<E>,
+        dataset_id: DatasetId,
+    ) -> Result<Vec<E>, DecryptError> {
+        self.decrypt_mixed_inner(items, decoder, Some(dataset_id))
+            .await
     }
 
+    async fn decrypt_mixed_inner<E>(
+        &self,
+        items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+        decoder: &MixedDecoder<E>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Vec<E>, DecryptError> {
+        let entries = SealedTableEntry::vec_from_items(items, &self.layout)?;
+        let scoped_cipher = self
+            .scoped_cipher(dataset_id)
+            .await
+            .map_err(SealError::from)?;
+
+        decoder
+            .decode(entries, &self.registry, &self.cipher, &scoped_cipher)
+            .await
+    }
+
+    /// Unseal an item that was stored in the default dataset.
     pub async fn unseal<'a>(
         &self,
         spec: UnsealSpec<'a>,
         item: HashMap<String, AttributeValue>,
     ) -> Result<Unsealed, DecryptError> {
-        unseal(&self.cipher, spec, item).await
+        self.unseal_inner(spec, item, None).await
+    }
+
+    /// Unseal an item that was stored in a specific dataset.
+    pub async fn unseal_via<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        item: HashMap<String, AttributeValue>,
+        dataset_id: DatasetId,
+    ) -> Result<Unsealed, DecryptError> {
+        self.unseal_inner(spec, item, Some(dataset_id)).await
     }
 
+    async fn unseal_inner<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        item: HashMap<String, AttributeValue>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Unsealed, DecryptError> {
+        let scoped_cipher = self
+            .scoped_cipher(dataset_id)
+            .await
+            .map_err(SealError::from)?;
+
+        unseal(&self.cipher, &scoped_cipher, &self.layout, spec, item).await
+    }
+
+    /// Unseal items that were stored in the default dataset.
     pub async fn unseal_all<'a>(
         &self,
         spec: UnsealSpec<'a>,
         items: Vec<HashMap<String, AttributeValue>>,
     ) -> Result<Vec<Unsealed>, DecryptError> {
-        Ok(unseal_all(&self.cipher, spec, items).await?)
+        self.unseal_all_inner(spec, items, None).await
+    }
+
+    /// Unseal items that were stored in a specific dataset.
+    pub async fn unseal_all_via<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        items: Vec<HashMap<String, AttributeValue>>,
+        dataset_id: DatasetId,
+    ) -> Result<Vec<Unsealed>, DecryptError> {
+        self.unseal_all_inner(spec, items, Some(dataset_id)).await
+    }
+
+    async fn unseal_all_inner<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        items: Vec<HashMap<String, AttributeValue>>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Vec<Unsealed>, DecryptError> {
+        let scoped_cipher = self
+            .scoped_cipher(dataset_id)
+            .await
+            .map_err(SealError::from)?;
+
+        Ok(unseal_all(&self.cipher, &scoped_cipher, &self.layout, spec, items).await?)
+    }
+
+    /// Unseal items that were stored in the default dataset, returning a result for each item
+    /// paired with its primary key, see [`EncryptedTable::decrypt_each`].
+    pub async fn unseal_each<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        items: Vec<HashMap<String, AttributeValue>>,
+    ) -> Result<Vec<(Option<PrimaryKeyParts>, Result<Unsealed, SealError>)>, DecryptError> {
+        self.unseal_each_inner(spec, items, None).await
+    }
+
+    /// Unseal items that were stored in a specific dataset, see [`EncryptedTable::unseal_each`].
+    pub async fn unseal_each_via<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        items: Vec<HashMap<String, AttributeValue>>,
+        dataset_id: DatasetId,
+    ) -> Result<Vec<(Option<PrimaryKeyParts>, Result<Unsealed, SealError>)>, DecryptError> {
+        self.unseal_each_inner(spec, items, Some(dataset_id)).await
+    }
+
+    async fn unseal_each_inner<'a>(
+        &self,
+        spec: UnsealSpec<'a>,
+        items: Vec<HashMap<String, AttributeValue>>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Vec<(Option<PrimaryKeyParts>, Result<Unsealed, SealError>)>, DecryptError> {
+        let scoped_cipher = self
+            .scoped_cipher(dataset_id)
+            .await
+            .map_err(SealError::from)?;
+
+        Ok(unseal_each(&self.cipher, &scoped_cipher, &self.layout, spec, items).await)
+    }
+
+    /// Get the cipher for the index keys of a dataset, or of the default dataset if
+    /// `dataset_id` is `None`, from the cache of the table.
+    pub(crate) async fn scoped_cipher(
+        &self,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Arc<ScopedZeroKmsCipher>, zerokms::Error> {
+        self.scoped_ciphers
+            .get_or_init(&self.cipher, dataset_id)
+            .await
     }
 
     pub async fn create_delete_patch(
@@ -310,8 +634,7 @@ impl<D> EncryptedTable<D> {
         delete: PreparedDelete,
         dataset_id: Option<DatasetId>,
     ) -> Result<DynamoRecordPatch, DeleteError> {
-        let keyset_id = dataset_id.map(IdentifiedBy::Uuid);
-        let scoped_cipher = ScopedZeroKmsCipher::init(self.cipher.clone(), keyset_id).await?;
+        let scoped_cipher = self.scoped_cipher(dataset_id).await?;
 
         let PrimaryKeyParts { pk, sk } =
             encrypt_primary_key_parts(&scoped_cipher, delete.primary_key)?;
@@ -351,8 +674,7 @@ impl<D> EncryptedTable<D> {
     ) -> Result<DynamoRecordPatch, PutError> {
         let mut seen_sk = HashSet::new();
 
-        let keyset_id = dataset_id.map(IdentifiedBy::Uuid);
-        let indexable_cipher = ScopedZeroKmsCipher::init(self.cipher.clone(), keyset_id).await?;
+        let indexable_cipher = self.scoped_cipher(dataset_id).await?;
 
         let PreparedRecord {
             protected_attributes,
@@ -371,14 +693,14 @@ impl<D> EncryptedTable<D> {
 
         let PrimaryKeyParts { pk, sk } = sealed.primary_key();
 
-        let (root, index_entries) = sealed.into_table_entries(index_predicate);
+        let (root, index_entries) = sealed.into_table_entries(index_predicate, &indexable_cipher);
 
         seen_sk.insert(root.inner().sk.clone());
-        put_records.push(root.try_into()?);
+        put_records.push(root.into_item(&self.layout)?);
 
         for entry in index_entries.into_iter() {
             seen_sk.insert(entry.inner().sk.clone());
-            put_records.push(entry.try_into()?);
+            put_records.push(entry.into_item(&self.layout)?);
         }
 
         for index_sk in all_index_keys(&sk, protected_indexes) {
@@ -404,35 +726,91 @@ impl<D> EncryptedTable<D> {
 }
 
 impl EncryptedTable<Dynamo> {
+    /// Initialize a table and verify that it has the schema expected by CipherStash for DynamoDB.
+    ///
+    /// See [`EncryptedTable::create_table`] to create a correctly shaped table.
     pub async fn init(
         db: aws_sdk_dynamodb::Client,
         table_name: impl Into<String>,
+    ) -> Result<Self, InitError> {
+        Self::init_with_layout(db, table_name, TableLayout::default()).await
+    }
+
+    /// Initialize a table that uses a custom [`TableLayout`].
+    pub async fn init_with_layout(
+        db: aws_sdk_dynamodb::Client,
+        table_name: impl Into<String>,
+        layout: TableLayout,
+    ) -> Result<Self, InitError> {
+        Self::init_with_verify_options(db, table_name, layout, VerifyOptions::default()).await
+    }
+
+    /// Initialize a table that uses a custom [`TableLayout`], checking its schema with
+    /// [`VerifyOptions`].
+    pub async fn init_with_verify_options(
+        db: aws_sdk_dynamodb::Client,
+        table_name: impl Into<String>,
+        layout: TableLayout,
+        verify_options: VerifyOptions,
     ) -> Result<Self, InitError> {
         let table = EncryptedTable::init_headless().await?;
 
-        Ok(Self {
-            db: Dynamo {
-                table_name: table_name.into(),
-                db,
-            },
-            cipher: table.cipher,
-        })
+        Self::init_with_cipher(table.cipher, db, table_name, layout, &verify_options).await
     }
 
     pub async fn init_with_zerokms_config(
         zerokms_config: ZeroKMSConfig<ClientKey>,
         db: aws_sdk_dynamodb::Client,
         table_name: impl Into<String>,
+    ) -> Result<Self, InitError> {
+        Self::init_with_zerokms_config_and_layout(
+            zerokms_config,
+            db,
+            table_name,
+            TableLayout::default(),
+        )
+        .await
+    }
+
+    pub async fn init_with_zerokms_config_and_layout(
+        zerokms_config: ZeroKMSConfig<ClientKey>,
+        db: aws_sdk_dynamodb::Client,
+        table_name: impl Into<String>,
+        layout: TableLayout,
     ) -> Result<Self, InitError> {
         let table = EncryptedTable::init_headless_with_zerokms_config(zerokms_config).await?;
 
-        Ok(Self {
+        Self::init_with_cipher(
+            table.cipher,
+            db,
+            table_name,
+            layout,
+            &VerifyOptions::default(),
+        )
+        .await
+    }
+
+    async fn init_with_cipher(
+        cipher: Arc<ZeroKmsCipher>,
+        db: aws_sdk_dynamodb::Client,
+        table_name: impl Into<String>,
+        layout: TableLayout,
+        verify_options: &VerifyOptions,
+    ) -> Result<Self, InitError> {
+        let table = Self {
             db: Dynamo {
                 table_name: table_name.into(),
                 db,
             },
-            cipher: table.cipher,
-        })
+            cipher,
+            layout,
+            registry: TypeRegistry::default(),
+            scoped_ciphers: ScopedCipherCache::new(DEFAULT_SCOPED_CIPHER_TTL),
+        };
+
+        table.verify_table_with(verify_options).await?;
+
+        Ok(table)
     }
 
     /// Get a record from the table by primary key from the default dataset.
@@ -463,27 +841,39 @@ impl EncryptedTable<Dynamo> {
     where
         T: Decryptable + Identifiable,
     {
-        let keyset_id = dataset_id.map(IdentifiedBy::Uuid);
-        let cipher = ScopedZeroKmsCipher::init(self.cipher.clone(), keyset_id).await?;
+        let scoped_cipher = self.scoped_cipher(dataset_id).await?;
 
-        let PrimaryKeyParts { pk, sk } =
-            encrypt_primary_key_parts(&cipher, PreparedPrimaryKey::new::<T>(k))?;
+        if let Some(item) = self
+            .get_item(PreparedPrimaryKey::new::<T>(k), &scoped_cipher)
+            .await?
+        {
+            Ok(Some(
+                decrypt(&self.cipher, &scoped_cipher, &self.layout, item).await?,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the item stored under a primary key without decrypting it.
+    async fn get_item(
+        &self,
+        primary_key: PreparedPrimaryKey,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<Option<HashMap<String, AttributeValue>>, GetError> {
+        let PrimaryKeyParts { pk, sk } = encrypt_primary_key_parts(scoped_cipher, primary_key)?;
 
         let result = self
             .db
             .get_item()
             .table_name(&self.db.table_name)
-            .key("pk", AttributeValue::S(pk))
-            .key("sk", AttributeValue::S(sk))
+            .key(&self.layout.partition_key, AttributeValue::S(pk))
+            .key(&self.layout.sort_key, AttributeValue::S(sk))
             .send()
             .await
-            .map_err(|e| GetError::Aws(format!("{e:?}")))?;
+            .map_err(|e| GetError::DynamoError(Box::new(e)))?;
 
-        if let Some(item) = result.item {
-            Ok(Some(decrypt(&self.cipher, item).await?))
-        } else {
-            Ok(None)
-        }
+        Ok(result.item)
     }
 
     /// Delete a record from the table by primary key from the default dataset.
@@ -507,23 +897,24 @@ impl EncryptedTable<Dynamo> {
         &self,
         k: E::PrimaryKey,
         dataset_id: Option<DatasetId>,
+    ) -> Result<(), DeleteError> {
+        self.delete_prepared(PreparedDelete::new::<E>(k), dataset_id)
+            .await
+    }
+
+    async fn delete_prepared(
+        &self,
+        delete: PreparedDelete,
+        dataset_id: Option<DatasetId>,
     ) -> Result<(), DeleteError> {
         let transact_items = self
-            .create_delete_patch(PreparedDelete::new::<E>(k), dataset_id)
+            .create_delete_patch(delete, dataset_id)
             .await?
-            .into_transact_write_items(&self.db.table_name)?;
-
-        // Dynamo has a limit of 100 items per transaction
-        for items in transact_items.chunks(100) {
-            self.db
-                .transact_write_items()
-                .set_transact_items(Some(items.to_vec()))
-                .send()
-                .await
-                .map_err(|e| DeleteError::Aws(format!("{e:?}")))?;
-        }
+            .into_transact_write_items_with_layout(&self.db.table_name, &self.layout)?;
 
-        Ok(())
+        self.send_transact_items(transact_items)
+            .await
+            .map_err(|e| DeleteError::DynamoError(Box::new(e)))
     }
 
     /// Put a record into the table using the default dataset.
@@ -546,8 +937,15 @@ impl EncryptedTable<Dynamo> {
     where
         T: Searchable + Identifiable,
     {
-        let record = PreparedRecord::prepare_record(record)?;
+        self.put_prepared(PreparedRecord::prepare_record(record)?, dataset_id)
+            .await
+    }
 
+    async fn put_prepared(
+        &self,
+        record: PreparedRecord,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<(), PutError> {
         let transact_items = self
             .create_put_patch(
                 record,
@@ -556,16 +954,27 @@ impl EncryptedTable<Dynamo> {
                 |_, _| true,
             )
             .await?
-            .into_transact_write_items(&self.db.table_name)?;
+            .into_transact_write_items_with_layout(&self.db.table_name, &self.layout)?;
+
+        self.send_transact_items(transact_items)
+            .await
+            .map_err(|e| PutError::DynamoError(Box::new(e)))
+    }
 
-        // Dynamo has a limit of 100 items per transaction
+    /// Write items in transactions of up to 100 items, the limit of DynamoDB.
+    ///
+    /// Each transaction is atomic but the items of earlier transactions stay written if a later
+    /// one fails.
+    pub(crate) async fn send_transact_items(
+        &self,
+        transact_items: Vec<TransactWriteItem>,
+    ) -> Result<(), SdkError<TransactWriteItemsError>> {
         for items in transact_items.chunks(100) {
             self.db
                 .transact_write_items()
                 .set_transact_items(Some(items.to_vec()))
                 .send()
-                .await
-                .map_err(|e| PutError::DynamoError(Box::new(e)))?;
+                .await?;
         }
 
         Ok(())
@@ -593,38 +1002,66 @@ pub fn encrypt_primary_key_parts(
 
 async fn decrypt<T>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
     item: HashMap<String, AttributeValue>,
 ) -> Result<T, DecryptError>
 where
     T: Decryptable + Identifiable,
 {
-    let spec = UnsealSpec::new_for_decryptable::<T>();
+    decrypt_with_spec(
+        cipher,
+        scoped_cipher,
+        layout,
+        UnsealSpec::new_for_decryptable::<T>(),
+        item,
+    )
+    .await
+}
 
-    Ok(unseal(cipher, spec, item).await?.into_value::<T>()?)
+async fn decrypt_with_spec<T>(
+    cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
+    spec: UnsealSpec<'_>,
+    item: HashMap<String, AttributeValue>,
+) -> Result<T, DecryptError>
+where
+    T: Decryptable + Identifiable,
+{
+    Ok(unseal(cipher, scoped_cipher, layout, spec, item)
+        .await?
+        .into_value::<T>()?)
 }
 
 async fn unseal<'a>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
     spec: UnsealSpec<'a>,
     item: HashMap<String, AttributeValue>,
 ) -> Result<Unsealed, DecryptError> {
-    let table_entry = SealedTableEntry::try_from(item)?;
+    let table_entry = SealedTableEntry::from_item(item, layout)?;
 
-    Ok(table_entry.unseal(spec, cipher).await?)
+    Ok(table_entry.unseal(spec, cipher, scoped_cipher).await?)
 }
 
 async fn unseal_all<'a>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
     spec: UnsealSpec<'a>,
     items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
 ) -> Result<Vec<Unsealed>, SealError> {
-    let table_entries = SealedTableEntry::vec_from(items)?;
+    let table_entries = SealedTableEntry::vec_from_items(items, layout)?;
 
-    SealedTableEntry::unseal_all(table_entries, spec, cipher).await
+    SealedTableEntry::unseal_all(table_entries, spec, cipher, scoped_cipher).await
 }
 
 async fn decrypt_all<T>(
     cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
     items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
 ) -> Result<Vec<T>, SealError>
 where
@@ -632,9 +1069,92 @@ where
 {
     let spec = UnsealSpec::new_for_decryptable::<T>();
 
-    unseal_all(cipher, spec, items)
+    unseal_all(cipher, scoped_cipher, layout, spec, items)
         .await?
         .into_iter()
         .map(|x| x.into_value::<T>())
         .collect::<Result<_, _>>()
 }
+
+async fn unseal_each<'a>(
+    cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
+    spec: UnsealSpec<'a>,
+    items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+) -> Vec<(Option<PrimaryKeyParts>, Result<Unsealed, SealError>)> {
+    let mut keys = Vec::new();
+    let mut table_entries = Vec::new();
+
+    // Items that can't be read as an entry get their own error in place of a result
+    for item in items {
+        match SealedTableEntry::from_item(item, layout) {
+            Ok(entry) => {
+                keys.push(Ok(PrimaryKeyParts {
+                    pk: entry.inner().pk.clone(),
+                    sk: entry.inner().sk.clone(),
+                }));
+                table_entries.push(entry);
+            }
+            Err(error) => keys.push(Err(error)),
+        }
+    }
+
+    let mut results = SealedTableEntry::unseal_each(table_entries, spec, cipher, scoped_cipher)
+        .await
+        .into_iter();
+
+    keys.into_iter()
+        .map(|key| match key {
+            Ok(key) => {
+                let result = results.next().unwrap_or_else(|| {
+                    Err(SealError::AssertionFailed(
+                        "Expected unseal_each to return a result for every entry".to_string(),
+                    ))
+                });
+
+                (Some(key), result)
+            }
+            Err(error) => (None, Err(error.into())),
+        })
+        .collect()
+}
+
+async fn decrypt_each<T>(
+    cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
+    items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+) -> Vec<(Option<PrimaryKeyParts>, Result<T, DecryptError>)>
+where
+    T: Decryptable + Identifiable,
+{
+    decrypt_each_with_spec(
+        cipher,
+        scoped_cipher,
+        layout,
+        UnsealSpec::new_for_decryptable::<T>(),
+        items,
+    )
+    .await
+}
+
+async fn decrypt_each_with_spec<T>(
+    cipher: &ZeroKmsCipher,
+    scoped_cipher: &ScopedZeroKmsCipher,
+    layout: &TableLayout,
+    spec: UnsealSpec<'_>,
+    items: impl IntoIterator<Item = HashMap<String, AttributeValue>>,
+) -> Vec<(Option<PrimaryKeyParts>, Result<T, DecryptError>)>
+where
+    T: Decryptable + Identifiable,
+{
+    unseal_each(cipher, scoped_cipher, layout, spec, items)
+        .await
+        .into_iter()
+        .map(|(key, result)| {
+            let value = result.and_then(Unsealed::into_value::<T>);
+            (key, value.map_err(DecryptError::from))
+        })
+        .collect()
+}
@@ -0,0 +1,97 @@
+use super::{decrypt_with_spec, reencrypt::read_key, DatasetId, Dynamo, EncryptedTable};
+use crate::{
+    crypto::{PreparedPrimaryKey, UnsealSpec, MAC_ATTRIBUTE},
+    errors::{DecryptError, PutError, ReencryptError},
+    traits::RecordMac,
+    Decryptable, Identifiable, Searchable,
+};
+
+impl EncryptedTable<Dynamo> {
+    /// Move a record from the dataset `from` to the dataset `to`, where `None` is the default
+    /// dataset.
+    ///
+    /// The keys and index terms of a record depend on its dataset, so the record is decrypted
+    /// and encrypted again into `to`. The new items are written and the old items are deleted in
+    /// one transaction, unless the record has more than 100 items with its index terms, the limit
+    /// of DynamoDB, in which case several transactions are used.
+    ///
+    /// The move is conditional on the record not changing after it is read. If it does, the
+    /// error returned reports a failed condition, see [`crate::errors::DynamoErrorKind`].
+    ///
+    /// Returns `false` if the record doesn't exist in `from`. A record with the same key in `to`
+    /// is overwritten.
+    ///
+    /// Records stored before MACs were added are moved even if `T` uses [`RecordMac::Required`],
+    /// and are written into `to` with a MAC.
+    ///
+    /// ```no_run
+    /// # use cipherstash_dynamodb::*;
+    /// # #[derive(Debug, Encryptable, Decryptable, Identifiable, Searchable)]
+    /// # struct User { #[partition_key] email: String, name: String }
+    /// # async fn example(table: EncryptedTable, from: uuid::Uuid, to: uuid::Uuid) -> Result<(), Box<dyn std::error::Error>> {
+    /// let moved = table
+    ///     .move_to_dataset::<User>("dan@coderdan.co", Some(from), Some(to))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn move_to_dataset<T>(
+        &self,
+        k: impl Into<T::PrimaryKey>,
+        from: Option<DatasetId>,
+        to: Option<DatasetId>,
+    ) -> Result<bool, ReencryptError>
+    where
+        T: Searchable + Decryptable + Identifiable,
+    {
+        let scoped_cipher = self.scoped_cipher(from).await?;
+
+        let Some(item) = self
+            .get_item(PreparedPrimaryKey::new::<T>(k), &scoped_cipher)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        let key = read_key(&item, &self.layout).map_err(DecryptError::from)?;
+        let mac = item.get(MAC_ATTRIBUTE).cloned();
+        // Records stored before MACs were added can be moved, and are written with one
+        let spec = UnsealSpec::new_for_decryptable::<T>().with_record_mac(RecordMac::Optional);
+        let record: T =
+            decrypt_with_spec(&self.cipher, &scoped_cipher, &self.layout, spec, item).await?;
+
+        let items = self.reseal_items(self, record, &key, mac, from, to).await?;
+
+        self.send_transact_items(items)
+            .await
+            .map_err(|e| PutError::DynamoError(Box::new(e)))?;
+
+        Ok(true)
+    }
+
+    /// Move several records from the dataset `from` to the dataset `to`, see
+    /// [`EncryptedTable::move_to_dataset`].
+    ///
+    /// Each record is moved in its own transaction so records that were moved before an error is
+    /// returned stay moved. Returns the number of records that were moved, which excludes records
+    /// that don't exist in `from`.
+    pub async fn move_all_to_dataset<T>(
+        &self,
+        keys: impl IntoIterator<Item = impl Into<T::PrimaryKey>>,
+        from: Option<DatasetId>,
+        to: Option<DatasetId>,
+    ) -> Result<usize, ReencryptError>
+    where
+        T: Searchable + Decryptable + Identifiable,
+    {
+        let mut moved = 0;
+
+        for k in keys {
+            if self.move_to_dataset::<T>(k, from, to).await? {
+                moved += 1;
+            }
+        }
+
+        Ok(moved)
+    }
+}
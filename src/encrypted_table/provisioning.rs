@@ -0,0 +1,522 @@
+use super::{CreateTableError, Dynamo, EncryptedTable, InitError, TableLayout};
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType, Projection,
+    ProjectionType, ProvisionedThroughput, ScalarAttributeType, TableDescription,
+};
+
+/// The name of the Global Secondary Index used to query index terms in the default
+/// [`TableLayout`].
+pub const DEFAULT_TERM_INDEX_NAME: &str = "TermIndex";
+
+/// Throughput settings used when creating a table with [`EncryptedTable::create_table`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Throughput {
+    /// Use on-demand (pay per request) billing for the table and its indexes.
+    OnDemand,
+    /// Use provisioned capacity for the table and its indexes.
+    Provisioned {
+        read_capacity_units: i64,
+        write_capacity_units: i64,
+    },
+}
+
+/// Options used by [`EncryptedTable::create_table`] to create a correctly shaped table.
+///
+/// By default the table is created with on-demand billing and the default [`TableLayout`], with a
+/// term index called `TermIndex` that projects all attributes.
+#[derive(Debug, Clone)]
+pub struct CreateTableOptions {
+    throughput: Throughput,
+    layout: TableLayout,
+    projection_type: ProjectionType,
+}
+
+impl Default for CreateTableOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CreateTableOptions {
+    pub fn new() -> Self {
+        Self {
+            throughput: Throughput::OnDemand,
+            layout: TableLayout::default(),
+            projection_type: ProjectionType::All,
+        }
+    }
+
+    /// Use on-demand (pay per request) billing.
+    pub fn on_demand(mut self) -> Self {
+        self.throughput = Throughput::OnDemand;
+        self
+    }
+
+    /// Use provisioned capacity for both the table and the term index.
+    pub fn provisioned(mut self, read_capacity_units: i64, write_capacity_units: i64) -> Self {
+        self.throughput = Throughput::Provisioned {
+            read_capacity_units,
+            write_capacity_units,
+        };
+        self
+    }
+
+    /// Set the name of the Global Secondary Index used for queries.
+    pub fn term_index_name(mut self, name: impl Into<String>) -> Self {
+        self.layout.term_index = name.into();
+        self
+    }
+
+    /// Create the table with the key attribute and index names of a custom [`TableLayout`].
+    pub fn layout(mut self, layout: TableLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set the projection type of the term index.
+    ///
+    /// Queries made with [`crate::QueryBuilder`] read records straight from the index so anything
+    /// other than [`ProjectionType::All`] will only be useful if records are loaded separately.
+    /// Such a table must be initialized with [`VerifyOptions::allow_partial_projection`].
+    pub fn projection_type(mut self, projection_type: ProjectionType) -> Self {
+        self.projection_type = projection_type;
+        self
+    }
+
+    fn provisioned_throughput(&self) -> Result<Option<ProvisionedThroughput>, CreateTableError> {
+        match self.throughput {
+            Throughput::OnDemand => Ok(None),
+            Throughput::Provisioned {
+                read_capacity_units,
+                write_capacity_units,
+            } => Ok(Some(
+                ProvisionedThroughput::builder()
+                    .read_capacity_units(read_capacity_units)
+                    .write_capacity_units(write_capacity_units)
+                    .build()?,
+            )),
+        }
+    }
+}
+
+/// Options used by [`EncryptedTable::verify_table_with`] to check the schema of a table.
+///
+/// By default every check is enforced.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    allow_partial_projection: bool,
+}
+
+impl VerifyOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept a term index that doesn't project all attributes.
+    ///
+    /// Queries read records from the term index, so only use this if records are loaded from the
+    /// table separately.
+    pub fn allow_partial_projection(mut self) -> Self {
+        self.allow_partial_projection = true;
+        self
+    }
+}
+
+impl EncryptedTable<Dynamo> {
+    /// Create a table with the schema expected by CipherStash for DynamoDB.
+    ///
+    /// The table uses a string partition key `pk`, a string sort key `sk` and a Global Secondary
+    /// Index keyed on the binary `term` attribute unless a custom layout is set with
+    /// [`CreateTableOptions::layout`].
+    ///
+    /// Note that DynamoDB creates tables asynchronously so the table may still be in the
+    /// `CREATING` state when this returns.
+    pub async fn create_table(
+        db: &aws_sdk_dynamodb::Client,
+        table_name: impl Into<String>,
+        options: CreateTableOptions,
+    ) -> Result<(), CreateTableError> {
+        let provisioned_throughput = options.provisioned_throughput()?;
+
+        let billing_mode = if provisioned_throughput.is_some() {
+            BillingMode::Provisioned
+        } else {
+            BillingMode::PayPerRequest
+        };
+
+        let layout = &options.layout;
+
+        let term_index = GlobalSecondaryIndex::builder()
+            .index_name(&layout.term_index)
+            .key_schema(
+                KeySchemaElement::builder()
+                    .attribute_name(&layout.term)
+                    .key_type(KeyType::Hash)
+                    .build()?,
+            )
+            .projection(
+                Projection::builder()
+                    .projection_type(options.projection_type)
+                    .build(),
+            )
+            .set_provisioned_throughput(provisioned_throughput.clone())
+            .build()?;
+
+        db.create_table()
+            .table_name(table_name)
+            .billing_mode(billing_mode)
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name(&layout.partition_key)
+                    .attribute_type(ScalarAttributeType::S)
+                    .build()?,
+            )
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name(&layout.sort_key)
+                    .attribute_type(ScalarAttributeType::S)
+                    .build()?,
+            )
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name(&layout.term)
+                    .attribute_type(ScalarAttributeType::B)
+                    .build()?,
+            )
+            .key_schema(
+                KeySchemaElement::builder()
+                    .attribute_name(&layout.partition_key)
+                    .key_type(KeyType::Hash)
+                    .build()?,
+            )
+            .key_schema(
+                KeySchemaElement::builder()
+                    .attribute_name(&layout.sort_key)
+                    .key_type(KeyType::Range)
+                    .build()?,
+            )
+            .set_provisioned_throughput(provisioned_throughput)
+            .global_secondary_indexes(term_index)
+            .send()
+            .await
+            .map_err(|e| CreateTableError::DynamoError(Box::new(e)))?;
+
+        Ok(())
+    }
+
+    /// Check that the table exists and has the keys, attribute types and term index expected by
+    /// CipherStash for DynamoDB.
+    ///
+    /// This is called by [`EncryptedTable::init`].
+    pub async fn verify_table(&self) -> Result<(), InitError> {
+        self.verify_table_with(&VerifyOptions::default()).await
+    }
+
+    /// Check the schema of the table like [`EncryptedTable::verify_table`] with [`VerifyOptions`].
+    pub async fn verify_table_with(&self, options: &VerifyOptions) -> Result<(), InitError> {
+        let table_name = &self.db.table_name;
+
+        let description = self
+            .db
+            .describe_table()
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(|e| InitError::DescribeTable(Box::new(e)))?
+            .table
+            .ok_or_else(|| InitError::InvalidTable {
+                table_name: table_name.clone(),
+                reason: "DescribeTable did not return a table description".to_string(),
+            })?;
+
+        check_table_description(&description, &self.layout, options).map_err(|reason| {
+            InitError::InvalidTable {
+                table_name: table_name.clone(),
+                reason,
+            }
+        })
+    }
+}
+
+/// Check a [`TableDescription`] against the schema expected by CipherStash for DynamoDB and
+/// return a description of the first problem found.
+fn check_table_description(
+    description: &TableDescription,
+    layout: &TableLayout,
+    options: &VerifyOptions,
+) -> Result<(), String> {
+    let term_index_name = layout.term_index.as_str();
+
+    check_key_schema(
+        description.key_schema(),
+        &layout.partition_key,
+        Some(&layout.sort_key),
+    )
+    .map_err(|e| format!("table key schema {e}"))?;
+
+    check_attribute_type(
+        description.attribute_definitions(),
+        &layout.partition_key,
+        ScalarAttributeType::S,
+    )?;
+    check_attribute_type(
+        description.attribute_definitions(),
+        &layout.sort_key,
+        ScalarAttributeType::S,
+    )?;
+    check_attribute_type(
+        description.attribute_definitions(),
+        &layout.term,
+        ScalarAttributeType::B,
+    )?;
+
+    let term_index = description
+        .global_secondary_indexes()
+        .iter()
+        .find(|index| index.index_name() == Some(term_index_name))
+        .ok_or_else(|| format!("missing global secondary index '{term_index_name}'"))?;
+
+    check_key_schema(term_index.key_schema(), &layout.term, None)
+        .map_err(|e| format!("index '{term_index_name}' key schema {e}"))?;
+
+    let projection_type = term_index
+        .projection()
+        .and_then(|projection| projection.projection_type());
+
+    if projection_type != Some(&ProjectionType::All) && !options.allow_partial_projection {
+        return Err(format!(
+            "index '{term_index_name}' has projection type {}, expected ALL",
+            projection_type.map_or("unset", |projection_type| projection_type.as_str())
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_key_schema(
+    key_schema: &[KeySchemaElement],
+    hash_key: &str,
+    range_key: Option<&str>,
+) -> Result<(), String> {
+    let find_key = |key_type: KeyType| {
+        key_schema
+            .iter()
+            .find(|element| element.key_type() == &key_type)
+            .map(|element| element.attribute_name())
+    };
+
+    match find_key(KeyType::Hash) {
+        Some(name) if name == hash_key => {}
+        Some(name) => return Err(format!("has hash key '{name}', expected '{hash_key}'")),
+        None => return Err(format!("is missing hash key '{hash_key}'")),
+    }
+
+    match (find_key(KeyType::Range), range_key) {
+        (Some(name), Some(range_key)) if name == range_key => Ok(()),
+        (Some(name), Some(range_key)) => {
+            Err(format!("has range key '{name}', expected '{range_key}'"))
+        }
+        (None, Some(range_key)) => Err(format!("is missing range key '{range_key}'")),
+        (Some(name), None) => Err(format!("has unexpected range key '{name}'")),
+        (None, None) => Ok(()),
+    }
+}
+
+fn check_attribute_type(
+    attribute_definitions: &[AttributeDefinition],
+    name: &str,
+    expected: ScalarAttributeType,
+) -> Result<(), String> {
+    match attribute_definitions
+        .iter()
+        .find(|definition| definition.attribute_name() == name)
+    {
+        Some(definition) if definition.attribute_type() == &expected => Ok(()),
+        Some(definition) => Err(format!(
+            "attribute '{name}' has type {}, expected {}",
+            definition.attribute_type().as_str(),
+            expected.as_str()
+        )),
+        None => Err(format!("attribute '{name}' is not defined")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::types::GlobalSecondaryIndexDescription;
+
+    fn key(name: &str, key_type: KeyType) -> KeySchemaElement {
+        KeySchemaElement::builder()
+            .attribute_name(name)
+            .key_type(key_type)
+            .build()
+            .unwrap()
+    }
+
+    fn attr(name: &str, attribute_type: ScalarAttributeType) -> AttributeDefinition {
+        AttributeDefinition::builder()
+            .attribute_name(name)
+            .attribute_type(attribute_type)
+            .build()
+            .unwrap()
+    }
+
+    fn term_index(name: &str, hash_key: &str) -> GlobalSecondaryIndexDescription {
+        term_index_with_projection(name, hash_key, ProjectionType::All)
+    }
+
+    fn term_index_with_projection(
+        name: &str,
+        hash_key: &str,
+        projection_type: ProjectionType,
+    ) -> GlobalSecondaryIndexDescription {
+        GlobalSecondaryIndexDescription::builder()
+            .index_name(name)
+            .key_schema(key(hash_key, KeyType::Hash))
+            .projection(
+                Projection::builder()
+                    .projection_type(projection_type)
+                    .build(),
+            )
+            .build()
+    }
+
+    fn description(
+        term_type: ScalarAttributeType,
+        index: GlobalSecondaryIndexDescription,
+    ) -> TableDescription {
+        TableDescription::builder()
+            .key_schema(key("pk", KeyType::Hash))
+            .key_schema(key("sk", KeyType::Range))
+            .attribute_definitions(attr("pk", ScalarAttributeType::S))
+            .attribute_definitions(attr("sk", ScalarAttributeType::S))
+            .attribute_definitions(attr("term", term_type))
+            .global_secondary_indexes(index)
+            .build()
+    }
+
+    #[test]
+    fn test_valid_table() {
+        let description = description(ScalarAttributeType::B, term_index("TermIndex", "term"));
+
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::default()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_missing_term_index() {
+        let description = description(ScalarAttributeType::B, term_index("OtherIndex", "term"));
+
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::default()
+            ),
+            Err("missing global secondary index 'TermIndex'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_wrong_term_type() {
+        let description = description(ScalarAttributeType::S, term_index("TermIndex", "term"));
+
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::default()
+            ),
+            Err("attribute 'term' has type S, expected B".to_string())
+        );
+    }
+
+    #[test]
+    fn test_wrong_index_key() {
+        let description = description(ScalarAttributeType::B, term_index("TermIndex", "pk"));
+
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::default()
+            ),
+            Err("index 'TermIndex' key schema has hash key 'pk', expected 'term'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_partial_projection() {
+        let description = description(
+            ScalarAttributeType::B,
+            term_index_with_projection("TermIndex", "term", ProjectionType::KeysOnly),
+        );
+
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::default()
+            ),
+            Err("index 'TermIndex' has projection type KEYS_ONLY, expected ALL".to_string())
+        );
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::new().allow_partial_projection()
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let layout = TableLayout::new("PK", "SK", "GSI1PK", "GSI1");
+
+        let description = TableDescription::builder()
+            .key_schema(key("PK", KeyType::Hash))
+            .key_schema(key("SK", KeyType::Range))
+            .attribute_definitions(attr("PK", ScalarAttributeType::S))
+            .attribute_definitions(attr("SK", ScalarAttributeType::S))
+            .attribute_definitions(attr("GSI1PK", ScalarAttributeType::B))
+            .global_secondary_indexes(term_index("GSI1", "GSI1PK"))
+            .build();
+
+        assert_eq!(
+            check_table_description(&description, &layout, &VerifyOptions::default()),
+            Ok(())
+        );
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::default()
+            ),
+            Err("table key schema has hash key 'PK', expected 'pk'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_sort_key() {
+        let description = TableDescription::builder()
+            .key_schema(key("pk", KeyType::Hash))
+            .build();
+
+        assert_eq!(
+            check_table_description(
+                &description,
+                &TableLayout::default(),
+                &VerifyOptions::default()
+            ),
+            Err("table key schema is missing range key 'sk'".to_string())
+        );
+    }
+}
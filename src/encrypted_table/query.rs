@@ -1,17 +1,15 @@
 use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
-use cipherstash_client::{
-    encryption::{
-        compound_indexer::{ComposableIndex, ComposablePlaintext},
-        Plaintext,
-    },
-    IdentifiedBy,
+use cipherstash_client::encryption::{
+    compound_indexer::{ComposableIndex, ComposablePlaintext},
+    Plaintext,
 };
 use itertools::Itertools;
 use std::{borrow::Cow, collections::HashMap, marker::PhantomData};
 use uuid::Uuid;
 
 use crate::{
-    traits::{Decryptable, Searchable},
+    errors::DecryptError,
+    traits::{Decryptable, PrimaryKeyParts, Searchable},
     Identifiable, IndexType, SingleIndex,
 };
 use cipherstash_client::encryption::IndexTerm;
@@ -74,8 +72,9 @@ impl PreparedQuery {
             .db
             .query()
             .table_name(&table.db.table_name)
-            .index_name("TermIndex")
-            .key_condition_expression("term = :term")
+            .index_name(&table.layout.term_index)
+            .key_condition_expression("#term = :term")
+            .expression_attribute_names("#term", &table.layout.term)
             .expression_attribute_values(":term", term);
 
         query
@@ -155,18 +154,24 @@ where
     where
         T: Decryptable + Identifiable,
     {
-        let keyset_id = self.dataset_id.map(IdentifiedBy::Uuid);
+        let storage = self.storage;
+        let scoped_cipher = storage.scoped_cipher(self.dataset_id).await?;
+        let items = self.send_items(&scoped_cipher).await?;
+        let results =
+            super::decrypt_all(&storage.cipher, &scoped_cipher, &storage.layout, items).await?;
 
-        let scoped_cipher =
-            ScopedZeroKmsCipher::init(self.storage.cipher.clone(), keyset_id).await?;
+        Ok(results)
+    }
 
+    /// Send the query and return the matching items without decrypting them.
+    async fn send_items(
+        self,
+        scoped_cipher: &ScopedZeroKmsCipher,
+    ) -> Result<Vec<HashMap<String, AttributeValue>>, QueryError> {
         let storage = self.storage;
         let query = self.build()?;
 
-        let items = query.send(storage, &scoped_cipher).await?;
-        let results = super::decrypt_all(&storage.cipher, items).await?;
-
-        Ok(results)
+        query.send(storage, scoped_cipher).await
     }
 }
 
@@ -177,18 +182,36 @@ where
     pub async fn send(self) -> Result<Vec<S>, QueryError> {
         self.load::<S>().await
     }
+
+    /// Load all records matching the query, returning a result for each item paired with its
+    /// primary key, so that items that can't be decrypted don't fail the whole query.
+    pub async fn send_partial(
+        self,
+    ) -> Result<Vec<(Option<PrimaryKeyParts>, Result<S, DecryptError>)>, QueryError> {
+        let storage = self.storage;
+        let scoped_cipher = storage.scoped_cipher(self.dataset_id).await?;
+        let items = self.send_items(&scoped_cipher).await?;
+
+        Ok(super::decrypt_each(&storage.cipher, &scoped_cipher, &storage.layout, items).await)
+    }
 }
 
+/// Looks up the indexer for an index by its name and type, see [`Searchable::index_by_name`].
+pub type IndexByName =
+    Box<dyn Fn(&str, IndexType) -> Option<Box<dyn ComposableIndex + Send>> + Send + Sync>;
+
 pub struct PreparedQueryBuilder {
     pub type_name: Cow<'static, str>,
-    pub index_by_name: fn(&str, IndexType) -> Option<Box<dyn ComposableIndex + Send>>,
+    pub index_by_name: IndexByName,
 }
 
 impl PreparedQueryBuilder {
     pub fn new<S: Searchable>() -> Self {
         Self {
             type_name: S::type_name(),
-            index_by_name: S::index_by_name,
+            index_by_name: Box::new(
+                S::index_by_name as fn(&str, IndexType) -> Option<Box<dyn ComposableIndex + Send>>,
+            ),
         }
     }
 
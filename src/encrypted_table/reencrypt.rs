@@ -0,0 +1,510 @@
+use aws_sdk_dynamodb::types::{AttributeValue, Delete, Put, TransactWriteItem};
+use itertools::Itertools;
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+use super::{
+    decrypt_each_with_spec, mixed::has_descriptor_prefix, scoped_cipher_cache::ScopedCipherCache,
+    DatasetId, Dynamo, DynamoRecordPatch, EncryptedTable, PreparedDelete, PreparedRecord,
+    ScopedZeroKmsCipher, TableEntry, TableLayout, TypeRegistry, ZeroKmsCipher,
+};
+use crate::{
+    crypto::{SealedTableEntry, UnsealSpec, MAC_ATTRIBUTE},
+    errors::{DecryptError, PutError, ReadConversionError, ReencryptError},
+    traits::{PrimaryKey, PrimaryKeyParts, RecordMac},
+    Decryptable, Identifiable, Searchable,
+};
+
+/// The progress of a [`ReencryptJob`], reported after each page of the table is processed.
+#[derive(Debug, Default)]
+pub struct ReencryptProgress {
+    /// The number of records of the type that have been read.
+    pub scanned: usize,
+
+    /// The number of records that have been re-encrypted.
+    pub reencrypted: usize,
+
+    /// Records that were changed by another writer after they were read, and so were not
+    /// re-encrypted.
+    pub conflicts: Vec<PrimaryKeyParts>,
+
+    /// Records that could not be decrypted, for example because they belong to another dataset.
+    pub failures: Vec<(PrimaryKeyParts, DecryptError)>,
+
+    /// The key to resume the job from with [`ReencryptJob::resume_from`], or `None` once the whole
+    /// table has been processed.
+    pub checkpoint: Option<PrimaryKeyParts>,
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&ReencryptProgress) + Send + 'a>;
+
+/// A job that re-encrypts every record of type `T` in a table, created with
+/// [`EncryptedTable::reencrypt`].
+///
+/// The job scans the table for the root items of `T` and decrypts them with the cipher of the
+/// table. Each record is then encrypted again with fresh data keys, and optionally with another
+/// cipher or into another dataset, and its root and index term items are written again.
+///
+/// The write of each record is conditional on the record not having changed since it was read,
+/// so that concurrent updates aren't lost. Records that did change are reported as
+/// [`ReencryptProgress::conflicts`] and are left as they are. DynamoDB transactions are limited
+/// to 100 items, so the items of a record with more index terms than that are written in several
+/// transactions and only the first one, which writes the root item, has the condition. If a
+/// later transaction fails, the record is left with some of its new index terms written.
+///
+/// When records are written into another dataset with [`ReencryptJob::to_dataset`], the items
+/// the job writes are in the same table and may be read again by the scan. Items that were
+/// already written into the target dataset, by this or an earlier run of the job, are skipped
+/// and aren't counted as [`ReencryptProgress::scanned`].
+///
+/// Records stored before MACs were added are read even if `T` uses [`RecordMac::Required`], so
+/// that the job can write them again with a MAC.
+///
+/// ```no_run
+/// # use cipherstash_dynamodb::*;
+/// # #[derive(Debug, Encryptable, Decryptable, Identifiable, Searchable)]
+/// # struct User { #[partition_key] email: String, name: String }
+/// # async fn example(table: EncryptedTable) -> Result<(), Box<dyn std::error::Error>> {
+/// let progress = table
+///     .reencrypt::<User>()
+///     .on_progress(|progress| println!("re-encrypted {} users", progress.reencrypted))
+///     .run()
+///     .await?;
+///
+/// assert!(progress.checkpoint.is_none());
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReencryptJob<'a, T> {
+    table: &'a EncryptedTable<Dynamo>,
+    cipher: Option<Arc<ZeroKmsCipher>>,
+    from_dataset: Option<DatasetId>,
+    to_dataset: Option<DatasetId>,
+    checkpoint: Option<PrimaryKeyParts>,
+    page_size: Option<i32>,
+    on_progress: Option<ProgressFn<'a>>,
+    __record: PhantomData<T>,
+}
+
+impl EncryptedTable<Dynamo> {
+    /// Create a job that re-encrypts every record of type `T`, see [`ReencryptJob`].
+    pub fn reencrypt<T>(&self) -> ReencryptJob<'_, T>
+    where
+        T: Searchable + Decryptable + Identifiable,
+    {
+        ReencryptJob {
+            table: self,
+            cipher: None,
+            from_dataset: None,
+            to_dataset: None,
+            checkpoint: None,
+            page_size: None,
+            on_progress: None,
+            __record: PhantomData,
+        }
+    }
+
+    /// Build the transaction that writes `record`, which was read from the item with `old_key`
+    /// in the dataset `from`, into the dataset `to` using the cipher of `target`.
+    ///
+    /// Items that are no longer used are deleted, such as the old items of a record whose keys
+    /// changed because it was written into another dataset. The write or delete of the old root
+    /// item is the first item of the transaction, and is conditional on its MAC still being
+    /// `mac` so that changes made since it was read aren't lost.
+    pub(super) async fn reseal_items<T>(
+        &self,
+        target: &EncryptedTable<Dynamo>,
+        record: T,
+        old_key: &PrimaryKeyParts,
+        mac: Option<AttributeValue>,
+        from: Option<DatasetId>,
+        to: Option<DatasetId>,
+    ) -> Result<Vec<TransactWriteItem>, ReencryptError>
+    where
+        T: Searchable + Identifiable,
+    {
+        let plaintext_key = record
+            .get_primary_key()
+            .into_parts(&T::type_name(), T::sort_key_prefix().as_deref());
+
+        let DynamoRecordPatch {
+            put_records,
+            delete_records,
+        } = target
+            .create_put_patch(
+                PreparedRecord::prepare_record(record).map_err(PutError::from)?,
+                to,
+                |_, _| true,
+            )
+            .await?;
+
+        let old_records = self
+            .create_delete_patch(PreparedDelete::new_from_parts::<T>(plaintext_key), from)
+            .await?
+            .delete_records;
+
+        let condition = Unchanged::new(mac, &self.layout);
+        let mut items = Vec::with_capacity(put_records.len() + delete_records.len() + 1);
+
+        for item in put_records.iter() {
+            let put = Put::builder()
+                .table_name(&self.db.table_name)
+                .set_item(Some(item.clone()));
+
+            if has_key(item, old_key, &self.layout) {
+                let put = condition
+                    .apply_to_put(put)
+                    .build()
+                    .map_err(PutError::from)?;
+                items.insert(0, TransactWriteItem::builder().put(put).build());
+            } else {
+                let put = put.build().map_err(PutError::from)?;
+                items.push(TransactWriteItem::builder().put(put).build());
+            }
+        }
+
+        let deletes = delete_records
+            .into_iter()
+            .chain(old_records)
+            .filter(|key| {
+                !put_records
+                    .iter()
+                    .any(|item| has_key(item, key, &self.layout))
+            })
+            .unique();
+
+        for key in deletes {
+            let delete = Delete::builder()
+                .table_name(&self.db.table_name)
+                .key(
+                    &self.layout.partition_key,
+                    AttributeValue::S(key.pk.clone()),
+                )
+                .key(&self.layout.sort_key, AttributeValue::S(key.sk.clone()));
+
+            if &key == old_key {
+                let delete = condition
+                    .apply_to_delete(delete)
+                    .build()
+                    .map_err(PutError::from)?;
+                items.insert(0, TransactWriteItem::builder().delete(delete).build());
+            } else {
+                let delete = delete.build().map_err(PutError::from)?;
+                items.push(TransactWriteItem::builder().delete(delete).build());
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// A table that writes to the same DynamoDB table as this one with another cipher.
+    pub(super) fn with_cipher(&self, cipher: Arc<ZeroKmsCipher>) -> EncryptedTable<Dynamo> {
+        EncryptedTable {
+            db: Dynamo {
+                db: self.db.db.clone(),
+                table_name: self.db.table_name.clone(),
+            },
+            cipher,
+            layout: self.layout.clone(),
+            registry: TypeRegistry::default(),
+            scoped_ciphers: ScopedCipherCache::new(self.scoped_ciphers.ttl()),
+        }
+    }
+}
+
+impl<'a, T> ReencryptJob<'a, T>
+where
+    T: Searchable + Decryptable + Identifiable,
+{
+    /// Encrypt the records with another cipher, for example one created with a rotated client
+    /// key. By default the cipher of the table is used.
+    pub fn to_cipher(mut self, cipher: Arc<ZeroKmsCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Read records from a specific dataset instead of the default dataset.
+    pub fn from_dataset(mut self, dataset_id: DatasetId) -> Self {
+        self.from_dataset = Some(dataset_id);
+        self
+    }
+
+    /// Write records into a specific dataset instead of the default dataset.
+    ///
+    /// The keys of records change when they are written into another dataset, so the items they
+    /// were read from are deleted.
+    pub fn to_dataset(mut self, dataset_id: DatasetId) -> Self {
+        self.to_dataset = Some(dataset_id);
+        self
+    }
+
+    /// Resume a job from the [`ReencryptProgress::checkpoint`] of an earlier run.
+    pub fn resume_from(mut self, checkpoint: PrimaryKeyParts) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// The maximum number of items to read from the table for each page.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Call `f` with the progress of the job after each page of the table is processed.
+    pub fn on_progress(mut self, f: impl FnMut(&ReencryptProgress) + Send + 'a) -> Self {
+        self.on_progress = Some(Box::new(f));
+        self
+    }
+
+    /// Run the job until every record has been processed.
+    ///
+    /// Records that can't be decrypted or that changed while the job was running are reported in
+    /// the returned [`ReencryptProgress`]. Other errors stop the job, which can then be resumed
+    /// from the last checkpoint passed to [`ReencryptJob::on_progress`].
+    pub async fn run(mut self) -> Result<ReencryptProgress, ReencryptError> {
+        let table = self.table;
+        let target = self.cipher.take().map(|cipher| table.with_cipher(cipher));
+        let target = target.as_ref().unwrap_or(table);
+
+        let scoped_cipher = table.scoped_cipher(self.from_dataset).await?;
+
+        // Records written into another dataset are in the same table, so they may be scanned again
+        let target_scoped_cipher = if self.to_dataset != self.from_dataset {
+            Some(target.scoped_cipher(self.to_dataset).await?)
+        } else {
+            None
+        };
+
+        let mut registry = TypeRegistry::default();
+        registry.register::<T>()?;
+
+        let mut progress = ReencryptProgress {
+            checkpoint: self.checkpoint.take(),
+            ..Default::default()
+        };
+
+        loop {
+            let page = table
+                .db
+                .scan()
+                .table_name(&table.db.table_name)
+                // Index term items are written again with their root item
+                .filter_expression("attribute_not_exists(#term)")
+                .expression_attribute_names("#term", &table.layout.term)
+                .set_exclusive_start_key(
+                    progress
+                        .checkpoint
+                        .as_ref()
+                        .map(|key| key_item(key, &table.layout)),
+                )
+                .set_limit(self.page_size)
+                .send()
+                .await
+                .map_err(|e| ReencryptError::DynamoError(Box::new(e)))?;
+
+            let items = page
+                .items
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|item| is_record_of::<T>(item, &table.layout, &registry))
+                .filter(|item| {
+                    target_scoped_cipher
+                        .as_deref()
+                        .is_none_or(|target_scoped_cipher| {
+                            !is_sealed_with(item, &table.layout, target_scoped_cipher)
+                        })
+                })
+                .collect_vec();
+
+            let macs = items
+                .iter()
+                .map(|item| item.get(MAC_ATTRIBUTE).cloned())
+                .collect_vec();
+
+            // Records stored before MACs were added are read so that they are written with one
+            let spec = UnsealSpec::new_for_decryptable::<T>().with_record_mac(RecordMac::Optional);
+            let records = decrypt_each_with_spec::<T>(
+                &table.cipher,
+                &scoped_cipher,
+                &table.layout,
+                spec,
+                items,
+            )
+            .await;
+
+            for ((key, record), mac) in records.into_iter().zip_eq(macs) {
+                progress.scanned += 1;
+
+                // Items without a primary key aren't records of `T` so they were filtered out
+                let Some(key) = key else {
+                    continue;
+                };
+
+                let record = match record {
+                    Ok(record) => record,
+                    Err(error) => {
+                        progress.failures.push((key, error));
+                        continue;
+                    }
+                };
+
+                let items = table
+                    .reseal_items(
+                        target,
+                        record,
+                        &key,
+                        mac,
+                        self.from_dataset,
+                        self.to_dataset,
+                    )
+                    .await?;
+
+                match table.send_transact_items(items).await {
+                    Ok(()) => progress.reencrypted += 1,
+                    Err(error) => {
+                        let error = PutError::DynamoError(Box::new(error));
+
+                        if error
+                            .dynamo_error_kind()
+                            .is_some_and(|kind| kind.is_conditional_check_failed())
+                        {
+                            progress.conflicts.push(key);
+                        } else {
+                            return Err(error.into());
+                        }
+                    }
+                }
+            }
+
+            progress.checkpoint = page
+                .last_evaluated_key
+                .map(|key| read_key(&key, &table.layout))
+                .transpose()
+                .map_err(DecryptError::from)?;
+
+            if let Some(on_progress) = self.on_progress.as_mut() {
+                on_progress(&progress);
+            }
+
+            if progress.checkpoint.is_none() {
+                return Ok(progress);
+            }
+        }
+    }
+}
+
+/// The condition that an item hasn't been changed or deleted since it was read.
+///
+/// Every write changes the MAC of an item as encrypted attributes are written with fresh data
+/// keys. Items written before MACs were added don't have one, so they are only checked for
+/// existence and for not having been written again since.
+struct Unchanged {
+    expression: &'static str,
+    names: HashMap<String, String>,
+    values: Option<HashMap<String, AttributeValue>>,
+}
+
+impl Unchanged {
+    fn new(mac: Option<AttributeValue>, layout: &TableLayout) -> Self {
+        match mac {
+            Some(mac) => Self {
+                expression: "#mac = :mac",
+                names: HashMap::from([("#mac".to_string(), MAC_ATTRIBUTE.to_string())]),
+                values: Some(HashMap::from([(":mac".to_string(), mac)])),
+            },
+            None => Self {
+                expression: "attribute_exists(#pk) AND attribute_not_exists(#mac)",
+                names: HashMap::from([
+                    ("#pk".to_string(), layout.partition_key.clone()),
+                    ("#mac".to_string(), MAC_ATTRIBUTE.to_string()),
+                ]),
+                values: None,
+            },
+        }
+    }
+
+    fn apply_to_put(
+        &self,
+        put: aws_sdk_dynamodb::types::builders::PutBuilder,
+    ) -> aws_sdk_dynamodb::types::builders::PutBuilder {
+        put.condition_expression(self.expression)
+            .set_expression_attribute_names(Some(self.names.clone()))
+            .set_expression_attribute_values(self.values.clone())
+    }
+
+    fn apply_to_delete(
+        &self,
+        delete: aws_sdk_dynamodb::types::builders::DeleteBuilder,
+    ) -> aws_sdk_dynamodb::types::builders::DeleteBuilder {
+        delete
+            .condition_expression(self.expression)
+            .set_expression_attribute_names(Some(self.names.clone()))
+            .set_expression_attribute_values(self.values.clone())
+    }
+}
+
+/// Returns true if `item` is a root item of a record of type `T`, identified in the same way as
+/// by [`super::MixedDecoder`].
+fn is_record_of<T>(
+    item: &HashMap<String, AttributeValue>,
+    layout: &TableLayout,
+    registry: &TypeRegistry,
+) -> bool
+where
+    T: Decryptable + Identifiable,
+{
+    let Ok(entry) = SealedTableEntry::from_item(item.clone(), layout) else {
+        return false;
+    };
+    let entry: &TableEntry = entry.inner();
+
+    let spec = UnsealSpec::new_for_decryptable::<T>();
+
+    has_descriptor_prefix(entry, &spec.protected_attributes, &spec.sort_key_prefix)
+        || registry.type_for_sort_key(&entry.sk).is_some()
+}
+
+/// Returns true if `item` was written into the dataset of `scoped_cipher`, see
+/// [`SealedTableEntry::is_sealed_with`].
+fn is_sealed_with(
+    item: &HashMap<String, AttributeValue>,
+    layout: &TableLayout,
+    scoped_cipher: &ScopedZeroKmsCipher,
+) -> bool {
+    SealedTableEntry::from_item(item.clone(), layout)
+        .is_ok_and(|entry| entry.is_sealed_with(scoped_cipher))
+}
+
+fn has_key(
+    item: &HashMap<String, AttributeValue>,
+    key: &PrimaryKeyParts,
+    layout: &TableLayout,
+) -> bool {
+    matches!(item.get(&layout.partition_key), Some(AttributeValue::S(pk)) if *pk == key.pk)
+        && matches!(item.get(&layout.sort_key), Some(AttributeValue::S(sk)) if *sk == key.sk)
+}
+
+fn key_item(key: &PrimaryKeyParts, layout: &TableLayout) -> HashMap<String, AttributeValue> {
+    HashMap::from([
+        (
+            layout.partition_key.clone(),
+            AttributeValue::S(key.pk.clone()),
+        ),
+        (layout.sort_key.clone(), AttributeValue::S(key.sk.clone())),
+    ])
+}
+
+pub(super) fn read_key(
+    item: &HashMap<String, AttributeValue>,
+    layout: &TableLayout,
+) -> Result<PrimaryKeyParts, ReadConversionError> {
+    let read = |name: &str| match item.get(name) {
+        Some(AttributeValue::S(value)) => Ok(value.clone()),
+        Some(_) => Err(ReadConversionError::InvalidFormat(name.to_string())),
+        None => Err(ReadConversionError::NoSuchAttribute(name.to_string())),
+    };
+
+    Ok(PrimaryKeyParts {
+        pk: read(&layout.partition_key)?,
+        sk: read(&layout.sort_key)?,
+    })
+}
@@ -0,0 +1,362 @@
+use std::borrow::Cow;
+
+use crate::{errors::RegistryError, traits::TypeSchema, Searchable};
+
+/// The range of sort keys used by the root records and index terms of a registered type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKeySpace<'a> {
+    /// Types with only a partition key use their type name as the sort key.
+    Exact(&'a str),
+    /// Types with a sort key prefix use sort keys of the form `prefix#value`.
+    Prefix(&'a str),
+    /// Types with a sort key and no prefix can use any sort key.
+    Any,
+}
+
+impl<'a> SortKeySpace<'a> {
+    fn of(schema: &'a TypeSchema) -> Self {
+        match (&schema.sort_key, &schema.sort_key_prefix) {
+            (None, _) => Self::Exact(&schema.type_name),
+            (Some(_), Some(prefix)) => Self::Prefix(prefix),
+            (Some(_), None) => Self::Any,
+        }
+    }
+
+    /// Returns true if the sort key of a root record or index term belongs to this space.
+    fn contains(&self, sort_key: &str) -> bool {
+        match self {
+            Self::Exact(value) => sort_key == *value || is_prefixed(sort_key, value),
+            Self::Prefix(prefix) => is_prefixed(sort_key, prefix),
+            Self::Any => true,
+        }
+    }
+
+    /// Returns true if sort keys (or index term keys derived from them) of the two spaces can be
+    /// equal.
+    fn overlaps(&self, other: &SortKeySpace<'_>) -> bool {
+        use SortKeySpace::*;
+
+        match (self, other) {
+            (Any, _) | (_, Any) => true,
+            (Exact(a), Exact(b)) => a == b,
+            // Index terms of an exact sort key `a` are stored under `a#...`
+            (Exact(a), Prefix(b)) | (Prefix(b), Exact(a)) => a == b || is_prefixed(a, b),
+            (Prefix(a), Prefix(b)) => a == b || is_prefixed(a, b) || is_prefixed(b, a),
+        }
+    }
+}
+
+impl std::fmt::Display for SortKeySpace<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(value) => write!(f, "sort key '{value}'"),
+            Self::Prefix(prefix) => write!(f, "sort key prefix '{prefix}'"),
+            Self::Any => f.write_str("unprefixed sort keys"),
+        }
+    }
+}
+
+/// The prefix of the descriptors of encrypted attributes of a type, see [`crate::crypto::UnsealSpec`].
+fn descriptor_prefix(schema: &TypeSchema) -> &str {
+    schema
+        .sort_key_prefix
+        .as_deref()
+        .unwrap_or(&schema.type_name)
+}
+
+/// The info used to derive the index terms of an index of a type, see
+/// [`crate::crypto::Sealer`].
+fn index_info(schema: &TypeSchema, index_name: &str) -> String {
+    format!("{}#{}", schema.type_name, index_name)
+}
+
+fn is_prefixed(value: &str, prefix: &str) -> bool {
+    value
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.starts_with('#'))
+}
+
+/// The types stored in a table.
+///
+/// Types in a single-table design must not share a type name, a descriptor prefix, which
+/// identifies the type of encrypted attributes, or a sort key space, which would make records and
+/// index terms of one type overwrite those of another. Index terms are derived from the type name
+/// and index name separated by `#`, so those must not combine into the same value either.
+/// The registry rejects types that would conflict with a type that has already been registered.
+#[derive(Debug, Clone, Default)]
+pub struct TypeRegistry {
+    types: Vec<TypeSchema>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the type `T`.
+    pub fn register<T: Searchable>(&mut self) -> Result<(), RegistryError> {
+        self.register_schema(T::type_schema())
+    }
+
+    /// Register a type from its [`TypeSchema`].
+    pub fn register_schema(&mut self, schema: TypeSchema) -> Result<(), RegistryError> {
+        let space = SortKeySpace::of(&schema);
+
+        for existing in self.types.iter() {
+            if existing.type_name == schema.type_name {
+                return Err(RegistryError::DuplicateTypeName(
+                    schema.type_name.to_string(),
+                ));
+            }
+
+            let existing_space = SortKeySpace::of(existing);
+
+            if space.overlaps(&existing_space) {
+                return Err(RegistryError::ConflictingSortKey {
+                    type_name: schema.type_name.to_string(),
+                    sort_key: space.to_string(),
+                    existing_type_name: existing.type_name.to_string(),
+                    existing_sort_key: existing_space.to_string(),
+                });
+            }
+
+            if descriptor_prefix(existing) == descriptor_prefix(&schema) {
+                return Err(RegistryError::ConflictingDescriptorPrefix {
+                    type_name: schema.type_name.to_string(),
+                    existing_type_name: existing.type_name.to_string(),
+                    prefix: descriptor_prefix(&schema).to_string(),
+                });
+            }
+
+            for index in schema.indexes.iter() {
+                let info = index_info(&schema, &index.name);
+
+                if let Some(existing_index) = existing
+                    .indexes
+                    .iter()
+                    .find(|existing_index| index_info(existing, &existing_index.name) == info)
+                {
+                    return Err(RegistryError::ConflictingIndexName {
+                        type_name: schema.type_name.to_string(),
+                        index_name: index.name.to_string(),
+                        existing_type_name: existing.type_name.to_string(),
+                        existing_index_name: existing_index.name.to_string(),
+                    });
+                }
+            }
+        }
+
+        self.types.push(schema);
+
+        Ok(())
+    }
+
+    /// Returns the schema of the registered type with the given name.
+    pub fn get(&self, type_name: &str) -> Option<&TypeSchema> {
+        self.types
+            .iter()
+            .find(|schema| schema.type_name == type_name)
+    }
+
+    /// Returns the registered type that owns a sort key.
+    ///
+    /// Registration guarantees that at most one type matches.
+    pub fn type_for_sort_key(&self, sort_key: &str) -> Option<&TypeSchema> {
+        self.types
+            .iter()
+            .find(|schema| SortKeySpace::of(schema).contains(sort_key))
+    }
+
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.get(type_name).is_some()
+    }
+
+    pub fn type_names(&self) -> impl Iterator<Item = Cow<'static, str>> + '_ {
+        self.types.iter().map(|schema| schema.type_name.clone())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TypeSchema> {
+        self.types.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        traits::{IndexSchema, KeySchema},
+        IndexType, SingleIndex,
+    };
+
+    fn schema(type_name: &'static str, sort_key: bool, prefix: Option<&'static str>) -> TypeSchema {
+        TypeSchema {
+            type_name: Cow::Borrowed(type_name),
+            sort_key_prefix: prefix.map(Cow::Borrowed),
+            partition_key: KeySchema {
+                field: None,
+                encrypted: true,
+            },
+            sort_key: sort_key.then_some(KeySchema {
+                field: None,
+                encrypted: true,
+            }),
+            attributes: vec![],
+            indexes: vec![],
+            skipped: vec![],
+        }
+    }
+
+    #[test]
+    fn test_register_distinct_types() {
+        let mut registry = TypeRegistry::new();
+
+        registry
+            .register_schema(schema("user", true, Some("user")))
+            .unwrap();
+        registry
+            .register_schema(schema("license", true, Some("license")))
+            .unwrap();
+        registry
+            .register_schema(schema("settings", false, None))
+            .unwrap();
+
+        assert_eq!(registry.len(), 3);
+        assert_eq!(
+            registry
+                .type_for_sort_key("license#123")
+                .map(|s| s.type_name.as_ref()),
+            Some("license")
+        );
+        assert_eq!(
+            registry
+                .type_for_sort_key("settings")
+                .map(|s| s.type_name.as_ref()),
+            Some("settings")
+        );
+        assert_eq!(
+            registry
+                .type_for_sort_key("settings#theme#exact#0")
+                .map(|s| s.type_name.as_ref()),
+            Some("settings")
+        );
+        assert!(registry.type_for_sort_key("userx#123").is_none());
+    }
+
+    #[test]
+    fn test_duplicate_type_name() {
+        let mut registry = TypeRegistry::new();
+
+        registry
+            .register_schema(schema("user", true, Some("a")))
+            .unwrap();
+
+        assert!(matches!(
+            registry.register_schema(schema("user", true, Some("b"))),
+            Err(RegistryError::DuplicateTypeName(name)) if name == "user"
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_prefixes() {
+        let mut registry = TypeRegistry::new();
+
+        registry
+            .register_schema(schema("user", true, Some("user")))
+            .unwrap();
+
+        assert!(matches!(
+            registry.register_schema(schema("admin", true, Some("user"))),
+            Err(RegistryError::ConflictingSortKey { .. })
+        ));
+        assert!(matches!(
+            registry.register_schema(schema("admin", true, Some("user#admin"))),
+            Err(RegistryError::ConflictingSortKey { .. })
+        ));
+        assert!(matches!(
+            registry.register_schema(schema("admin", false, None)),
+            Ok(())
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_descriptor_prefix() {
+        let mut registry = TypeRegistry::new();
+
+        registry
+            .register_schema(schema("user", false, Some("account")))
+            .unwrap();
+
+        assert!(matches!(
+            registry.register_schema(schema("admin", true, Some("account"))),
+            Err(RegistryError::ConflictingDescriptorPrefix { prefix, .. }) if prefix == "account"
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_index_names() {
+        let mut registry = TypeRegistry::new();
+
+        let mut user = schema("user", true, Some("user"));
+        user.indexes.push(IndexSchema::new(
+            "email#name",
+            IndexType::Compound2((SingleIndex::Exact, SingleIndex::Exact)),
+        ));
+        registry.register_schema(user).unwrap();
+
+        // Another index with the same name is fine as the type name is part of the index terms
+        let mut license = schema("license", true, Some("license"));
+        license.indexes.push(IndexSchema::new(
+            "email",
+            IndexType::Single(SingleIndex::Exact),
+        ));
+        registry.register_schema(license).unwrap();
+
+        let mut other = schema("user#email", true, Some("other"));
+        other.indexes.push(IndexSchema::new(
+            "name",
+            IndexType::Single(SingleIndex::Exact),
+        ));
+
+        assert!(matches!(
+            registry.register_schema(other),
+            Err(RegistryError::ConflictingIndexName { existing_type_name, .. }) if existing_type_name == "user"
+        ));
+    }
+
+    #[test]
+    fn test_exact_sort_key_conflicts_with_prefix() {
+        let mut registry = TypeRegistry::new();
+
+        registry
+            .register_schema(schema("user", false, None))
+            .unwrap();
+
+        // Index terms for `user` are stored under `user#...`
+        assert!(matches!(
+            registry.register_schema(schema("other", true, Some("user"))),
+            Err(RegistryError::ConflictingSortKey { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unprefixed_sort_key_conflicts_with_everything() {
+        let mut registry = TypeRegistry::new();
+
+        registry
+            .register_schema(schema("user", true, None))
+            .unwrap();
+
+        assert!(matches!(
+            registry.register_schema(schema("license", true, Some("license"))),
+            Err(RegistryError::ConflictingSortKey { .. })
+        ));
+    }
+}
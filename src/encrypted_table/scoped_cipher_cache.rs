@@ -0,0 +1,83 @@
+use super::{DatasetId, ScopedZeroKmsCipher, ZeroKmsCipher};
+use cipherstash_client::{zerokms, IdentifiedBy};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// How long the cipher of a dataset is cached by default, see
+/// [`super::EncryptedTable::with_scoped_cipher_ttl`].
+pub const DEFAULT_SCOPED_CIPHER_TTL: Duration = Duration::from_secs(300);
+
+type Entries = HashMap<Option<DatasetId>, (Instant, Arc<ScopedZeroKmsCipher>)>;
+
+/// The ciphers of the datasets used by a table, so that the keys of a dataset are loaded from
+/// ZeroKMS once per TTL rather than for each request.
+pub(crate) struct ScopedCipherCache {
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+impl ScopedCipherCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Get the cipher of a dataset, or of the default dataset if `dataset_id` is `None`, creating
+    /// it if it isn't cached or has expired.
+    ///
+    /// The lock isn't held while the cipher is created so concurrent misses for the same dataset
+    /// may each create it, in which case the last one is kept.
+    pub(crate) async fn get_or_init(
+        &self,
+        cipher: &Arc<ZeroKmsCipher>,
+        dataset_id: Option<DatasetId>,
+    ) -> Result<Arc<ScopedZeroKmsCipher>, zerokms::Error> {
+        if let Some(scoped_cipher) = self.get(dataset_id) {
+            return Ok(scoped_cipher);
+        }
+
+        let keyset_id = dataset_id.map(IdentifiedBy::Uuid);
+        let scoped_cipher = Arc::new(ScopedZeroKmsCipher::init(cipher.clone(), keyset_id).await?);
+
+        if !self.ttl.is_zero() {
+            let now = Instant::now();
+            let mut entries = self.lock();
+
+            // Drop expired entries so datasets that are no longer used don't stay in memory
+            entries.retain(|_, (created_at, _)| now.duration_since(*created_at) < self.ttl);
+            entries.insert(dataset_id, (now, scoped_cipher.clone()));
+        }
+
+        Ok(scoped_cipher)
+    }
+
+    fn get(&self, dataset_id: Option<DatasetId>) -> Option<Arc<ScopedZeroKmsCipher>> {
+        self.lock()
+            .get(&dataset_id)
+            .filter(|(created_at, _)| created_at.elapsed() < self.ttl)
+            .map(|(_, scoped_cipher)| scoped_cipher.clone())
+    }
+
+    pub(crate) fn invalidate(&self, dataset_id: Option<DatasetId>) {
+        self.lock().remove(&dataset_id);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        // The entries are always left in a valid state so a panic while the lock was held
+        // doesn't need to be propagated
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
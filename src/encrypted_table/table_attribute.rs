@@ -1,5 +1,7 @@
-use super::{ReadConversionError, SealError};
+use super::{ExpectedBinding, ReadConversionError, SealError};
+use crate::traits::KeyPart;
 use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+use chrono::{DateTime, NaiveDate, Utc};
 use cipherstash_client::zerokms::EncryptedRecord;
 use std::{
     collections::{BTreeMap, HashMap},
@@ -35,30 +37,55 @@ impl TableAttribute {
     /// (which will be verified to be the correct descriptor for the record via AAD).
     ///
     /// If the descriptor does not match, an error is returned and this may indicate that the record
-    /// has been tampered with (e.g. via a confused deputy attack).
+    /// has been tampered with (e.g. via a confused deputy attack). Records that are bound to their
+    /// primary key must also be bound to the record described by `binding`.
     pub(crate) fn as_encrypted_record(
         &self,
         descriptor: &str,
+        binding: &ExpectedBinding,
     ) -> Result<EncryptedRecord, SealError> {
         if let TableAttribute::Bytes(s) = self {
             EncryptedRecord::from_mp_bytes(&s[..])
-                .map_err(|_| SealError::AssertionFailed("Could not parse EncryptedRecord".to_string()))
+                .map_err(|_| SealError::MalformedCiphertext(descriptor.to_string()))
                 .and_then(|record| {
-                    if record.descriptor == descriptor {
-                        Ok(record)
-                    } else {
-                        Err(SealError::AssertionFailed(format!(
-                            "Expected descriptor {}, got {} - WARNING: record may have been tampered with",
-                            descriptor,
-                            record.descriptor
-                        )))
-                    }
+                    binding.verify(&record.descriptor, descriptor)?;
+                    Ok(record)
                 })
         } else {
-            Err(SealError::AssertionFailed(format!(
-                "Expected TableAttribute::Bytes, got {}",
-                descriptor
-            )))
+            Err(SealError::UnsupportedStoredType {
+                attribute: descriptor.to_string(),
+                found: self.type_name(),
+            })
+        }
+    }
+
+    /// Returns the name of the DynamoDB type this attribute is stored as.
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            TableAttribute::String(_) => "a string",
+            TableAttribute::Number(_) => "a number",
+            TableAttribute::Bool(_) => "a boolean",
+            TableAttribute::Bytes(_) => "bytes",
+            TableAttribute::StringVec(_) => "a string set",
+            TableAttribute::ByteVec(_) => "a binary set",
+            TableAttribute::NumberVec(_) => "a number set",
+            TableAttribute::Map(_) => "a map",
+            TableAttribute::List(_) => "a list",
+            TableAttribute::Null => "null",
+        }
+    }
+
+    /// Returns the descriptor of the encrypted record stored in this attribute without decrypting
+    /// it, or the descriptor of one of its entries if this is a map of encrypted records.
+    ///
+    /// The descriptor is only verified when the record is decrypted.
+    pub(crate) fn encrypted_descriptor(&self) -> Option<String> {
+        match self {
+            TableAttribute::Bytes(bytes) => EncryptedRecord::from_mp_bytes(&bytes[..])
+                .ok()
+                .map(|record| record.descriptor),
+            TableAttribute::Map(map) => map.values().find_map(Self::encrypted_descriptor),
+            _ => None,
         }
     }
 
@@ -188,6 +215,34 @@ impl From<&str> for TableAttribute {
     }
 }
 
+// Dates are stored as strings in the format of their key parts, so that plaintext sort keys
+// order the same way as the dates
+macro_rules! impl_table_attr_via_key_part {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for TableAttribute {
+                fn from(value: $ty) -> Self {
+                    TableAttribute::String(value.to_key_part())
+                }
+            }
+
+            impl TryFromTableAttr for $ty {
+                fn try_from_table_attr(value: TableAttribute) -> Result<Self, ReadConversionError> {
+                    let TableAttribute::String(value) = value else {
+                        return Err(ReadConversionError::ConversionFailed(
+                            stringify!($ty).to_string(),
+                        ));
+                    };
+
+                    <$ty>::from_key_part(&value)
+                }
+            }
+        )*
+    };
+}
+
+impl_table_attr_via_key_part!(DateTime<Utc>, NaiveDate);
+
 impl<T> TryFromTableAttr for Option<T>
 where
     T: TryFromTableAttr,
@@ -613,6 +668,29 @@ mod test {
         assert_eq!(original, test_vec);
     }
 
+    #[test]
+    fn test_dates() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let table_attribute = TableAttribute::from(date);
+        assert_eq!(
+            table_attribute,
+            TableAttribute::String("2024-03-01".to_string())
+        );
+        assert_eq!(
+            NaiveDate::try_from_table_attr(table_attribute).unwrap(),
+            date
+        );
+
+        let time = date.and_hms_opt(9, 30, 0).unwrap().and_utc();
+        let table_attribute = TableAttribute::from(time);
+        assert_eq!(
+            DateTime::<Utc>::try_from_table_attr(table_attribute).unwrap(),
+            time
+        );
+
+        assert!(NaiveDate::try_from_table_attr(TableAttribute::Number("1".to_string())).is_err());
+    }
+
     #[test]
     fn test_hashmap() {
         let map = [
@@ -64,11 +64,26 @@ impl TableAttributes {
         (protected.into(), unprotected.into())
     }
 
+    /// Returns the name of an attribute that isn't one of `known_keys`.
+    ///
+    /// Attributes prefixed with `__` are reserved for internal use and are never unknown.
+    pub(crate) fn find_unknown(&self, known_keys: &[Cow<'_, str>]) -> Option<&str> {
+        self.0
+            .keys()
+            .map(|k| k.as_external_name())
+            .filter(|name| !name.starts_with("__"))
+            .find(|name| !known_keys.iter().any(|key| name == key))
+    }
+
     // TODO: Doc, test
     pub(crate) fn get(&self, name: impl Into<AttributeName>) -> Option<&TableAttribute> {
         let name: AttributeName = name.into();
         self.0.get(&name)
     }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&AttributeName, &TableAttribute)> {
+        self.0.iter()
+    }
 }
 
 impl From<HashMap<AttributeName, TableAttribute>> for TableAttributes {
@@ -0,0 +1,119 @@
+use super::DEFAULT_TERM_INDEX_NAME;
+
+/// Describes the physical names of the key attributes and term index of a table.
+///
+/// By default tables use `pk` and `sk` for the partition and sort keys, `term` for the index term
+/// and `TermIndex` for the Global Secondary Index. Use a custom layout to adopt CipherStash for
+/// DynamoDB in an existing single-table design.
+///
+/// ```
+/// use cipherstash_dynamodb::encrypted_table::TableLayout;
+///
+/// let layout = TableLayout::new("PK", "SK", "GSI1PK", "GSI1");
+/// assert!(layout.is_reserved("GSI1PK"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TableLayout {
+    /// Name of the partition key attribute.
+    pub partition_key: String,
+    /// Name of the sort key attribute.
+    pub sort_key: String,
+    /// Name of the binary attribute holding index terms.
+    pub term: String,
+    /// Name of the Global Secondary Index keyed on the term attribute.
+    pub term_index: String,
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        Self::new("pk", "sk", "term", DEFAULT_TERM_INDEX_NAME)
+    }
+}
+
+impl TableLayout {
+    pub fn new(
+        partition_key: impl Into<String>,
+        sort_key: impl Into<String>,
+        term: impl Into<String>,
+        term_index: impl Into<String>,
+    ) -> Self {
+        Self {
+            partition_key: partition_key.into(),
+            sort_key: sort_key.into(),
+            term: term.into(),
+            term_index: term_index.into(),
+        }
+    }
+
+    /// Returns true if `name` is used by one of the key attributes of this layout and so can't be
+    /// used to store a record attribute.
+    pub fn is_reserved(&self, name: &str) -> bool {
+        name == self.partition_key || name == self.sort_key || name == self.term
+    }
+
+    /// Returns the name a record attribute is stored under in this layout.
+    ///
+    /// Attributes with the name of one of the key attributes are stored with a `__` prefix, so
+    /// with the default layout a field named `pk` is stored as `__pk`.
+    pub fn stored_attribute_name(&self, name: &str) -> String {
+        if self.is_reserved(name) {
+            format!("__{name}")
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Returns the name of the record attribute stored under `stored_name`, the inverse of
+    /// [`TableLayout::stored_attribute_name`].
+    pub fn attribute_name<'a>(&self, stored_name: &'a str) -> &'a str {
+        match stored_name.strip_prefix("__") {
+            Some(name) if self.is_reserved(name) => name,
+            _ => stored_name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_layout() {
+        let layout = TableLayout::default();
+
+        assert_eq!(layout.partition_key, "pk");
+        assert_eq!(layout.sort_key, "sk");
+        assert_eq!(layout.term, "term");
+        assert_eq!(layout.term_index, "TermIndex");
+    }
+
+    #[test]
+    fn test_is_reserved() {
+        let layout = TableLayout::new("PK", "SK", "GSI1PK", "GSI1");
+
+        assert!(layout.is_reserved("PK"));
+        assert!(layout.is_reserved("SK"));
+        assert!(layout.is_reserved("GSI1PK"));
+        assert!(!layout.is_reserved("GSI1"));
+        assert!(!layout.is_reserved("pk"));
+    }
+
+    #[test]
+    fn test_stored_attribute_name() {
+        let layout = TableLayout::default();
+
+        assert_eq!(layout.stored_attribute_name("pk"), "__pk");
+        assert_eq!(layout.stored_attribute_name("term"), "__term");
+        assert_eq!(layout.stored_attribute_name("PK"), "PK");
+        assert_eq!(layout.attribute_name("__pk"), "pk");
+        assert_eq!(layout.attribute_name("__PK"), "__PK");
+
+        let layout = TableLayout::new("PK", "SK", "GSI1PK", "GSI1");
+
+        assert_eq!(layout.stored_attribute_name("PK"), "__PK");
+        assert_eq!(layout.stored_attribute_name("pk"), "pk");
+        assert_eq!(layout.attribute_name("__GSI1PK"), "GSI1PK");
+        assert_eq!(layout.attribute_name("pk"), "pk");
+        assert_eq!(layout.attribute_name("__pk"), "__pk");
+    }
+}
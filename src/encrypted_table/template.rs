@@ -0,0 +1,688 @@
+use serde_json::{json, Map, Value};
+use std::{borrow::Cow, fmt::Write, str::FromStr};
+
+use super::{TableLayout, Throughput, TypeRegistry};
+use crate::{IndexType, Searchable};
+
+/// The output format of a [`TableTemplate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateFormat {
+    CloudFormationJson,
+    CloudFormationYaml,
+    Terraform,
+}
+
+impl FromStr for TemplateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cloudformation-json" | "cfn-json" | "json" => Ok(Self::CloudFormationJson),
+            "cloudformation-yaml" | "cfn-yaml" | "yaml" => Ok(Self::CloudFormationYaml),
+            "terraform" | "hcl" => Ok(Self::Terraform),
+            other => Err(format!(
+                "Unknown template format '{other}', expected one of cloudformation-json, cloudformation-yaml or terraform"
+            )),
+        }
+    }
+}
+
+/// A type registered with a [`TableTemplate`].
+#[derive(Debug, Clone, PartialEq)]
+struct TemplateType {
+    type_name: Cow<'static, str>,
+    sort_key_prefix: Option<Cow<'static, str>>,
+    indexes: Vec<(Cow<'static, str>, IndexType)>,
+}
+
+/// Generates infrastructure templates for a table with the schema expected by CipherStash for
+/// DynamoDB.
+///
+/// The generated table uses the key attribute and term index names of the [`TableLayout`] and
+/// records the types stored in the table (and their indexes) as metadata.
+///
+/// ```
+/// use cipherstash_dynamodb::encrypted_table::{TableTemplate, TemplateFormat};
+///
+/// let template = TableTemplate::new("users").ttl_attribute("expires_at");
+/// let hcl = template.render(TemplateFormat::Terraform);
+///
+/// assert!(hcl.contains(r#"name            = "TermIndex""#));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TableTemplate {
+    table_name: String,
+    layout: TableLayout,
+    throughput: Throughput,
+    ttl_attribute: Option<String>,
+    types: Vec<TemplateType>,
+}
+
+impl TableTemplate {
+    pub fn new(table_name: impl Into<String>) -> Self {
+        Self {
+            table_name: table_name.into(),
+            layout: TableLayout::default(),
+            throughput: Throughput::OnDemand,
+            ttl_attribute: None,
+            types: vec![],
+        }
+    }
+
+    /// Use the key attribute and term index names of a custom [`TableLayout`].
+    pub fn layout(mut self, layout: TableLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set the throughput of the table and the term index.
+    pub fn throughput(mut self, throughput: Throughput) -> Self {
+        self.throughput = throughput;
+        self
+    }
+
+    /// Enable TTL on the table using the given attribute.
+    ///
+    /// The attribute must be a plaintext number attribute holding the expiry time in seconds
+    /// since the epoch.
+    pub fn ttl_attribute(mut self, name: impl Into<String>) -> Self {
+        self.ttl_attribute = Some(name.into());
+        self
+    }
+
+    /// Record a type stored in the table in the template metadata.
+    pub fn register<T: Searchable>(mut self) -> Self {
+        self.types.push(TemplateType {
+            type_name: T::type_name(),
+            sort_key_prefix: T::sort_key_prefix(),
+            indexes: T::protected_indexes().into_owned(),
+        });
+        self
+    }
+
+    /// Record all of the types in a [`TypeRegistry`] in the template metadata.
+    pub fn registry(mut self, registry: &TypeRegistry) -> Self {
+        self.types.extend(registry.iter().map(|schema| {
+            TemplateType {
+                type_name: schema.type_name.clone(),
+                sort_key_prefix: schema.sort_key_prefix.clone(),
+                indexes: schema
+                    .indexes
+                    .iter()
+                    .map(|index| (index.name.clone(), index.index_type))
+                    .collect(),
+            }
+        }));
+        self
+    }
+
+    pub fn render(&self, format: TemplateFormat) -> String {
+        match format {
+            TemplateFormat::CloudFormationJson => self.to_cloudformation_json(),
+            TemplateFormat::CloudFormationYaml => self.to_cloudformation_yaml(),
+            TemplateFormat::Terraform => self.to_terraform(),
+        }
+    }
+
+    /// Render the template as a CloudFormation JSON document.
+    pub fn to_cloudformation_json(&self) -> String {
+        format!("{:#}", self.cloudformation())
+    }
+
+    /// Render the template as a CloudFormation YAML document.
+    pub fn to_cloudformation_yaml(&self) -> String {
+        let mut out = String::new();
+        write_yaml(&mut out, &self.cloudformation(), 0);
+        out
+    }
+
+    /// Render the template as a Terraform `aws_dynamodb_table` resource.
+    pub fn to_terraform(&self) -> String {
+        let layout = &self.layout;
+        let mut out = String::new();
+
+        for ty in &self.types {
+            let _ = writeln!(out, "# CipherStash type: {}", describe_type(ty));
+        }
+
+        let _ = writeln!(
+            out,
+            "resource \"aws_dynamodb_table\" \"{}\" {{",
+            resource_name(&self.table_name)
+        );
+        let _ = writeln!(out, "  name         = {}", hcl_string(&self.table_name));
+        let _ = writeln!(out, "  billing_mode = \"{}\"", self.billing_mode());
+        let _ = writeln!(
+            out,
+            "  hash_key     = {}",
+            hcl_string(&layout.partition_key)
+        );
+        let _ = writeln!(out, "  range_key    = {}", hcl_string(&layout.sort_key));
+
+        if let Throughput::Provisioned {
+            read_capacity_units,
+            write_capacity_units,
+        } = self.throughput
+        {
+            let _ = writeln!(out, "  read_capacity  = {read_capacity_units}");
+            let _ = writeln!(out, "  write_capacity = {write_capacity_units}");
+        }
+
+        for (name, attribute_type) in self.attribute_definitions() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "  attribute {{");
+            let _ = writeln!(out, "    name = {}", hcl_string(name));
+            let _ = writeln!(out, "    type = \"{attribute_type}\"");
+            let _ = writeln!(out, "  }}");
+        }
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "  global_secondary_index {{");
+        let _ = writeln!(
+            out,
+            "    name            = {}",
+            hcl_string(&layout.term_index)
+        );
+        let _ = writeln!(out, "    hash_key        = {}", hcl_string(&layout.term));
+        let _ = writeln!(out, "    projection_type = \"ALL\"");
+
+        if let Throughput::Provisioned {
+            read_capacity_units,
+            write_capacity_units,
+        } = self.throughput
+        {
+            let _ = writeln!(out, "    read_capacity   = {read_capacity_units}");
+            let _ = writeln!(out, "    write_capacity  = {write_capacity_units}");
+        }
+
+        let _ = writeln!(out, "  }}");
+
+        if let Some(ttl_attribute) = &self.ttl_attribute {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "  ttl {{");
+            let _ = writeln!(out, "    attribute_name = {}", hcl_string(ttl_attribute));
+            let _ = writeln!(out, "    enabled        = true");
+            let _ = writeln!(out, "  }}");
+        }
+
+        let _ = writeln!(out, "}}");
+
+        out
+    }
+
+    fn billing_mode(&self) -> &'static str {
+        match self.throughput {
+            Throughput::OnDemand => "PAY_PER_REQUEST",
+            Throughput::Provisioned { .. } => "PROVISIONED",
+        }
+    }
+
+    fn attribute_definitions(&self) -> [(&str, &'static str); 3] {
+        [
+            (self.layout.partition_key.as_str(), "S"),
+            (self.layout.sort_key.as_str(), "S"),
+            (self.layout.term.as_str(), "B"),
+        ]
+    }
+
+    fn provisioned_throughput(&self) -> Option<Value> {
+        match self.throughput {
+            Throughput::OnDemand => None,
+            Throughput::Provisioned {
+                read_capacity_units,
+                write_capacity_units,
+            } => Some(json!({
+                "ReadCapacityUnits": read_capacity_units,
+                "WriteCapacityUnits": write_capacity_units,
+            })),
+        }
+    }
+
+    fn cloudformation(&self) -> Value {
+        let layout = &self.layout;
+
+        let mut term_index = json!({
+            "IndexName": layout.term_index,
+            "KeySchema": [{ "AttributeName": layout.term, "KeyType": "HASH" }],
+            "Projection": { "ProjectionType": "ALL" },
+        });
+
+        let mut properties = Map::new();
+
+        properties.insert("TableName".into(), json!(self.table_name));
+        properties.insert("BillingMode".into(), json!(self.billing_mode()));
+        properties.insert(
+            "AttributeDefinitions".into(),
+            self.attribute_definitions()
+                .iter()
+                .map(|(name, attribute_type)| {
+                    json!({ "AttributeName": name, "AttributeType": attribute_type })
+                })
+                .collect(),
+        );
+        properties.insert(
+            "KeySchema".into(),
+            json!([
+                { "AttributeName": layout.partition_key, "KeyType": "HASH" },
+                { "AttributeName": layout.sort_key, "KeyType": "RANGE" },
+            ]),
+        );
+
+        if let Some(provisioned_throughput) = self.provisioned_throughput() {
+            term_index["ProvisionedThroughput"] = provisioned_throughput.clone();
+            properties.insert("ProvisionedThroughput".into(), provisioned_throughput);
+        }
+
+        properties.insert("GlobalSecondaryIndexes".into(), json!([term_index]));
+
+        if let Some(ttl_attribute) = &self.ttl_attribute {
+            properties.insert(
+                "TimeToLiveSpecification".into(),
+                json!({ "AttributeName": ttl_attribute, "Enabled": true }),
+            );
+        }
+
+        let mut resource = Map::new();
+
+        resource.insert("Type".into(), json!("AWS::DynamoDB::Table"));
+
+        if !self.types.is_empty() {
+            let types: Vec<Value> = self
+                .types
+                .iter()
+                .map(|ty| {
+                    json!({
+                        "TypeName": ty.type_name,
+                        "SortKeyPrefix": ty.sort_key_prefix,
+                        "Indexes": ty
+                            .indexes
+                            .iter()
+                            .map(|(name, index_type)| {
+                                json!({ "Name": name, "Type": index_type.to_string() })
+                            })
+                            .collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+
+            resource.insert(
+                "Metadata".into(),
+                json!({ "CipherStash": { "Types": types } }),
+            );
+        }
+
+        resource.insert("Properties".into(), Value::Object(properties));
+
+        json!({
+            "AWSTemplateFormatVersion": "2010-09-09",
+            "Description": format!("CipherStash for DynamoDB table {}", self.table_name),
+            "Resources": { logical_id(&self.table_name): resource },
+        })
+    }
+}
+
+fn describe_type(ty: &TemplateType) -> String {
+    let mut description = ty.type_name.to_string();
+
+    if let Some(prefix) = &ty.sort_key_prefix {
+        let _ = write!(description, " (sort key prefix '{prefix}')");
+    }
+
+    if !ty.indexes.is_empty() {
+        let indexes = ty
+            .indexes
+            .iter()
+            .map(|(name, index_type)| format!("{name}:{index_type}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let _ = write!(description, ", indexes: {indexes}");
+    }
+
+    description
+}
+
+/// Convert a table name into a CloudFormation logical ID, which must be alphanumeric.
+fn logical_id(table_name: &str) -> String {
+    let mut id = String::new();
+    let mut upper = true;
+
+    for c in table_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if upper {
+                id.push(c.to_ascii_uppercase());
+            } else {
+                id.push(c);
+            }
+            upper = false;
+        } else {
+            upper = true;
+        }
+    }
+
+    format!("{id}Table")
+}
+
+/// Convert a table name into a Terraform resource name.
+fn resource_name(table_name: &str) -> String {
+    let name: String = table_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("_{name}")
+    }
+}
+
+fn hcl_string(value: &str) -> String {
+    // HCL string literals use the same escapes as JSON, except that template sequences must be
+    // escaped.
+    Value::String(value.to_string())
+        .to_string()
+        .replace("${", "$${")
+        .replace("%{", "%%{")
+}
+
+/// Write a JSON value as YAML. Strings are always double quoted which is valid YAML and avoids
+/// any ambiguity with YAML's implicit types.
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let _ = write!(out, "{pad}{key}:");
+                write_yaml_nested(out, value, indent);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                let _ = write!(out, "{pad}-");
+
+                match item {
+                    Value::Object(map) if !map.is_empty() => {
+                        // Write the first key on the same line as the dash
+                        let mut inner = String::new();
+                        write_yaml(&mut inner, item, indent + 2);
+                        let _ = write!(out, " {}", inner.trim_start());
+                    }
+                    _ => write_yaml_nested(out, item, indent),
+                }
+            }
+        }
+        scalar => {
+            let _ = writeln!(out, "{pad}{}", yaml_scalar(scalar));
+        }
+    }
+}
+
+fn write_yaml_nested(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_yaml(out, value, indent + 2);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_yaml(out, value, indent + 2);
+        }
+        scalar => {
+            let _ = writeln!(out, " {}", yaml_scalar(scalar));
+        }
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Arguments of a command that prints a [`TableTemplate`].
+///
+/// The arguments only describe the table layout, so applications build their own command and add
+/// their types with [`TemplateArgs::registry`], as in the `schema` example of this crate.
+#[derive(Debug, Clone)]
+pub struct TemplateArgs {
+    pub template: TableTemplate,
+    pub format: TemplateFormat,
+}
+
+impl TemplateArgs {
+    pub const USAGE: &'static str = "\
+Usage: schema --table-name <NAME> [OPTIONS]
+
+Options:
+  --table-name <NAME>       Name of the DynamoDB table
+  --format <FORMAT>         cloudformation-json, cloudformation-yaml or terraform [default: cloudformation-yaml]
+  --partition-key <NAME>    Name of the partition key attribute [default: pk]
+  --sort-key <NAME>         Name of the sort key attribute [default: sk]
+  --term <NAME>             Name of the term attribute [default: term]
+  --term-index <NAME>       Name of the term index [default: TermIndex]
+  --ttl <NAME>              Enable TTL using the given attribute
+  --provisioned <R>,<W>     Use provisioned read and write capacity units [default: on-demand]";
+
+    /// Parse the arguments following the `schema` command.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+
+        let mut table_name = None;
+        let mut format = TemplateFormat::CloudFormationYaml;
+        let mut layout = TableLayout::default();
+        let mut ttl_attribute = None;
+        let mut throughput = Throughput::OnDemand;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for argument '{arg}'"))
+            };
+
+            match arg.as_str() {
+                "--table-name" => table_name = Some(value()?),
+                "--format" => format = value()?.parse()?,
+                "--partition-key" => layout.partition_key = value()?,
+                "--sort-key" => layout.sort_key = value()?,
+                "--term" => layout.term = value()?,
+                "--term-index" => layout.term_index = value()?,
+                "--ttl" => ttl_attribute = Some(value()?),
+                "--provisioned" => throughput = parse_provisioned(&value()?)?,
+                other => return Err(format!("Unknown argument '{other}'")),
+            }
+        }
+
+        let table_name = table_name.ok_or("Missing required argument '--table-name'")?;
+
+        let mut template = TableTemplate::new(table_name)
+            .layout(layout)
+            .throughput(throughput);
+
+        if let Some(ttl_attribute) = ttl_attribute {
+            template = template.ttl_attribute(ttl_attribute);
+        }
+
+        Ok(Self { template, format })
+    }
+
+    /// Record all of the types in a [`TypeRegistry`] in the template metadata.
+    pub fn registry(mut self, registry: &TypeRegistry) -> Self {
+        self.template = self.template.registry(registry);
+        self
+    }
+
+    pub fn render(&self) -> String {
+        self.template.render(self.format)
+    }
+}
+
+fn parse_provisioned(value: &str) -> Result<Throughput, String> {
+    let invalid = || format!("Invalid provisioned throughput '{value}', expected <READ>,<WRITE>");
+
+    let (read, write) = value.split_once(',').ok_or_else(invalid)?;
+
+    Ok(Throughput::Provisioned {
+        read_capacity_units: read.trim().parse().map_err(|_| invalid())?,
+        write_capacity_units: write.trim().parse().map_err(|_| invalid())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::{KeySchema, TypeSchema};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_cloudformation_json() {
+        let template = TableTemplate::new("users")
+            .throughput(Throughput::Provisioned {
+                read_capacity_units: 5,
+                write_capacity_units: 10,
+            })
+            .ttl_attribute("expires_at");
+
+        let value: Value = serde_json::from_str(&template.to_cloudformation_json()).unwrap();
+        let properties = &value["Resources"]["UsersTable"]["Properties"];
+
+        assert_eq!(properties["TableName"], "users");
+        assert_eq!(properties["BillingMode"], "PROVISIONED");
+        assert_eq!(
+            properties["AttributeDefinitions"][2],
+            json!({ "AttributeName": "term", "AttributeType": "B" })
+        );
+        assert_eq!(
+            properties["GlobalSecondaryIndexes"][0]["IndexName"],
+            "TermIndex"
+        );
+        assert_eq!(
+            properties["GlobalSecondaryIndexes"][0]["ProvisionedThroughput"]["WriteCapacityUnits"],
+            10
+        );
+        assert_eq!(
+            properties["TimeToLiveSpecification"],
+            json!({ "AttributeName": "expires_at", "Enabled": true })
+        );
+    }
+
+    #[test]
+    fn test_cloudformation_yaml() {
+        let template = TableTemplate::new("users");
+
+        let yaml = template.to_cloudformation_yaml();
+
+        assert!(yaml.contains("AWSTemplateFormatVersion: \"2010-09-09\"\n"));
+        assert!(yaml.contains("      BillingMode: \"PAY_PER_REQUEST\"\n"));
+        assert!(yaml.contains(
+            "      KeySchema:\n        - AttributeName: \"pk\"\n          KeyType: \"HASH\"\n"
+        ));
+        assert!(yaml.contains("        - IndexName: \"TermIndex\"\n"));
+        assert!(!yaml.contains("TimeToLiveSpecification"));
+    }
+
+    #[test]
+    fn test_terraform_custom_layout() {
+        let template =
+            TableTemplate::new("my-table").layout(TableLayout::new("PK", "SK", "GSI1PK", "GSI1"));
+
+        let hcl = template.to_terraform();
+
+        assert!(hcl.starts_with("resource \"aws_dynamodb_table\" \"my_table\" {\n"));
+        assert!(hcl.contains("  hash_key     = \"PK\"\n"));
+        assert!(hcl.contains("  range_key    = \"SK\"\n"));
+        assert!(hcl.contains("    name = \"GSI1PK\"\n    type = \"B\"\n"));
+        assert!(hcl.contains("    name            = \"GSI1\"\n"));
+        assert!(hcl.contains("    hash_key        = \"GSI1PK\"\n"));
+        assert!(!hcl.contains("read_capacity"));
+        assert!(!hcl.contains("ttl {"));
+    }
+
+    #[test]
+    fn test_hcl_string_escapes_templates() {
+        assert_eq!(hcl_string("a\"${b}"), r#""a\"$${b}""#);
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(logical_id("my-users.table"), "MyUsersTableTable");
+        assert_eq!(resource_name("1-users"), "_1_users");
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = TemplateArgs::parse(args(&[
+            "--table-name",
+            "users",
+            "--format",
+            "terraform",
+            "--term-index",
+            "GSI1",
+            "--provisioned",
+            "5,5",
+            "--ttl",
+            "expires_at",
+        ]))
+        .unwrap();
+
+        assert_eq!(parsed.format, TemplateFormat::Terraform);
+        assert_eq!(parsed.template.layout.term_index, "GSI1");
+        assert_eq!(
+            parsed.template.throughput,
+            Throughput::Provisioned {
+                read_capacity_units: 5,
+                write_capacity_units: 5
+            }
+        );
+        assert_eq!(parsed.template.ttl_attribute.as_deref(), Some("expires_at"));
+    }
+
+    #[test]
+    fn test_parse_args_with_registry() {
+        let mut registry = TypeRegistry::new();
+        registry
+            .register_schema(TypeSchema {
+                type_name: Cow::Borrowed("user"),
+                sort_key_prefix: None,
+                partition_key: KeySchema {
+                    field: None,
+                    encrypted: true,
+                },
+                sort_key: None,
+                attributes: vec![],
+                indexes: vec![],
+                skipped: vec![],
+            })
+            .unwrap();
+
+        let parsed = TemplateArgs::parse(args(&["--table-name", "users", "--format", "json"]))
+            .unwrap()
+            .registry(&registry);
+
+        let value: Value = serde_json::from_str(&parsed.render()).unwrap();
+
+        assert_eq!(
+            value["Resources"]["UsersTable"]["Metadata"]["CipherStash"]["Types"][0]["TypeName"],
+            "user"
+        );
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert_eq!(
+            TemplateArgs::parse(args(&["--format", "terraform"])).unwrap_err(),
+            "Missing required argument '--table-name'"
+        );
+        assert_eq!(
+            TemplateArgs::parse(args(&["--table-name"])).unwrap_err(),
+            "Missing value for argument '--table-name'"
+        );
+        assert!(TemplateArgs::parse(args(&["--table-name", "t", "--provisioned", "5"])).is_err());
+    }
+}
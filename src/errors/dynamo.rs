@@ -0,0 +1,177 @@
+use aws_sdk_dynamodb::{
+    error::{ProvideErrorMetadata, SdkError},
+    operation::{
+        create_table::CreateTableError, describe_table::DescribeTableError, get_item::GetItemError,
+        query::QueryError, scan::ScanError, transact_write_items::TransactWriteItemsError,
+    },
+};
+
+/// Cancellation reasons of transaction items that are expected to succeed when the transaction is
+/// retried.
+const RETRYABLE_CANCELLATION_REASONS: &[&str] = &[
+    "ThrottlingError",
+    "ProvisionedThroughputExceeded",
+    "RequestLimitExceeded",
+    "TransactionConflict",
+];
+
+/// How a request to DynamoDB failed, so that callers can decide whether to back off and retry it
+/// or to handle a conflicting write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynamoErrorKind {
+    /// The request exceeded the provisioned throughput of the table or a limit of the account.
+    Throttled,
+    /// A condition of the request wasn't met.
+    ConditionalCheckFailed,
+    /// A transaction was cancelled.
+    ///
+    /// `reasons` has the reason code of each item in the order the items were sent, such as
+    /// `ConditionalCheckFailed` or `TransactionConflict`, or `None` for items that didn't cause
+    /// the cancellation.
+    TransactionCancelled { reasons: Vec<Option<String>> },
+    /// The table or index doesn't exist or isn't active.
+    ResourceNotFound,
+    /// The request timed out, couldn't be sent or failed with an internal error in DynamoDB.
+    Transient,
+    /// Any other error, such as a validation or permissions error.
+    Other,
+}
+
+impl DynamoErrorKind {
+    /// Returns true if the request may succeed when it is retried after backing off.
+    ///
+    /// A cancelled transaction is retryable when every item that caused the cancellation was
+    /// throttled or conflicted with another transaction.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Throttled | Self::Transient => true,
+            Self::TransactionCancelled { reasons } => {
+                let mut reasons = reasons.iter().flatten().peekable();
+
+                reasons.peek().is_some()
+                    && reasons
+                        .all(|reason| RETRYABLE_CANCELLATION_REASONS.contains(&reason.as_str()))
+            }
+            Self::ConditionalCheckFailed | Self::ResourceNotFound | Self::Other => false,
+        }
+    }
+
+    /// Returns true if a condition of the request wasn't met, including a condition of any item
+    /// in a cancelled transaction.
+    pub fn is_conditional_check_failed(&self) -> bool {
+        match self {
+            Self::ConditionalCheckFailed => true,
+            Self::TransactionCancelled { reasons } => reasons
+                .iter()
+                .flatten()
+                .any(|reason| reason == "ConditionalCheckFailed"),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn of<E, R>(error: &SdkError<E, R>) -> Self
+    where
+        E: ProvideErrorMetadata + CancellationReasons,
+    {
+        match error {
+            SdkError::ServiceError(service_error) => Self::of_service_error(service_error.err()),
+            SdkError::TimeoutError(_)
+            | SdkError::DispatchFailure(_)
+            | SdkError::ResponseError(_) => Self::Transient,
+            _ => Self::Other,
+        }
+    }
+
+    fn of_service_error<E>(error: &E) -> Self
+    where
+        E: ProvideErrorMetadata + CancellationReasons,
+    {
+        if let Some(reasons) = error.cancellation_reasons() {
+            return Self::TransactionCancelled { reasons };
+        }
+
+        match error.code() {
+            Some(
+                "ProvisionedThroughputExceededException"
+                | "ThrottlingException"
+                | "RequestLimitExceeded",
+            ) => Self::Throttled,
+            Some("ConditionalCheckFailedException") => Self::ConditionalCheckFailed,
+            Some("ResourceNotFoundException") => Self::ResourceNotFound,
+            Some("InternalServerError" | "ServiceUnavailable") => Self::Transient,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Implemented by the errors of each DynamoDB operation used by the crate, to read the per-item
+/// reasons of cancelled transactions.
+pub(crate) trait CancellationReasons {
+    fn cancellation_reasons(&self) -> Option<Vec<Option<String>>> {
+        None
+    }
+}
+
+impl CancellationReasons for TransactWriteItemsError {
+    fn cancellation_reasons(&self) -> Option<Vec<Option<String>>> {
+        match self {
+            Self::TransactionCanceledException(error) => Some(
+                error
+                    .cancellation_reasons()
+                    .iter()
+                    // DynamoDB uses the code `None` for items that didn't fail
+                    .map(|reason| {
+                        reason
+                            .code()
+                            .filter(|code| *code != "None")
+                            .map(String::from)
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl CancellationReasons for GetItemError {}
+impl CancellationReasons for QueryError {}
+impl CancellationReasons for ScanError {}
+impl CancellationReasons for CreateTableError {}
+impl CancellationReasons for DescribeTableError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cancelled(reasons: &[Option<&str>]) -> DynamoErrorKind {
+        DynamoErrorKind::TransactionCancelled {
+            reasons: reasons.iter().map(|r| r.map(String::from)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(DynamoErrorKind::Throttled.is_retryable());
+        assert!(DynamoErrorKind::Transient.is_retryable());
+        assert!(!DynamoErrorKind::ConditionalCheckFailed.is_retryable());
+        assert!(!DynamoErrorKind::ResourceNotFound.is_retryable());
+        assert!(!DynamoErrorKind::Other.is_retryable());
+
+        assert!(cancelled(&[None, Some("TransactionConflict")]).is_retryable());
+        assert!(cancelled(&[Some("ThrottlingError"), None]).is_retryable());
+        assert!(
+            !cancelled(&[Some("TransactionConflict"), Some("ConditionalCheckFailed")])
+                .is_retryable()
+        );
+        assert!(!cancelled(&[Some("ValidationError")]).is_retryable());
+        assert!(!cancelled(&[None, None]).is_retryable());
+    }
+
+    #[test]
+    fn test_is_conditional_check_failed() {
+        assert!(DynamoErrorKind::ConditionalCheckFailed.is_conditional_check_failed());
+        assert!(cancelled(&[None, Some("ConditionalCheckFailed")]).is_conditional_check_failed());
+        assert!(!cancelled(&[Some("TransactionConflict")]).is_conditional_check_failed());
+        assert!(!DynamoErrorKind::Throttled.is_conditional_check_failed());
+    }
+}
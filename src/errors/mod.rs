@@ -3,11 +3,14 @@ use cipherstash_client::zerokms;
 use miette::Diagnostic;
 use thiserror::Error;
 
+mod dynamo;
+
 use crate::traits::PrimaryKeyError;
 pub use crate::{
     crypto::{CryptoError, SealError},
     traits::{ReadConversionError, WriteConversionError},
 };
+pub use dynamo::DynamoErrorKind;
 
 pub use cipherstash_client::{config::errors::ConfigError, encryption::EncryptionError};
 
@@ -46,8 +49,9 @@ pub enum GetError {
     DecryptError(#[from] DecryptError),
     #[error(transparent)]
     Encryption(#[from] EncryptionError),
-    #[error("AwsError: {0}")]
-    Aws(String),
+
+    #[error(transparent)]
+    DynamoError(#[from] Box<SdkError<operation::get_item::GetItemError>>),
 
     #[error("ZeroKMS Error: {0}")]
     ZeroKMS(#[from] zerokms::Error),
@@ -62,8 +66,9 @@ pub enum DeleteError {
     Encryption(#[from] EncryptionError),
     #[error("AwsBuildError: {0}")]
     AwsBuildError(#[from] BuildError),
-    #[error("AwsError: {0}")]
-    Aws(String),
+
+    #[error(transparent)]
+    DynamoError(#[from] Box<SdkError<operation::transact_write_items::TransactWriteItemsError>>),
 
     #[error("ZeroKMS Error: {0}")]
     ZeroKMS(#[from] zerokms::Error),
@@ -76,6 +81,10 @@ pub enum DecryptError {
     ReadConversionError(#[from] ReadConversionError),
     #[error(transparent)]
     SealError(#[from] SealError),
+    #[error("Item with sort key '{0}' does not match any of the types being decrypted")]
+    UnknownType(String),
+    #[error(transparent)]
+    RegistryError(#[from] RegistryError),
 }
 
 /// Error returned by `EncryptedTable::query` when indexing, retrieving and decrypting records from DynamoDB
@@ -107,6 +116,32 @@ pub enum QueryError {
     ZeroKMS(#[from] zerokms::Error),
 }
 
+/// Error returned by `ReencryptJob::run` and `EncryptedTable::move_to_dataset` when
+/// re-encrypting records
+#[derive(Error, Debug, Diagnostic)]
+pub enum ReencryptError {
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+
+    #[error(transparent)]
+    Get(#[from] GetError),
+
+    #[error(transparent)]
+    Decrypt(#[from] DecryptError),
+
+    #[error(transparent)]
+    Put(#[from] PutError),
+
+    #[error(transparent)]
+    Delete(#[from] DeleteError),
+
+    #[error(transparent)]
+    DynamoError(#[from] Box<SdkError<operation::scan::ScanError>>),
+
+    #[error("ZeroKMS Error: {0}")]
+    ZeroKMS(#[from] zerokms::Error),
+}
+
 pub trait DynamoError: std::error::Error + Sized {}
 
 /// Error returned by `EncryptedTable::init` when connecting to CipherStash services
@@ -117,13 +152,103 @@ pub enum InitError {
 
     #[error(transparent)]
     ZeroKMS(#[from] zerokms::Error),
+
+    #[error(transparent)]
+    DescribeTable(#[from] Box<SdkError<operation::describe_table::DescribeTableError>>),
+
+    #[error("Table '{table_name}' is not configured correctly: {reason}")]
+    InvalidTable { table_name: String, reason: String },
+}
+
+/// Error returned by `EncryptedTable::create_table` when creating a table in DynamoDB
+#[derive(Error, Debug, Diagnostic)]
+pub enum CreateTableError {
+    #[error("AwsBuildError: {0}")]
+    AwsBuildError(#[from] BuildError),
+
+    #[error(transparent)]
+    DynamoError(#[from] Box<SdkError<operation::create_table::CreateTableError>>),
+}
+
+/// Error returned by `EncryptedTable::register` when a type conflicts with a registered type
+#[derive(Error, Debug, Diagnostic)]
+pub enum RegistryError {
+    #[error("A type named '{0}' has already been registered")]
+    #[diagnostic(help("Set a unique type name for each type stored in the table"))]
+    DuplicateTypeName(String),
+
+    #[error("Type '{type_name}' uses descriptor prefix '{prefix}' which is already used by type '{existing_type_name}'")]
+    #[diagnostic(help(
+        "Encrypted attributes of these types can't be told apart. Set a unique `sort_key_prefix` for each type"
+    ))]
+    ConflictingDescriptorPrefix {
+        type_name: String,
+        existing_type_name: String,
+        prefix: String,
+    },
+
+    #[error("Type '{type_name}' with {sort_key} conflicts with type '{existing_type_name}' with {existing_sort_key}")]
+    #[diagnostic(help(
+        "Records and index terms of these types would overwrite each other. Set a unique `sort_key_prefix` for each type"
+    ))]
+    ConflictingSortKey {
+        type_name: String,
+        sort_key: String,
+        existing_type_name: String,
+        existing_sort_key: String,
+    },
+
+    #[error("Index '{index_name}' of type '{type_name}' conflicts with index '{existing_index_name}' of type '{existing_type_name}'")]
+    #[diagnostic(help(
+        "Index terms of these indexes can't be told apart. Rename one of the types or indexes"
+    ))]
+    ConflictingIndexName {
+        type_name: String,
+        index_name: String,
+        existing_type_name: String,
+        existing_index_name: String,
+    },
+
+    #[error("Type '{0}' has not been registered with the table")]
+    #[diagnostic(help("Register the type with `EncryptedTable::register`"))]
+    UnregisteredType(String),
+}
+
+/// Implements `dynamo_error_kind` for an error whose `$dynamo` variant holds the error returned
+/// by DynamoDB, and whose `$nested` variants hold errors that implement `dynamo_error_kind`.
+macro_rules! impl_dynamo_error_kind {
+    ($error:ident, $dynamo:ident $(; $($nested:ident),+)?) => {
+        impl $error {
+            /// Returns how the request to DynamoDB failed, or `None` if the error didn't come from
+            /// DynamoDB.
+            pub fn dynamo_error_kind(&self) -> Option<DynamoErrorKind> {
+                match self {
+                    Self::$dynamo(error) => Some(DynamoErrorKind::of(&**error)),
+                    $($(Self::$nested(error) => error.dynamo_error_kind(),)+)?
+                    _ => None,
+                }
+            }
+        }
+    };
 }
 
+impl_dynamo_error_kind!(PutError, DynamoError);
+impl_dynamo_error_kind!(GetError, DynamoError);
+impl_dynamo_error_kind!(DeleteError, DynamoError);
+impl_dynamo_error_kind!(QueryError, DynamoError);
+impl_dynamo_error_kind!(ReencryptError, DynamoError; Get, Put, Delete);
+impl_dynamo_error_kind!(InitError, DescribeTable);
+impl_dynamo_error_kind!(CreateTableError, DynamoError);
+
 /// The [`enum@Error`] type abstracts all errors returned by `cipherstash-dynamodb` for easy use with the `?` operator.
 #[derive(Error, Debug, Diagnostic)]
 pub enum Error {
     #[error("InitError: {0}")]
     InitError(#[from] InitError),
+    #[error("CreateTableError: {0}")]
+    CreateTableError(#[from] CreateTableError),
+    #[error("RegistryError: {0}")]
+    RegistryError(#[from] RegistryError),
     #[error("PutError: {0}")]
     PutError(#[from] PutError),
     #[error("GetError: {0}")]
@@ -132,4 +257,28 @@ pub enum Error {
     DeleteError(#[from] DeleteError),
     #[error(transparent)]
     QueryError(#[from] QueryError),
+    #[error("ReencryptError: {0}")]
+    ReencryptError(#[from] ReencryptError),
+    #[error("ZeroKMS Error: {0}")]
+    ZeroKMS(#[from] zerokms::Error),
+}
+
+impl Error {
+    /// Returns how the request to DynamoDB failed, or `None` if the error didn't come from
+    /// DynamoDB.
+    ///
+    /// Use [`DynamoErrorKind::is_retryable`] to decide whether to back off and retry an operation.
+    pub fn dynamo_error_kind(&self) -> Option<DynamoErrorKind> {
+        match self {
+            Self::InitError(error) => error.dynamo_error_kind(),
+            Self::CreateTableError(error) => error.dynamo_error_kind(),
+            Self::RegistryError(_) => None,
+            Self::PutError(error) => error.dynamo_error_kind(),
+            Self::GetError(error) => error.dynamo_error_kind(),
+            Self::DeleteError(error) => error.dynamo_error_kind(),
+            Self::QueryError(error) => error.dynamo_error_kind(),
+            Self::ReencryptError(error) => error.dynamo_error_kind(),
+            Self::ZeroKMS(_) => None,
+        }
+    }
 }
@@ -8,15 +8,15 @@ pub mod encrypted_table;
 pub mod traits;
 pub use encrypted_table::{EncryptedTable, QueryBuilder};
 pub use traits::{
-    Decryptable, Encryptable, Identifiable, IndexType, Pk, PkSk, PrimaryKey, Searchable,
-    SingleIndex,
+    Decryptable, Encryptable, Identifiable, IndexType, Nested, Pk, PkSk, PrimaryKey, Searchable,
+    SingleIndex, TypeSchema,
 };
 
 pub mod errors;
 pub use errors::Error;
 
 #[doc(hidden)]
-pub use cipherstash_dynamodb_derive::{Decryptable, Encryptable, Identifiable, Searchable};
+pub use cipherstash_dynamodb_derive::{Decryptable, Encryptable, Identifiable, Nested, Searchable};
 
 // Re-exports
 pub use cipherstash_client::encryption;
@@ -11,9 +11,16 @@ pub use cipherstash_client::{
     },
 };
 
+mod nested;
 mod primary_key;
+mod schema;
+mod serde_record;
 use miette::Diagnostic;
+pub use nested::*;
 pub use primary_key::*;
+pub use schema::*;
+use serde::{Serialize, Serializer};
+pub use serde_record::{SerdeRecord, SerdeSchema};
 
 use std::{
     borrow::Cow,
@@ -21,7 +28,8 @@ use std::{
 };
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SingleIndex {
     Exact,
     Prefix,
@@ -42,6 +50,30 @@ pub enum IndexType {
     Compound2((SingleIndex, SingleIndex)),
 }
 
+impl IndexType {
+    /// Returns the indexer used to create terms for this type of index.
+    pub(crate) fn indexer(&self) -> Box<dyn ComposableIndex + Send> {
+        use SingleIndex::*;
+
+        match self {
+            Self::Single(Exact) => Box::new(ExactIndex::default()),
+            Self::Single(Prefix) => Box::new(PrefixIndex::default()),
+            Self::Compound2((Exact, Exact)) => {
+                Box::new(CompoundIndex::new(ExactIndex::default()).and(ExactIndex::default()))
+            }
+            Self::Compound2((Exact, Prefix)) => {
+                Box::new(CompoundIndex::new(ExactIndex::default()).and(PrefixIndex::default()))
+            }
+            Self::Compound2((Prefix, Exact)) => {
+                Box::new(CompoundIndex::new(PrefixIndex::default()).and(ExactIndex::default()))
+            }
+            Self::Compound2((Prefix, Prefix)) => {
+                Box::new(CompoundIndex::new(PrefixIndex::default()).and(PrefixIndex::default()))
+            }
+        }
+    }
+}
+
 impl Display for IndexType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -56,6 +88,12 @@ impl Display for IndexType {
     }
 }
 
+impl Serialize for IndexType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 #[derive(Debug, Error, Diagnostic)]
 pub enum ReadConversionError {
     #[error("Missing attribute: {0}")]
@@ -64,12 +102,16 @@ pub enum ReadConversionError {
     InvalidFormat(String),
     #[error("Failed to convert attribute: {0} from Plaintext")]
     ConversionFailed(String),
+    #[error("Unknown variant: {0}")]
+    UnknownVariant(String),
 }
 
 #[derive(Debug, Error)]
 pub enum WriteConversionError {
     #[error("Failed to convert attribute: '{0}' to Plaintext")]
     ConversionFailed(String),
+    #[error("Attribute '{0}' conflicts with a key attribute of the table layout")]
+    ReservedAttribute(String),
 }
 
 #[derive(Error, Debug)]
@@ -95,6 +137,33 @@ pub trait Identifiable {
 
     fn type_name() -> Cow<'static, str>;
     fn sort_key_prefix() -> Option<Cow<'static, str>>;
+
+    /// Whether the encrypted attributes of a record are bound to its primary key.
+    ///
+    /// Set to [`KeyBinding::Enabled`] with `#[cipherstash(key_binding)]` or to
+    /// [`KeyBinding::Required`] with `#[cipherstash(key_binding = "required")]`.
+    fn key_binding() -> KeyBinding {
+        KeyBinding::Disabled
+    }
+}
+
+/// Whether the encrypted attributes of a record are bound to the primary key of the record.
+///
+/// Bound attributes include the stored partition and sort keys of their record in their
+/// descriptor, which is authenticated when the attribute is decrypted. Copying a bound attribute
+/// to another record makes decrypting that record fail.
+///
+/// Bound attributes are always verified when they are read, whatever the setting of the type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyBinding {
+    /// Attributes are written without a binding.
+    #[default]
+    Disabled,
+    /// Attributes are written with a binding. Attributes written without one, such as those of
+    /// records stored before the binding was enabled, can still be read.
+    Enabled,
+    /// Attributes are written with a binding and reading an attribute without one fails.
+    Required,
 }
 
 pub trait Encryptable: Debug + Sized + Identifiable {
@@ -108,7 +177,24 @@ pub trait Encryptable: Debug + Sized + Identifiable {
     /// Must be equal to or a superset of plaintext_attributes on the [`Decryptable`] type.
     fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]>;
 
+    /// The version of the schema of this type which is stored with every record.
+    ///
+    /// Set with `#[cipherstash(version = N)]` and must be equal to
+    /// [`Decryptable::schema_version`].
+    fn schema_version() -> u32 {
+        0
+    }
+
     fn into_unsealed(self) -> Unsealed;
+
+    /// Convert the record into an [`Unsealed`] ready to be encrypted, returning a
+    /// [`WriteConversionError`] if a field can't be converted to a plaintext.
+    ///
+    /// This is used when records are written and defaults to [`Encryptable::into_unsealed`], so
+    /// only types whose conversion can fail, like [`SerdeRecord`] types, need to implement it.
+    fn try_into_unsealed(self) -> Result<Unsealed, SealError> {
+        Ok(self.into_unsealed())
+    }
 }
 
 pub trait Searchable: Encryptable {
@@ -132,6 +218,39 @@ pub trait Searchable: Encryptable {
     ) -> Option<Box<dyn ComposableIndex + Send>> {
         None
     }
+
+    /// Returns a [`TypeSchema`] describing the keys, attributes and indexes of this type.
+    fn type_schema() -> TypeSchema {
+        TypeSchema::from_traits::<Self>()
+    }
+}
+
+/// How attributes stored with a record that aren't attributes of its [`Decryptable`] type are
+/// handled when the record is decrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownAttributes {
+    /// Unknown attributes are ignored so fields can be removed from a type without breaking
+    /// existing records.
+    #[default]
+    Ignore,
+    /// Decrypting a record with an unknown attribute fails.
+    Deny,
+}
+
+/// Whether items must have a MAC when they are decrypted.
+///
+/// Every item is written with a MAC of its key and attributes. Items without one were either
+/// stored before MACs were added or had it removed to get around the check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordMac {
+    /// Items without a MAC are read without checking their plaintext attributes, so that records
+    /// stored before MACs were added can be read until they are written again.
+    #[default]
+    Optional,
+    /// Decrypting an item without a MAC fails with [`SealError::PlaintextTampered`].
+    ///
+    /// Only use this once every record has been written again with a MAC.
+    Required,
 }
 
 pub trait Decryptable: Sized {
@@ -147,6 +266,86 @@ pub trait Decryptable: Sized {
     ///
     /// Must be equal to or a subset of protected_attributes on the [`Encryptable`] type.
     fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]>;
+
+    /// Defines how attributes that are neither protected nor plaintext attributes of this type
+    /// are handled when decrypting.
+    ///
+    /// Set to [`UnknownAttributes::Deny`] with `#[cipherstash(deny_unknown_attributes)]`.
+    fn unknown_attributes() -> UnknownAttributes {
+        UnknownAttributes::Ignore
+    }
+
+    /// Whether items must have a MAC when they are decrypted.
+    ///
+    /// Set to [`RecordMac::Required`] with `#[cipherstash(record_mac = "required")]`.
+    fn record_mac() -> RecordMac {
+        RecordMac::Optional
+    }
+
+    /// The current version of the schema of this type.
+    ///
+    /// Records stored with an older version are passed to [`Decryptable::upcast_from`] before
+    /// [`Decryptable::from_unsealed`]. Records stored before versions were recorded have version 0.
+    fn schema_version() -> u32 {
+        0
+    }
+
+    /// Protected attributes that were stored by older versions of this type but are no longer
+    /// fields of it, such as fields that have been renamed.
+    ///
+    /// They are decrypted along with [`Decryptable::protected_attributes`] so that
+    /// [`Decryptable::upcast_from`] can migrate them. Set with
+    /// `#[cipherstash(upcast, legacy_protected = "name")]`.
+    fn legacy_protected_attributes() -> Cow<'static, [Cow<'static, str>]> {
+        Cow::Borrowed(&[])
+    }
+
+    /// Migrate a record stored with the older schema `version` to the current schema version.
+    ///
+    /// Returns the record unchanged by default. Derived implementations call [`Upcast::upcast`]
+    /// when the type is annotated with `#[cipherstash(upcast)]`.
+    fn upcast_from(_version: u32, unsealed: Unsealed) -> Result<Unsealed, SealError> {
+        Ok(unsealed)
+    }
+}
+
+/// A hook to migrate records stored with an older version of a type when they are read.
+///
+/// Only the protected attributes of the current version are decrypted, so protected attributes
+/// that older versions stored under another name must be listed with `legacy_protected` to be
+/// passed to the hook.
+///
+/// ```
+/// use cipherstash_dynamodb::{
+///     crypto::{SealError, Unsealed},
+///     traits::Upcast,
+///     Decryptable, Encryptable, Identifiable,
+/// };
+///
+/// #[derive(Debug, Identifiable, Encryptable, Decryptable)]
+/// #[cipherstash(version = 2, upcast, legacy_protected = "name")]
+/// struct User {
+///     #[partition_key]
+///     email: String,
+///     full_name: String,
+/// }
+///
+/// impl Upcast for User {
+///     fn upcast(version: u32, mut unsealed: Unsealed) -> Result<Unsealed, SealError> {
+///         if version < 2 {
+///             // Version 1 stored the name as `name`
+///             if let Some(name) = unsealed.take_protected("name") {
+///                 unsealed.add_protected("full_name", name);
+///             }
+///         }
+///
+///         Ok(unsealed)
+///     }
+/// }
+/// ```
+pub trait Upcast {
+    /// Migrate `unsealed` from the schema `version` it was stored with to the current version.
+    fn upcast(version: u32, unsealed: Unsealed) -> Result<Unsealed, SealError>;
 }
 
 #[cfg(test)]
@@ -0,0 +1,190 @@
+use super::{ComposableIndex, ComposablePlaintext, IndexType};
+use crate::crypto::{SealError, Unsealed};
+use itertools::Itertools;
+use std::borrow::Cow;
+
+/// A struct that is stored as part of a record rather than as a record of its own.
+///
+/// Fields of a record that are annotated with `#[cipherstash(nested)]` are stored under
+/// descriptor paths like `address.city`. Protected fields of the nested struct are encrypted
+/// as fields of a protected map named after the record field and plaintext fields are stored
+/// as attributes named by their path.
+///
+/// Fields that are annotated with `#[cipherstash(flatten)]` are stored as if they were fields of
+/// the record itself.
+///
+/// Indexes defined on a nested struct can be queried using the path of the field, for example
+/// `address.city`.
+///
+/// ```
+/// use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Nested, Searchable};
+///
+/// #[derive(Debug, Nested)]
+/// struct Address {
+///     #[cipherstash(query = "exact")]
+///     city: String,
+///     #[cipherstash(plaintext)]
+///     postcode: String,
+/// }
+///
+/// #[derive(Debug, Identifiable, Encryptable, Decryptable, Searchable)]
+/// struct User {
+///     #[partition_key]
+///     email: String,
+///     #[cipherstash(nested)]
+///     address: Address,
+/// }
+/// ```
+pub trait Nested: Sized {
+    /// Returns the paths of the protected fields relative to this struct.
+    fn protected_attributes() -> Cow<'static, [Cow<'static, str>]>;
+
+    /// Returns the paths of the plaintext fields relative to this struct.
+    fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]>;
+
+    /// Returns the indexes of this struct with names relative to this struct.
+    fn protected_indexes() -> Cow<'static, [(Cow<'static, str>, IndexType)]> {
+        Cow::Borrowed(&[])
+    }
+
+    fn index_by_name(
+        _index_name: &str,
+        _index_type: IndexType,
+    ) -> Option<Box<dyn ComposableIndex + Send>> {
+        None
+    }
+
+    fn attribute_for_index(
+        &self,
+        _index_name: &str,
+        _index_type: IndexType,
+    ) -> Option<ComposablePlaintext> {
+        None
+    }
+
+    /// Add the fields of `self` to `unsealed` under the path `prefix`.
+    fn write_unsealed(self, prefix: Option<&str>, unsealed: &mut Unsealed);
+
+    /// Take the fields under the path `prefix` from `unsealed`.
+    fn read_unsealed(prefix: Option<&str>, unsealed: &mut Unsealed) -> Result<Self, SealError>;
+
+    /// Returns the paths of the protected fields when stored under `prefix`.
+    fn protected_paths(prefix: Option<&str>) -> Vec<Cow<'static, str>> {
+        Self::protected_attributes()
+            .iter()
+            .map(|name| nested_path(prefix, name))
+            .collect()
+    }
+
+    /// Returns the paths of the plaintext fields when stored under `prefix`.
+    fn plaintext_paths(prefix: Option<&str>) -> Vec<Cow<'static, str>> {
+        Self::plaintext_attributes()
+            .iter()
+            .map(|name| nested_path(prefix, name))
+            .collect()
+    }
+
+    /// Returns the names of the record attributes that hold the protected fields when stored
+    /// under `prefix`.
+    fn protected_roots(prefix: Option<&str>) -> Vec<Cow<'static, str>> {
+        Self::protected_paths(prefix)
+            .into_iter()
+            .map(|path| match path.split_once('.') {
+                Some((root, _)) => Cow::Owned(root.to_string()),
+                None => path,
+            })
+            .unique()
+            .collect()
+    }
+
+    /// Returns the indexes of this struct when stored under `prefix`.
+    fn indexes_with_prefix(prefix: Option<&str>) -> Vec<(Cow<'static, str>, IndexType)> {
+        Self::protected_indexes()
+            .iter()
+            .map(|(name, index_type)| {
+                let name = name
+                    .split('#')
+                    .map(|part| nested_path(prefix, part))
+                    .join("#");
+                (Cow::Owned(name), *index_type)
+            })
+            .collect()
+    }
+
+    /// Looks up an index by the name it has when stored under `prefix`.
+    fn index_by_path(
+        prefix: Option<&str>,
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Option<Box<dyn ComposableIndex + Send>> {
+        Self::index_by_name(&strip_index_prefix(prefix, index_name)?, index_type)
+    }
+
+    /// Returns the plaintext for an index by the name it has when stored under `prefix`.
+    fn attribute_for_path(
+        &self,
+        prefix: Option<&str>,
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Option<ComposablePlaintext> {
+        self.attribute_for_index(&strip_index_prefix(prefix, index_name)?, index_type)
+    }
+}
+
+/// Returns the path of the field `name` when stored under `prefix`.
+pub fn nested_path(prefix: Option<&str>, name: &str) -> Cow<'static, str> {
+    match prefix {
+        Some(prefix) => Cow::Owned(format!("{prefix}.{name}")),
+        None => Cow::Owned(name.to_string()),
+    }
+}
+
+/// Strips `prefix` from each field of an index name or returns `None` if any field of the index
+/// isn't stored under `prefix`.
+fn strip_index_prefix<'a>(prefix: Option<&str>, index_name: &'a str) -> Option<Cow<'a, str>> {
+    let Some(prefix) = prefix else {
+        return Some(Cow::Borrowed(index_name));
+    };
+
+    index_name
+        .split('#')
+        .map(|part| {
+            part.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_prefix('.'))
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|parts| Cow::Owned(parts.join("#")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_path() {
+        assert_eq!(nested_path(None, "city"), "city");
+        assert_eq!(nested_path(Some("address"), "city"), "address.city");
+        assert_eq!(
+            nested_path(Some("user.address"), "city"),
+            "user.address.city"
+        );
+    }
+
+    #[test]
+    fn test_strip_index_prefix() {
+        assert_eq!(strip_index_prefix(None, "city").as_deref(), Some("city"));
+        assert_eq!(
+            strip_index_prefix(Some("address"), "address.city").as_deref(),
+            Some("city")
+        );
+        assert_eq!(
+            strip_index_prefix(Some("address"), "address.city#address.postcode").as_deref(),
+            Some("city#postcode")
+        );
+        assert_eq!(
+            strip_index_prefix(Some("address"), "address.city#email"),
+            None
+        );
+        assert_eq!(strip_index_prefix(Some("address"), "addresses.city"), None);
+    }
+}
@@ -1,45 +1,266 @@
-#[derive(Clone)]
+use super::ReadConversionError;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use std::borrow::Cow;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct PrimaryKeyParts {
     pub pk: String,
     pub sk: String,
 }
 
+/// A value that can be used as the partition key or sort key of a record.
+///
+/// Key parts are encoded as strings so that the order of the encoded strings matches the order of
+/// the values, which is what DynamoDB uses to sort items by their sort key. Numbers are padded
+/// with zeros, signed numbers are offset so negative numbers sort first and timestamps are
+/// formatted as RFC 3339 with nanoseconds in UTC.
+///
+/// Implement this trait to use a newtype as a key:
+///
+/// ```
+/// use cipherstash_dynamodb::{
+///     traits::{KeyPart, ReadConversionError},
+///     Pk,
+/// };
+///
+/// struct UserId(u64);
+///
+/// impl KeyPart for UserId {
+///     fn to_key_part(&self) -> String {
+///         self.0.to_key_part()
+///     }
+///
+///     fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+///         u64::from_key_part(value).map(Self)
+///     }
+/// }
+///
+/// let key: Pk<UserId> = UserId(42).into();
+/// ```
+pub trait KeyPart: Sized {
+    /// Encode `self` as a string that sorts in the same order as `self`.
+    fn to_key_part(&self) -> String;
+
+    /// Decode a value encoded with [`KeyPart::to_key_part`].
+    fn from_key_part(value: &str) -> Result<Self, ReadConversionError>;
+}
+
+impl KeyPart for String {
+    fn to_key_part(&self) -> String {
+        self.clone()
+    }
+
+    fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+        Ok(value.to_string())
+    }
+}
+
+macro_rules! impl_unsigned_key_part {
+    ($($ty:ty => $width:literal),* $(,)?) => {
+        $(
+            impl KeyPart for $ty {
+                fn to_key_part(&self) -> String {
+                    format!("{:0width$}", self, width = $width)
+                }
+
+                fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+                    value
+                        .parse()
+                        .map_err(|_| ReadConversionError::InvalidFormat(stringify!($ty).to_string()))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_signed_key_part {
+    ($($ty:ty => $unsigned:ty),* $(,)?) => {
+        $(
+            impl KeyPart for $ty {
+                // Flip the sign bit so that negative numbers sort before positive numbers
+                fn to_key_part(&self) -> String {
+                    ((*self as $unsigned) ^ (1 << (<$unsigned>::BITS - 1))).to_key_part()
+                }
+
+                fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+                    <$unsigned>::from_key_part(value)
+                        .map(|value| (value ^ (1 << (<$unsigned>::BITS - 1))) as $ty)
+                        .map_err(|_| ReadConversionError::InvalidFormat(stringify!($ty).to_string()))
+                }
+            }
+        )*
+    };
+}
+
+impl_unsigned_key_part!(u8 => 3, u16 => 5, u32 => 10, u64 => 20);
+impl_signed_key_part!(i8 => u8, i16 => u16, i32 => u32, i64 => u64);
+
+/// Escape the `#` separators and `%` escape characters in a part of a tuple.
+fn escape_tuple_part(part: String) -> String {
+    if part.contains(['%', '#']) {
+        part.replace('%', "%25").replace('#', "%23")
+    } else {
+        part
+    }
+}
+
+fn unescape_tuple_part(part: &str) -> Cow<'_, str> {
+    if part.contains('%') {
+        Cow::Owned(part.replace("%23", "#").replace("%25", "%"))
+    } else {
+        Cow::Borrowed(part)
+    }
+}
+
+// Tuples are used for keys made of several fields. The parts are separated by `#`, and any `#` in
+// a part is escaped so that tuples with different parts never have the same encoding.
+macro_rules! impl_tuple_key_part {
+    ($(($($idx:tt $name:ident),+)),* $(,)?) => {
+        $(
+            impl<$($name: KeyPart),+> KeyPart for ($($name,)+) {
+                fn to_key_part(&self) -> String {
+                    [$(escape_tuple_part(self.$idx.to_key_part())),+].join("#")
+                }
+
+                fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+                    let invalid = || ReadConversionError::InvalidFormat(value.to_string());
+                    let mut parts = value.split('#');
+
+                    let tuple = ($(
+                        $name::from_key_part(&unescape_tuple_part(parts.next().ok_or_else(invalid)?))?,
+                    )+);
+
+                    match parts.next() {
+                        Some(_) => Err(invalid()),
+                        None => Ok(tuple),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_tuple_key_part!(
+    (0 A, 1 B),
+    (0 A, 1 B, 2 C),
+    (0 A, 1 B, 2 C, 3 D),
+    (0 A, 1 B, 2 C, 3 D, 4 E),
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F),
+);
+
+impl KeyPart for Uuid {
+    fn to_key_part(&self) -> String {
+        self.as_hyphenated().to_string()
+    }
+
+    fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+        Uuid::parse_str(value).map_err(|_| ReadConversionError::InvalidFormat("Uuid".to_string()))
+    }
+}
+
+impl KeyPart for DateTime<Utc> {
+    fn to_key_part(&self) -> String {
+        self.to_rfc3339_opts(SecondsFormat::Nanos, true)
+    }
+
+    fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+        DateTime::parse_from_rfc3339(value)
+            .map(|value| value.with_timezone(&Utc))
+            .map_err(|_| ReadConversionError::InvalidFormat("DateTime".to_string()))
+    }
+}
+
+impl KeyPart for NaiveDate {
+    fn to_key_part(&self) -> String {
+        self.format("%Y-%m-%d").to_string()
+    }
+
+    fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| ReadConversionError::InvalidFormat("NaiveDate".to_string()))
+    }
+}
+
 pub trait PrimaryKey: private::Sealed {
     type Pk;
     type Sk;
 
+    /// Whether the key includes a sort key provided by the caller.
+    const HAS_SORT_KEY: bool;
+
     fn into_parts(self, type_name: &str, sort_key_prefix: Option<&str>) -> PrimaryKeyParts;
+
+    /// Decode a key from the parts stored in the table.
+    ///
+    /// Keys that are stored encrypted can't be decoded.
+    fn from_parts(
+        parts: &PrimaryKeyParts,
+        sort_key_prefix: Option<&str>,
+    ) -> Result<Self, ReadConversionError>
+    where
+        Self: Sized;
 }
 
-impl PrimaryKey for Pk {
-    type Pk = String;
+impl<P: KeyPart> PrimaryKey for Pk<P> {
+    type Pk = P;
     type Sk = ();
 
+    const HAS_SORT_KEY: bool = false;
+
     fn into_parts(self, type_name: &str, _sort_key_prefix: Option<&str>) -> PrimaryKeyParts {
         PrimaryKeyParts {
-            pk: self.0,
+            pk: self.0.to_key_part(),
             sk: type_name.into(),
         }
     }
+
+    fn from_parts(
+        parts: &PrimaryKeyParts,
+        _sort_key_prefix: Option<&str>,
+    ) -> Result<Self, ReadConversionError> {
+        P::from_key_part(&parts.pk).map(Self)
+    }
 }
 
-impl PrimaryKey for PkSk {
-    type Pk = String;
-    type Sk = String;
+impl<P: KeyPart, S: KeyPart> PrimaryKey for PkSk<P, S> {
+    type Pk = P;
+    type Sk = S;
+
+    const HAS_SORT_KEY: bool = true;
 
     fn into_parts(self, _type_name: &str, sort_key_prefix: Option<&str>) -> PrimaryKeyParts {
+        let sk = self.1.to_key_part();
+
         PrimaryKeyParts {
-            pk: self.0,
+            pk: self.0.to_key_part(),
             sk: if let Some(prefix) = sort_key_prefix {
-                format!("{prefix}#{}", self.1)
+                format!("{prefix}#{sk}")
             } else {
-                self.1
+                sk
             },
         }
     }
+
+    fn from_parts(
+        parts: &PrimaryKeyParts,
+        sort_key_prefix: Option<&str>,
+    ) -> Result<Self, ReadConversionError> {
+        let sk = match sort_key_prefix {
+            Some(prefix) => parts
+                .sk
+                .strip_prefix(prefix)
+                .and_then(|sk| sk.strip_prefix('#'))
+                .ok_or_else(|| ReadConversionError::InvalidFormat(parts.sk.clone()))?,
+            None => &parts.sk,
+        };
+
+        Ok(Self(P::from_key_part(&parts.pk)?, S::from_key_part(sk)?))
+    }
 }
 
-pub struct Pk(pub String);
+/// A primary key made of a partition key only.
+pub struct Pk<P = String>(pub P);
 
 impl Pk {
     pub fn new(pk: impl Into<String>) -> Self {
@@ -47,17 +268,30 @@ impl Pk {
     }
 }
 
-impl<P: Into<String>> From<P> for Pk {
+impl<P: KeyPart> From<P> for Pk<P> {
     fn from(value: P) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Pk {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&String> for Pk {
+    fn from(value: &String) -> Self {
         Self::new(value)
     }
 }
 
-pub struct PkSk(pub String, pub String);
+/// A primary key made of a partition key and a sort key.
+pub struct PkSk<P = String, S = String>(pub P, pub S);
 
-impl<Pk: Into<String>, Sk: Into<String>> From<(Pk, Sk)> for PkSk {
-    fn from(value: (Pk, Sk)) -> Self {
-        Self::new(value.0, value.1)
+impl<P: KeyPart, S: KeyPart, A: Into<P>, B: Into<S>> From<(A, B)> for PkSk<P, S> {
+    fn from(value: (A, B)) -> Self {
+        Self(value.0.into(), value.1.into())
     }
 }
 
@@ -72,6 +306,109 @@ mod private {
 
     pub trait Sealed {}
 
-    impl Sealed for Pk {}
-    impl Sealed for PkSk {}
+    impl<P: KeyPart> Sealed for Pk<P> {}
+    impl<P: KeyPart, S: KeyPart> Sealed for PkSk<P, S> {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ordered<T: KeyPart + PartialEq + std::fmt::Debug>(values: &[T]) {
+        let encoded = values.iter().map(KeyPart::to_key_part).collect::<Vec<_>>();
+
+        let mut sorted = encoded.clone();
+        sorted.sort();
+        assert_eq!(encoded, sorted);
+
+        for (value, encoded) in values.iter().zip(encoded) {
+            assert_eq!(&T::from_key_part(&encoded).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_numbers_are_ordered() {
+        assert_ordered(&[0u64, 9, 10, 100, u64::MAX]);
+        assert_ordered(&[i64::MIN, -100, -10, -9, 0, 9, 10, 100, i64::MAX]);
+        assert_ordered(&[i8::MIN, -1, 0, 1, i8::MAX]);
+        assert_ordered(&[0u16, 7, 70, u16::MAX]);
+    }
+
+    #[test]
+    fn test_dates_are_ordered() {
+        let earlier = DateTime::parse_from_rfc3339("2024-01-01T09:00:00.5+10:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let later = DateTime::parse_from_rfc3339("2024-01-01T00:00:01Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_ordered(&[earlier, later]);
+        assert_ordered(&[
+            NaiveDate::from_ymd_opt(2023, 12, 31).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn test_tuples_are_ordered() {
+        assert_ordered(&[
+            (2023u16, 12u8, 1u32),
+            (2024, 1, 2),
+            (2024, 2, 1),
+            (2024, 10, 0),
+        ]);
+        assert_ordered(&[(1u8, "a#b".to_string()), (1, "b".to_string())]);
+
+        assert!(<(u8, u8)>::from_key_part("001").is_err());
+        assert!(<(u8, u8)>::from_key_part("001#002#003").is_err());
+    }
+
+    #[test]
+    fn test_tuples_escape_separators() {
+        let left = ("a#b".to_string(), "c".to_string());
+        let right = ("a".to_string(), "b#c".to_string());
+
+        assert_ne!(left.to_key_part(), right.to_key_part());
+        assert_eq!(left.to_key_part(), "a%23b#c");
+
+        for value in [
+            left,
+            right,
+            ("%23".to_string(), "%#%".to_string()),
+            ("".to_string(), "#".to_string()),
+        ] {
+            assert_eq!(
+                <(String, String)>::from_key_part(&value.to_key_part()).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_uuid_key_part() {
+        let id = Uuid::new_v4();
+        assert_eq!(id.to_key_part(), id.to_string());
+        assert_eq!(Uuid::from_key_part(&id.to_key_part()).unwrap(), id);
+    }
+
+    #[test]
+    fn test_primary_key_round_trip() {
+        let id = Uuid::new_v4();
+        let parts = PkSk::<Uuid, u64>(id, 42).into_parts("user", Some("user"));
+
+        assert_eq!(parts.pk, id.to_string());
+        assert_eq!(parts.sk, "user#00000000000000000042");
+
+        let PkSk(pk, sk) = PkSk::<Uuid, u64>::from_parts(&parts, Some("user")).unwrap();
+        assert_eq!(pk, id);
+        assert_eq!(sk, 42);
+
+        let parts = Pk::new("dan@coderdan.co").into_parts("user", None);
+        assert_eq!(parts.sk, "user");
+        assert_eq!(
+            Pk::<String>::from_parts(&parts, None).unwrap().0,
+            "dan@coderdan.co"
+        );
+    }
 }
@@ -0,0 +1,173 @@
+use super::{IndexType, PrimaryKey, Searchable, SingleIndex};
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// An aggregate description of how a type is stored and indexed.
+///
+/// The schema serializes to JSON so that it can be published for review or shared with services
+/// that aren't written in Rust.
+/// Types that use the derive macros get a complete schema from [`Searchable::type_schema`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeSchema {
+    pub type_name: Cow<'static, str>,
+    pub sort_key_prefix: Option<Cow<'static, str>>,
+    pub partition_key: KeySchema,
+    /// The sort key of the type or `None` when the type only has a partition key.
+    pub sort_key: Option<KeySchema>,
+    pub attributes: Vec<AttributeSchema>,
+    pub indexes: Vec<IndexSchema>,
+    /// Fields that are never stored.
+    pub skipped: Vec<Cow<'static, str>>,
+}
+
+/// Describes a key of a type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeySchema {
+    /// The field used as the key, if known.
+    /// Keys made of several fields are named after their fields separated by `#`.
+    pub field: Option<Cow<'static, str>>,
+    pub encrypted: bool,
+}
+
+/// Whether an attribute is encrypted or stored in plaintext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeMode {
+    Protected,
+    Plaintext,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AttributeSchema {
+    pub name: Cow<'static, str>,
+    pub mode: AttributeMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndexSchema {
+    pub name: Cow<'static, str>,
+    pub index_type: IndexType,
+    /// The fields that make up the index and the index type of each.
+    pub components: Vec<IndexComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndexComponent {
+    pub field: Cow<'static, str>,
+    pub index_type: SingleIndex,
+}
+
+impl IndexSchema {
+    /// Describe an index from its name and type.
+    ///
+    /// Compound indexes are named after their fields separated by `#`.
+    pub fn new(name: impl Into<Cow<'static, str>>, index_type: IndexType) -> Self {
+        let name = name.into();
+
+        let component_types = match index_type {
+            IndexType::Single(index) => vec![index],
+            IndexType::Compound2((index_a, index_b)) => vec![index_a, index_b],
+        };
+
+        let components = name
+            .split('#')
+            .zip(component_types)
+            .map(|(field, index_type)| IndexComponent {
+                field: Cow::Owned(field.to_string()),
+                index_type,
+            })
+            .collect();
+
+        Self {
+            name,
+            index_type,
+            components,
+        }
+    }
+}
+
+impl TypeSchema {
+    /// Build a schema from the functions of the [`Searchable`] trait and its supertraits.
+    ///
+    /// Key field names and skipped fields can't be known from the traits alone and are left
+    /// empty.
+    pub fn from_traits<T: Searchable>() -> Self {
+        let attributes = T::protected_attributes()
+            .iter()
+            .map(|name| AttributeSchema {
+                name: name.clone(),
+                mode: AttributeMode::Protected,
+            })
+            .chain(
+                T::plaintext_attributes()
+                    .iter()
+                    .map(|name| AttributeSchema {
+                        name: name.clone(),
+                        mode: AttributeMode::Plaintext,
+                    }),
+            )
+            .collect();
+
+        let indexes = T::protected_indexes()
+            .iter()
+            .map(|(name, index_type)| IndexSchema::new(name.clone(), *index_type))
+            .collect();
+
+        Self {
+            type_name: T::type_name(),
+            sort_key_prefix: T::sort_key_prefix(),
+            partition_key: KeySchema {
+                field: None,
+                encrypted: T::is_pk_encrypted(),
+            },
+            sort_key: <T::PrimaryKey as PrimaryKey>::HAS_SORT_KEY.then(|| KeySchema {
+                field: None,
+                encrypted: T::is_sk_encrypted(),
+            }),
+            attributes,
+            indexes,
+            skipped: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_components() {
+        let index = IndexSchema::new(
+            "name#email",
+            IndexType::Compound2((SingleIndex::Prefix, SingleIndex::Exact)),
+        );
+
+        assert_eq!(
+            index.components,
+            vec![
+                IndexComponent {
+                    field: Cow::Borrowed("name"),
+                    index_type: SingleIndex::Prefix,
+                },
+                IndexComponent {
+                    field: Cow::Borrowed("email"),
+                    index_type: SingleIndex::Exact,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_json() {
+        let index = IndexSchema::new("email", IndexType::Single(SingleIndex::Exact));
+
+        assert_eq!(
+            serde_json::to_value(&index).unwrap(),
+            serde_json::json!({
+                "name": "email",
+                "index_type": "exact",
+                "components": [{ "field": "email", "index_type": "exact" }],
+            })
+        );
+    }
+}
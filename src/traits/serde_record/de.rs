@@ -0,0 +1,267 @@
+//! Deserializes a record directly from an [`Unsealed`], the inverse of [`super::ser`].
+
+use crate::{
+    crypto::{plaintext_map_to_json, table_attribute_to_json, Unsealed},
+    encrypted_table::TableAttribute,
+    traits::ReadConversionError,
+};
+use cipherstash_client::encryption::Plaintext;
+use serde::{
+    de::{
+        self,
+        value::{Error, MapDeserializer, SeqDeserializer},
+        DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap};
+
+/// Deserialize a `T` from the fields in `protected` and `plaintext` of `unsealed`.
+pub(super) fn from_unsealed<T: DeserializeOwned>(
+    mut unsealed: Unsealed,
+    protected: &[Cow<'static, str>],
+    plaintext: &[Cow<'static, str>],
+) -> Result<T, ReadConversionError> {
+    let mut fields = Vec::with_capacity(protected.len() + plaintext.len());
+
+    for name in protected {
+        if let Some(plaintext) = unsealed.take_protected(name) {
+            fields.push((name.to_string(), Field::Plaintext(plaintext)));
+        } else if let Some(map) = unsealed.take_protected_map(name) {
+            fields.push((name.to_string(), Field::Map(map)));
+        }
+    }
+
+    for name in plaintext {
+        match unsealed.take_unprotected(name.as_ref()) {
+            TableAttribute::Null => {}
+            attribute => fields.push((name.to_string(), Field::Attribute(attribute))),
+        }
+    }
+
+    T::deserialize(MapDeserializer::new(fields.into_iter()))
+        .map_err(|e: Error| ReadConversionError::InvalidFormat(e.to_string()))
+}
+
+/// A field of a record read from an [`Unsealed`].
+enum Field {
+    Plaintext(Plaintext),
+    Map(HashMap<String, Plaintext>),
+    Attribute(TableAttribute),
+}
+
+impl Field {
+    /// Returns the field as JSON if it holds a value that was stored as JSON.
+    fn into_json(self) -> Result<Value, Self> {
+        match self {
+            Self::Plaintext(Plaintext::JsonB(Some(ref value))) => Ok(value.clone()),
+            Self::Map(map) => Ok(plaintext_map_to_json(&map)),
+            Self::Attribute(
+                attribute @ (TableAttribute::StringVec(_)
+                | TableAttribute::ByteVec(_)
+                | TableAttribute::NumberVec(_)
+                | TableAttribute::Map(_)
+                | TableAttribute::List(_)),
+            ) => Ok(table_attribute_to_json(&attribute)),
+            field => Err(field),
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        match self {
+            Self::Plaintext(plaintext) => plaintext_is_null(plaintext),
+            Self::Map(_) => false,
+            Self::Attribute(attribute) => matches!(attribute, TableAttribute::Null),
+        }
+    }
+}
+
+fn plaintext_is_null(plaintext: &Plaintext) -> bool {
+    match plaintext {
+        Plaintext::BigInt(x) => x.is_none(),
+        Plaintext::BigUInt(x) => x.is_none(),
+        Plaintext::Boolean(x) => x.is_none(),
+        Plaintext::Decimal(x) => x.is_none(),
+        Plaintext::Float(x) => x.is_none(),
+        Plaintext::Int(x) => x.is_none(),
+        Plaintext::NaiveDate(x) => x.is_none(),
+        Plaintext::SmallInt(x) => x.is_none(),
+        Plaintext::Timestamp(x) => x.is_none(),
+        Plaintext::Utf8Str(x) => x.is_none(),
+        Plaintext::JsonB(x) => x.as_ref().is_none_or(Value::is_null),
+    }
+}
+
+fn visit_plaintext<'de, V: Visitor<'de>>(
+    plaintext: &Plaintext,
+    visitor: V,
+) -> Result<V::Value, Error> {
+    match plaintext {
+        Plaintext::BigInt(Some(x)) => visitor.visit_i64(*x),
+        Plaintext::BigUInt(Some(x)) => visitor.visit_u64(*x),
+        Plaintext::Boolean(Some(x)) => visitor.visit_bool(*x),
+        Plaintext::Decimal(Some(x)) => visitor.visit_string(x.to_string()),
+        Plaintext::Float(Some(x)) => visitor.visit_f64(*x),
+        Plaintext::Int(Some(x)) => visitor.visit_i32(*x),
+        Plaintext::NaiveDate(Some(x)) => visitor.visit_string(x.to_string()),
+        Plaintext::SmallInt(Some(x)) => visitor.visit_i16(*x),
+        Plaintext::Timestamp(Some(x)) => visitor.visit_string(x.to_rfc3339()),
+        Plaintext::Utf8Str(Some(x)) => visitor.visit_str(x),
+        _ => visitor.visit_unit(),
+    }
+}
+
+fn visit_attribute<'de, V: Visitor<'de>>(
+    attribute: TableAttribute,
+    visitor: V,
+) -> Result<V::Value, Error> {
+    match attribute {
+        TableAttribute::String(x) => visitor.visit_string(x),
+        TableAttribute::Number(x) => visit_number(x, visitor),
+        TableAttribute::Bool(x) => visitor.visit_bool(x),
+        TableAttribute::Bytes(x) => visitor.visit_byte_buf(x),
+        TableAttribute::Null => visitor.visit_unit(),
+        attribute => table_attribute_to_json(&attribute)
+            .deserialize_any(visitor)
+            .map_err(de::Error::custom),
+    }
+}
+
+/// DynamoDB numbers are strings so integers of any size can be read back.
+fn visit_number<'de, V: Visitor<'de>>(number: String, visitor: V) -> Result<V::Value, Error> {
+    if let Ok(x) = number.parse() {
+        visitor.visit_i64(x)
+    } else if let Ok(x) = number.parse() {
+        visitor.visit_u64(x)
+    } else if let Ok(x) = number.parse() {
+        visitor.visit_i128(x)
+    } else if let Ok(x) = number.parse() {
+        visitor.visit_u128(x)
+    } else if let Ok(x) = number.parse() {
+        visitor.visit_f64(x)
+    } else {
+        visitor.visit_string(number)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Field {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Forwards to the JSON value of fields stored as JSON so that structs and enums are read with
+/// the type hints they expect.
+macro_rules! forward_to_json {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Error> {
+                match self.into_json() {
+                    Ok(value) => value.$method($($arg,)* visitor).map_err(de::Error::custom),
+                    Err(field) => field.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Field {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.into_json() {
+            Ok(value) => value.deserialize_any(visitor).map_err(de::Error::custom),
+            Err(Self::Plaintext(plaintext)) => visit_plaintext(&plaintext, visitor),
+            Err(Self::Attribute(attribute)) => visit_attribute(attribute, visitor),
+            Err(Self::Map(_)) => unreachable!("maps are read as JSON"),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.into_json() {
+            Ok(value) => value
+                .deserialize_newtype_struct(name, visitor)
+                .map_err(de::Error::custom),
+            Err(field) => visitor.visit_newtype_struct(field),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.into_json() {
+            Ok(value) => value
+                .deserialize_enum(name, variants, visitor)
+                .map_err(de::Error::custom),
+            // Unit variants are stored as their name
+            Err(Self::Plaintext(Plaintext::Utf8Str(Some(ref variant)))) => {
+                visitor.visit_enum(variant.clone().into_deserializer())
+            }
+            Err(Self::Attribute(TableAttribute::String(variant))) => {
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            Err(field) => field.deserialize_any(visitor),
+        }
+    }
+
+    // Byte fields such as `Vec<u8>` are read as sequences
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.into_json() {
+            Ok(value) => value.deserialize_seq(visitor).map_err(de::Error::custom),
+            Err(Self::Attribute(TableAttribute::Bytes(bytes))) => {
+                visitor.visit_seq(SeqDeserializer::new(bytes.into_iter()))
+            }
+            Err(field) => field.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_json! {
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_i128()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_u128()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_bytes()
+        deserialize_byte_buf()
+        deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_identifier()
+    }
+
+    forward_to_deserialize_any! {
+        ignored_any
+    }
+}
@@ -0,0 +1,457 @@
+mod de;
+mod ser;
+
+use super::{
+    ComposableIndex, ComposablePlaintext, Decryptable, Encryptable, Identifiable, IndexType,
+    Searchable, SingleIndex,
+};
+use crate::crypto::{SealError, Unsealed};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, fmt::Debug};
+
+/// Describes which fields of a [`SerdeRecord`] are stored and how they are indexed.
+///
+/// Fields that aren't added to the schema are not stored, so they must be optional or skipped
+/// when deserializing.
+///
+/// ```
+/// # use cipherstash_dynamodb::{traits::SerdeSchema, SingleIndex};
+/// let schema = SerdeSchema::new()
+///     .query("email", SingleIndex::Exact)
+///     .protected("address")
+///     .plaintext("created_at");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SerdeSchema {
+    protected_attributes: Vec<Cow<'static, str>>,
+    plaintext_attributes: Vec<Cow<'static, str>>,
+    indexes: Vec<(Cow<'static, str>, IndexType)>,
+}
+
+impl SerdeSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encrypt the field `name`.
+    ///
+    /// Nested structs, enums and collections are encrypted as a single JSON value.
+    pub fn protected(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        let name = name.into();
+        self.plaintext_attributes.retain(|x| x != &name);
+
+        if !self.protected_attributes.contains(&name) {
+            self.protected_attributes.push(name);
+        }
+
+        self
+    }
+
+    /// Store the field `name` in plaintext.
+    pub fn plaintext(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        let name = name.into();
+        self.protected_attributes.retain(|x| x != &name);
+
+        if !self.plaintext_attributes.contains(&name) {
+            self.plaintext_attributes.push(name);
+        }
+
+        self
+    }
+
+    /// Encrypt the field `name` and add an index on it.
+    pub fn query(self, name: impl Into<Cow<'static, str>>, index: SingleIndex) -> Self {
+        let name = name.into();
+        let mut schema = self.protected(name.clone());
+
+        schema.indexes.push((name, IndexType::Single(index)));
+        schema
+    }
+
+    /// Encrypt the fields `name_a` and `name_b` and add a compound index on them.
+    pub fn compound_query(
+        self,
+        (name_a, index_a): (impl Into<Cow<'static, str>>, SingleIndex),
+        (name_b, index_b): (impl Into<Cow<'static, str>>, SingleIndex),
+    ) -> Self {
+        let name_a = name_a.into();
+        let name_b = name_b.into();
+        let index_name = format!("{name_a}#{name_b}");
+
+        let mut schema = self.protected(name_a).protected(name_b);
+
+        schema.indexes.push((
+            Cow::Owned(index_name),
+            IndexType::Compound2((index_a, index_b)),
+        ));
+        schema
+    }
+
+    fn has_index(&self, index_name: &str, index_type: IndexType) -> bool {
+        self.indexes
+            .iter()
+            .any(|(name, t)| name == index_name && *t == index_type)
+    }
+}
+
+/// Implements [`Encryptable`], [`Decryptable`] and [`Searchable`] for a type that implements
+/// [`Serialize`] and [`Deserialize`](serde::Deserialize) so that the fields don't need to be
+/// annotated with `#[cipherstash(...)]` attributes.
+///
+/// Scalar fields are stored as plaintexts of the matching type and nested structs, enums and
+/// collections are stored as JSON. Which fields are encrypted and indexed is described by a
+/// [`SerdeSchema`].
+///
+/// Encrypted numbers are stored as `BigInt`, `BigUInt` or `Float` plaintexts, so queries on
+/// numeric indexes must use `i64`, `u64` or `f64` values, and encrypting an integer that doesn't
+/// fit in a `u64` fails. Plaintext fields keep integers of any size and store values serialized
+/// as bytes, such as with `serde_bytes`, as binary.
+///
+/// Encrypting a value that doesn't serialize to a struct or map fails with a
+/// [`WriteConversionError`](super::WriteConversionError).
+///
+/// ```
+/// use cipherstash_dynamodb::{traits::{SerdeRecord, SerdeSchema}, Identifiable, SingleIndex};
+/// use serde::{Deserialize, Serialize};
+/// use std::sync::OnceLock;
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// enum Plan {
+///     Free,
+///     Paid { seats: u32 },
+/// }
+///
+/// #[derive(Debug, Serialize, Deserialize, Identifiable)]
+/// struct Account {
+///     #[partition_key]
+///     email: String,
+///     plan: Plan,
+///     created_at: String,
+/// }
+///
+/// impl SerdeRecord for Account {
+///     fn schema() -> &'static SerdeSchema {
+///         static SCHEMA: OnceLock<SerdeSchema> = OnceLock::new();
+///
+///         SCHEMA.get_or_init(|| {
+///             SerdeSchema::new()
+///                 .query("email", SingleIndex::Exact)
+///                 .protected("plan")
+///                 .plaintext("created_at")
+///         })
+///     }
+/// }
+/// ```
+pub trait SerdeRecord: Serialize + DeserializeOwned + Debug + Identifiable {
+    fn schema() -> &'static SerdeSchema;
+}
+
+impl<T: SerdeRecord> Encryptable for T {
+    fn protected_attributes() -> Cow<'static, [Cow<'static, str>]> {
+        Cow::Borrowed(&T::schema().protected_attributes)
+    }
+
+    fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]> {
+        Cow::Borrowed(&T::schema().plaintext_attributes)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a field can't be converted to a plaintext, records are written with
+    /// [`Encryptable::try_into_unsealed`] which returns an error instead.
+    fn into_unsealed(self) -> Unsealed {
+        self.try_into_unsealed()
+            .unwrap_or_else(|e| panic!("Failed to convert record into Unsealed: {e}"))
+    }
+
+    fn try_into_unsealed(self) -> Result<Unsealed, SealError> {
+        let schema = T::schema();
+
+        Ok(ser::to_unsealed(
+            &self,
+            &T::type_name(),
+            &schema.protected_attributes,
+            &schema.plaintext_attributes,
+        )?)
+    }
+}
+
+impl<T: SerdeRecord> Decryptable for T {
+    fn from_unsealed(unsealed: Unsealed) -> Result<Self, SealError> {
+        let schema = T::schema();
+
+        Ok(de::from_unsealed(
+            unsealed,
+            &schema.protected_attributes,
+            &schema.plaintext_attributes,
+        )?)
+    }
+
+    fn protected_attributes() -> Cow<'static, [Cow<'static, str>]> {
+        Cow::Borrowed(&T::schema().protected_attributes)
+    }
+
+    fn plaintext_attributes() -> Cow<'static, [Cow<'static, str>]> {
+        Cow::Borrowed(&T::schema().plaintext_attributes)
+    }
+}
+
+impl<T: SerdeRecord> Searchable for T {
+    fn attribute_for_index(
+        &self,
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Option<ComposablePlaintext> {
+        if !T::schema().has_index(index_name, index_type) {
+            return None;
+        }
+
+        // Serialization errors are returned when the record is encrypted
+        let fields = index_name
+            .split('#')
+            .map(|field| Cow::Owned(field.to_string()))
+            .collect::<Vec<_>>();
+        let mut unsealed = ser::to_unsealed(self, &T::type_name(), &fields, &[]).ok()?;
+
+        let mut fields = fields.iter();
+        let first = unsealed.take_protected(fields.next()?)?;
+
+        fields.try_fold(ComposablePlaintext::new(first), |plaintext, field| {
+            plaintext.try_compose(unsealed.take_protected(field)?).ok()
+        })
+    }
+
+    fn protected_indexes() -> Cow<'static, [(Cow<'static, str>, IndexType)]> {
+        Cow::Borrowed(&T::schema().indexes)
+    }
+
+    fn index_by_name(
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Option<Box<dyn ComposableIndex + Send>> {
+        T::schema()
+            .has_index(index_name, index_type)
+            .then(|| index_type.indexer())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encrypted_table::{PreparedRecord, TableAttribute},
+        traits::{Plaintext, WriteConversionError},
+        Pk,
+    };
+    use serde::Deserialize;
+    use std::sync::OnceLock;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Plan {
+        Free,
+        Paid { seats: u32 },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+        postcode: Option<String>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Account {
+        email: String,
+        name: String,
+        age: i32,
+        plan: Plan,
+        address: Address,
+        nickname: Option<String>,
+        tag: String,
+        visits: u64,
+        balance: u128,
+        #[serde(serialize_with = "serialize_bytes")]
+        avatar: Vec<u8>,
+        #[serde(default)]
+        cached: bool,
+    }
+
+    fn serialize_bytes<S: serde::Serializer>(
+        bytes: &[u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    impl Identifiable for Account {
+        type PrimaryKey = Pk;
+
+        fn get_primary_key(&self) -> Self::PrimaryKey {
+            Pk(self.email.clone())
+        }
+
+        fn type_name() -> Cow<'static, str> {
+            Cow::Borrowed("account")
+        }
+
+        fn sort_key_prefix() -> Option<Cow<'static, str>> {
+            None
+        }
+    }
+
+    impl SerdeRecord for Account {
+        fn schema() -> &'static SerdeSchema {
+            static SCHEMA: OnceLock<SerdeSchema> = OnceLock::new();
+
+            SCHEMA.get_or_init(|| {
+                SerdeSchema::new()
+                    .compound_query(("email", SingleIndex::Exact), ("name", SingleIndex::Prefix))
+                    .protected("age")
+                    .protected("plan")
+                    .protected("address")
+                    .protected("nickname")
+                    .protected("visits")
+                    .plaintext("tag")
+                    .plaintext("balance")
+                    .plaintext("avatar")
+            })
+        }
+    }
+
+    fn account() -> Account {
+        Account {
+            email: "dan@coderdan.co".to_string(),
+            name: "Dan".to_string(),
+            age: 42,
+            plan: Plan::Paid { seats: 5 },
+            address: Address {
+                city: "Sydney".to_string(),
+                postcode: None,
+            },
+            nickname: None,
+            tag: "admin".to_string(),
+            visits: u64::MAX,
+            balance: u128::MAX,
+            avatar: vec![0, 1, 255],
+            cached: true,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let account = account();
+        let unsealed = account.clone().into_unsealed();
+
+        // Fields that aren't in the schema aren't stored
+        assert_eq!(
+            Account::from_unsealed(unsealed).unwrap(),
+            Account {
+                cached: false,
+                ..account
+            }
+        );
+    }
+
+    #[test]
+    fn test_into_unsealed() {
+        let mut unsealed = account().into_unsealed();
+
+        assert_eq!(
+            unsealed.take_protected("age"),
+            Some(Plaintext::BigInt(Some(42)))
+        );
+        assert_eq!(
+            unsealed.take_protected("plan"),
+            Some(Plaintext::JsonB(Some(
+                serde_json::json!({ "Paid": { "seats": 5 } })
+            )))
+        );
+        assert_eq!(unsealed.take_protected("nickname"), None);
+        assert_eq!(unsealed.take_protected("cached"), None);
+        assert_eq!(
+            unsealed.take_protected("visits"),
+            Some(Plaintext::BigUInt(Some(u64::MAX)))
+        );
+        assert_eq!(
+            unsealed.take_unprotected("balance"),
+            TableAttribute::Number(u128::MAX.to_string())
+        );
+        assert_eq!(
+            unsealed.take_unprotected("avatar"),
+            TableAttribute::Bytes(vec![0, 1, 255])
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Counter(u128);
+
+    impl Identifiable for Counter {
+        type PrimaryKey = Pk;
+
+        fn get_primary_key(&self) -> Self::PrimaryKey {
+            Pk(self.0.to_string())
+        }
+
+        fn type_name() -> Cow<'static, str> {
+            Cow::Borrowed("counter")
+        }
+
+        fn sort_key_prefix() -> Option<Cow<'static, str>> {
+            None
+        }
+    }
+
+    impl SerdeRecord for Counter {
+        fn schema() -> &'static SerdeSchema {
+            static SCHEMA: OnceLock<SerdeSchema> = OnceLock::new();
+
+            SCHEMA.get_or_init(|| SerdeSchema::new().query("count", SingleIndex::Exact))
+        }
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        assert!(matches!(
+            Counter(1).try_into_unsealed(),
+            Err(SealError::WriteConversionError(_))
+        ));
+
+        assert!(matches!(
+            PreparedRecord::prepare_record(Counter(1)),
+            Err(SealError::WriteConversionError(_))
+        ));
+
+        #[derive(Serialize)]
+        struct Visits {
+            visits: u128,
+        }
+
+        // Encrypted integers must fit in a `u64`
+        let value = Visits { visits: u128::MAX };
+        assert!(matches!(
+            ser::to_unsealed(&value, "visits", &[Cow::Borrowed("visits")], &[]),
+            Err(WriteConversionError::ConversionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_indexes() {
+        assert_eq!(
+            <Account as Searchable>::protected_indexes().as_ref(),
+            &[(
+                Cow::Borrowed("email#name"),
+                IndexType::Compound2((SingleIndex::Exact, SingleIndex::Prefix))
+            )]
+        );
+
+        let account = account();
+        let index_type = IndexType::Compound2((SingleIndex::Exact, SingleIndex::Prefix));
+
+        assert!(account
+            .attribute_for_index("email#name", index_type)
+            .is_some());
+        assert!(account
+            .attribute_for_index("email", IndexType::Single(SingleIndex::Exact))
+            .is_none());
+        assert!(Account::index_by_name("email#name", index_type).is_some());
+    }
+}
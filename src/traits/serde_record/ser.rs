@@ -0,0 +1,675 @@
+//! Serializes a record directly into an [`Unsealed`].
+//!
+//! Scalars are converted to plaintexts and table attributes without going through
+//! [`serde_json::Value`], so integers that don't fit in an `i64` or `u64` are kept in plaintext
+//! attributes and bytes are stored as binary attributes. Sequences, maps and enum variants with
+//! fields are stored as JSON.
+
+use crate::{
+    crypto::{json_to_table_attribute, Unsealed},
+    encrypted_table::TableAttribute,
+    traits::WriteConversionError,
+};
+use cipherstash_client::encryption::Plaintext;
+use serde::ser::{self, Impossible, Serialize, SerializeMap, SerializeStruct, Serializer};
+use serde_json::Value;
+use std::{borrow::Cow, fmt::Display};
+
+/// Serialize `value` into an [`Unsealed`], encrypting the fields in `protected` and storing the
+/// fields in `plaintext` as they are. Other fields are skipped.
+pub(super) fn to_unsealed<T: Serialize + ?Sized>(
+    value: &T,
+    type_name: &str,
+    protected: &[Cow<'static, str>],
+    plaintext: &[Cow<'static, str>],
+) -> Result<Unsealed, WriteConversionError> {
+    value
+        .serialize(RecordSerializer {
+            unsealed: Unsealed::new_with_descriptor(type_name),
+            type_name,
+            protected,
+            plaintext,
+            key: None,
+        })
+        .map_err(|e| WriteConversionError::ConversionFailed(format!("{type_name}: {e}")))
+}
+
+#[derive(Debug)]
+enum Error {
+    /// The value is a sequence, map or enum variant with fields so it is stored as JSON.
+    Compound,
+    Custom(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Compound => f.write_str("expected a scalar value"),
+            Self::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+    serde_json::to_value(value).map_err(ser::Error::custom)
+}
+
+/// Serializes the fields of a struct or map into an [`Unsealed`].
+struct RecordSerializer<'a> {
+    unsealed: Unsealed,
+    type_name: &'a str,
+    protected: &'a [Cow<'static, str>],
+    plaintext: &'a [Cow<'static, str>],
+    /// The key of the map entry being serialized.
+    key: Option<String>,
+}
+
+impl RecordSerializer<'_> {
+    fn add_field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        if self.protected.iter().any(|x| x == name) {
+            let plaintext = match value.serialize(PlaintextSerializer) {
+                Err(Error::Compound) => Some(Plaintext::JsonB(Some(to_json(value)?))),
+                result => result?,
+            };
+
+            // Null values are not stored
+            if let Some(plaintext) = plaintext {
+                self.unsealed.add_protected(name, plaintext);
+            }
+        } else if self.plaintext.iter().any(|x| x == name) {
+            let attribute = match value.serialize(TableAttributeSerializer) {
+                Err(Error::Compound) => json_to_table_attribute(to_json(value)?),
+                result => result?,
+            };
+
+            self.unsealed.add_unprotected(name, attribute);
+        }
+
+        Ok(())
+    }
+
+    fn not_a_record<T>(&self) -> Result<T, Error> {
+        Err(Error::Custom(format!(
+            "{} must serialize to a struct or map",
+            self.type_name
+        )))
+    }
+}
+
+impl<'a> Serializer for RecordSerializer<'a> {
+    type Ok = Unsealed;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<Unsealed, Error>;
+    type SerializeTuple = Impossible<Unsealed, Error>;
+    type SerializeTupleStruct = Impossible<Unsealed, Error>;
+    type SerializeTupleVariant = Impossible<Unsealed, Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<Unsealed, Error>;
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Unsealed, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_none(self) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_unit(self) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Unsealed, Error> {
+        self.not_a_record()
+    }
+}
+
+impl SerializeStruct for RecordSerializer<'_> {
+    type Ok = Unsealed;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.add_field(key, value)
+    }
+
+    fn end(self) -> Result<Unsealed, Error> {
+        Ok(self.unsealed)
+    }
+}
+
+impl SerializeMap for RecordSerializer<'_> {
+    type Ok = Unsealed;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match to_json(key)? {
+            Value::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::Custom("map keys must be strings".to_string())),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::Custom("map value without a key".to_string()))?;
+
+        self.add_field(&key, value)
+    }
+
+    fn end(self) -> Result<Unsealed, Error> {
+        Ok(self.unsealed)
+    }
+}
+
+/// Serializes a scalar into a [`Plaintext`], or `None` for null values.
+///
+/// Integers are stored as `BigInt` plaintexts unless they only fit in a `u64`, in which case they
+/// are stored as `BigUInt`.
+struct PlaintextSerializer;
+
+impl Serializer for PlaintextSerializer {
+    type Ok = Option<Plaintext>;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<Option<Plaintext>, Error>;
+    type SerializeTuple = Impossible<Option<Plaintext>, Error>;
+    type SerializeTupleStruct = Impossible<Option<Plaintext>, Error>;
+    type SerializeTupleVariant = Impossible<Option<Plaintext>, Error>;
+    type SerializeMap = Impossible<Option<Plaintext>, Error>;
+    type SerializeStruct = Impossible<Option<Plaintext>, Error>;
+    type SerializeStructVariant = Impossible<Option<Plaintext>, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(Some(Plaintext::Boolean(Some(v))))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Ok(Some(Plaintext::BigInt(Some(v))))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Error> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => u64::try_from(v)
+                .map_err(|_| Error::Custom(format!("{v} is too large to be encrypted")))
+                .and_then(|v| self.serialize_u64(v)),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Ok(Some(Plaintext::BigUInt(Some(v)))),
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Error> {
+        u64::try_from(v)
+            .map_err(|_| Error::Custom(format!("{v} is too large to be encrypted")))
+            .and_then(|v| self.serialize_u64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Ok(Some(Plaintext::Float(Some(v))))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        Ok(Some(Plaintext::Utf8Str(Some(v.to_string()))))
+    }
+
+    // There is no bytes plaintext so bytes are stored as a JSON array
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Compound)
+    }
+}
+
+/// Serializes a scalar into a [`TableAttribute`].
+///
+/// Numbers are stored as their decimal representation so integers of any size are kept.
+struct TableAttributeSerializer;
+
+impl TableAttributeSerializer {
+    fn number(v: impl ToString) -> Result<TableAttribute, Error> {
+        Ok(TableAttribute::Number(v.to_string()))
+    }
+}
+
+impl Serializer for TableAttributeSerializer {
+    type Ok = TableAttribute;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<TableAttribute, Error>;
+    type SerializeTuple = Impossible<TableAttribute, Error>;
+    type SerializeTupleStruct = Impossible<TableAttribute, Error>;
+    type SerializeTupleVariant = Impossible<TableAttribute, Error>;
+    type SerializeMap = Impossible<TableAttribute, Error>;
+    type SerializeStruct = Impossible<TableAttribute, Error>;
+    type SerializeStructVariant = Impossible<TableAttribute, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(TableAttribute::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Error> {
+        Self::number(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    // Like JSON, numbers that aren't finite are stored as null
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        if v.is_finite() {
+            Self::number(v)
+        } else {
+            Ok(TableAttribute::Null)
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        Ok(TableAttribute::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        Ok(TableAttribute::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(TableAttribute::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(TableAttribute::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(TableAttribute::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        Ok(TableAttribute::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::Compound)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Compound)
+    }
+}
@@ -1,13 +1,10 @@
-use aws_sdk_dynamodb::{
-    types::{
-        AttributeDefinition, GlobalSecondaryIndex, KeySchemaElement, KeyType, Projection,
-        ProjectionType, ProvisionedThroughput, ScalarAttributeType,
-    },
-    Client,
+use aws_sdk_dynamodb::{types::AttributeValue, Client};
+use cipherstash_dynamodb::{
+    encrypted_table::{CreateTableOptions, Headless, PreparedRecord},
+    Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable,
 };
-use cipherstash_dynamodb::EncryptedTable;
 use miette::Diagnostic;
-use std::{env, future::Future, sync::OnceLock};
+use std::{collections::HashMap, env, future::Future, sync::OnceLock};
 use uuid::Uuid;
 
 static SECONDARY_DATASET_ID: OnceLock<Uuid> = OnceLock::new();
@@ -91,79 +88,89 @@ pub async fn delete_table(client: &Client, table_name: &str) {
 pub async fn create_table(client: &Client, table_name: &str) {
     delete_table(client, table_name).await;
 
-    client
-        .create_table()
-        .table_name(table_name)
-        .attribute_definitions(
-            AttributeDefinition::builder()
-                .attribute_name("pk")
-                .attribute_type(ScalarAttributeType::S)
-                .build()
-                .expect("Failed to build attribute definition"),
-        )
-        .attribute_definitions(
-            AttributeDefinition::builder()
-                .attribute_name("sk")
-                .attribute_type(ScalarAttributeType::S)
-                .build()
-                .expect("Failed to build attribute definition"),
-        )
-        .attribute_definitions(
-            AttributeDefinition::builder()
-                .attribute_name("term")
-                .attribute_type(ScalarAttributeType::B)
-                .build()
-                .expect("Failed to build attribute definition"),
-        )
-        .key_schema(
-            KeySchemaElement::builder()
-                .attribute_name("pk")
-                .key_type(KeyType::Hash)
-                .build()
-                .expect("Failed to build key schema element"),
-        )
-        .key_schema(
-            KeySchemaElement::builder()
-                .attribute_name("sk")
-                .key_type(KeyType::Range)
-                .build()
-                .expect("Failed to build key schema element"),
-        )
-        .provisioned_throughput(
-            ProvisionedThroughput::builder()
-                .read_capacity_units(5)
-                .write_capacity_units(5)
-                .build()
-                .expect("Failed to build provisioned throughput"),
-        )
-        .global_secondary_indexes(
-            GlobalSecondaryIndex::builder()
-                .index_name("TermIndex")
-                .key_schema(
-                    KeySchemaElement::builder()
-                        .attribute_name("term")
-                        .key_type(KeyType::Hash)
-                        .build()
-                        .expect("Failed to build key schema element"),
-                )
-                .projection(
-                    Projection::builder()
-                        .projection_type(ProjectionType::All)
-                        .build(),
-                )
-                .provisioned_throughput(
-                    ProvisionedThroughput::builder()
-                        .read_capacity_units(5)
-                        .write_capacity_units(5)
-                        .build()
-                        .expect("Failed to build provisioned throughput"),
-                )
-                .build()
-                .expect("Failed to build index"),
-        )
-        .send()
+    EncryptedTable::create_table(
+        client,
+        table_name,
+        CreateTableOptions::new().provisioned(5, 5),
+    )
+    .await
+    .expect("Failed to create table");
+}
+
+#[allow(dead_code)]
+pub type Item = HashMap<String, AttributeValue>;
+
+/// A user with plaintext attributes, for tests that change the items of a record.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+pub struct User {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    pub email: String,
+    #[cipherstash(query = "exact")]
+    pub name: String,
+    #[cipherstash(plaintext)]
+    pub role: String,
+    #[cipherstash(plaintext)]
+    pub logins: i64,
+}
+
+/// Reads the items of a [`User`] but rejects items without a MAC, like a type whose records have
+/// all been written again since MACs were added.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "user", record_mac = "required")]
+pub struct MacRequiredUser {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    pub email: String,
+    #[cipherstash(query = "exact")]
+    pub name: String,
+    #[cipherstash(plaintext)]
+    pub role: String,
+    #[cipherstash(plaintext)]
+    pub logins: i64,
+}
+
+#[allow(dead_code)]
+pub fn user(email: &str) -> User {
+    User {
+        email: email.to_string(),
+        name: "Alice".to_string(),
+        role: "member".to_string(),
+        logins: 3,
+    }
+}
+
+/// Encrypt a record and return its root item and its index term items.
+#[allow(dead_code)]
+pub async fn encrypt_items<T>(table: &EncryptedTable<Headless>, record: T) -> (Item, Vec<Item>)
+where
+    T: Searchable + Identifiable,
+{
+    let record = PreparedRecord::prepare_record(record).expect("failed to prepare record");
+
+    let (roots, terms): (Vec<_>, Vec<_>) = table
+        .create_put_patch(record, None, |_, _| true)
         .await
-        .expect("Failed to create table");
+        .expect("failed to encrypt")
+        .put_records
+        .into_iter()
+        .partition(|item| !item.contains_key("term"));
+
+    (
+        roots.into_iter().next().expect("expected a root item"),
+        terms,
+    )
+}
+
+/// Encrypt a record and return its root item, without its index term items.
+#[allow(dead_code)]
+pub async fn encrypt_root<T>(table: &EncryptedTable<Headless>, record: T) -> Item
+where
+    T: Searchable + Identifiable,
+{
+    encrypt_items(table, record).await.0
 }
 
 #[allow(dead_code)]
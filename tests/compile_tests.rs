@@ -20,12 +20,14 @@ macro_rules! run_tests {
 
 run_tests! {
     fail => {
+        "./ui/composite-key-missing-field.rs",
         "./ui/compound-index-missing-config.rs",
         "./ui/compound-index-missing-field.rs",
         "./ui/compound-index-too-many-fields.rs",
         "./ui/compound-index-unsupported.rs",
         "./ui/index-unsupported.rs",
         "./ui/invalid-field-name.rs",
+        "./ui/legacy-protected-without-upcast.rs",
         "./ui/no-multi-same-index-per-field.rs",
         "./ui/pk-field-no-partition.rs",
         "./ui/pk-field-wrong-partition.rs",
@@ -0,0 +1,150 @@
+use cipherstash_dynamodb::{
+    traits::{KeyPart, PrimaryKey},
+    Decryptable, Encryptable, Identifiable, Pk, PkSk, Searchable,
+};
+use common::{check_eq, check_none, fail_not_found, with_encrypted_table};
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(sort_key_prefix = "invoice", sort_key = "year#month#number")]
+struct Invoice {
+    #[partition_key]
+    account: String,
+    #[cipherstash(plaintext)]
+    year: i32,
+    #[cipherstash(plaintext)]
+    month: i16,
+    #[cipherstash(plaintext)]
+    number: String,
+    #[cipherstash(query = "exact")]
+    status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(
+    typed_keys,
+    sort_key_prefix = "entry",
+    partition_key = "tenant#ledger",
+    sort_key = "year#month"
+)]
+struct Entry {
+    tenant: String,
+    ledger: String,
+    #[cipherstash(plaintext)]
+    year: i32,
+    #[cipherstash(plaintext)]
+    month: i16,
+    amount: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(partition_key = "tenant#ledger")]
+struct Ledger {
+    tenant: String,
+    ledger: String,
+}
+
+fn invoice(year: i32, month: i16, number: &str) -> Invoice {
+    Invoice {
+        account: "acme".to_string(),
+        year,
+        month,
+        number: number.to_string(),
+        status: "paid".to_string(),
+    }
+}
+
+fn entry() -> Entry {
+    Entry {
+        tenant: "acme".to_string(),
+        ledger: "sales".to_string(),
+        year: 2024,
+        month: 3,
+        amount: 100,
+    }
+}
+
+#[test]
+fn test_composite_sort_key() {
+    let PkSk(pk, sk) = invoice(2024, 3, "INV-1").get_primary_key();
+    assert_eq!(pk, "acme");
+    assert_eq!(sk, "2024#3#INV-1");
+
+    let parts = invoice(2024, 3, "INV-1")
+        .get_primary_key()
+        .into_parts(&Invoice::type_name(), Invoice::sort_key_prefix().as_deref());
+    assert_eq!(parts.sk, "invoice#2024#3#INV-1");
+
+    // A `#` in a field is escaped so it can't be mistaken for a separator
+    let PkSk(_, sk) = invoice(2024, 3, "INV#1").get_primary_key();
+    assert_eq!(sk, "2024#3#INV%231");
+}
+
+#[test]
+fn test_composite_keys_with_separators_are_distinct() {
+    let ledger = |tenant: &str, ledger: &str| Ledger {
+        tenant: tenant.to_string(),
+        ledger: ledger.to_string(),
+    };
+
+    let Pk(left) = ledger("a#b", "c").get_primary_key();
+    let Pk(right) = ledger("a", "b#c").get_primary_key();
+
+    assert_ne!(left, right);
+    assert_eq!(
+        <(String, String)>::from_key_part(&left).unwrap(),
+        ("a#b".to_string(), "c".to_string())
+    );
+}
+
+#[test]
+fn test_typed_composite_keys() {
+    let key = entry().get_primary_key();
+    let parts = key.into_parts(&Entry::type_name(), Entry::sort_key_prefix().as_deref());
+
+    assert_eq!(parts.pk, "acme#sales");
+    assert_eq!(
+        parts.sk,
+        format!("entry#{}#{}", 2024i32.to_key_part(), 3i16.to_key_part())
+    );
+
+    let PkSk((tenant, ledger), (year, month)) =
+        <Entry as Identifiable>::PrimaryKey::from_parts(&parts, Some("entry")).unwrap();
+    assert_eq!(tenant, "acme");
+    assert_eq!(ledger, "sales");
+    assert_eq!((year, month), (2024, 3));
+
+    // Keys without a sort key use a tuple for a composite partition key too
+    let Pk((tenant, ledger)) = Pk::<(String, String)>::from_parts(&parts, None).unwrap();
+    assert_eq!((tenant, ledger), ("acme".to_string(), "sales".to_string()));
+}
+
+#[tokio::test]
+async fn test_round_trip_composite_keys() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("composite-keys", |table| async move {
+        table.put(invoice(2024, 3, "INV-1")).await?;
+        table.put(invoice(2024, 4, "INV-2")).await?;
+        table.put(entry()).await?;
+
+        // The fields of the key are read back from their own attributes
+        let found: Invoice = table
+            .get(("acme", "2024#3#INV-1"))
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found, invoice(2024, 3, "INV-1"))?;
+
+        let found: Entry = table
+            .get((("acme".to_string(), "sales".to_string()), (2024i32, 3i16)))
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found, entry())?;
+
+        table.delete::<Invoice>(("acme", "2024#3#INV-1")).await?;
+        check_none(table.get::<Invoice>(("acme", "2024#3#INV-1")).await?)?;
+
+        let found = table.query::<Invoice>().eq("status", "paid").send().await?;
+        check_eq(found, vec![invoice(2024, 4, "INV-2")])
+    })
+    .await
+}
@@ -0,0 +1,200 @@
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, KeySchemaElement, KeyType, ProjectionType, ProvisionedThroughput,
+    ScalarAttributeType,
+};
+use cipherstash_dynamodb::{
+    encrypted_table::{CreateTableOptions, TableLayout, VerifyOptions},
+    errors::InitError,
+    Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable,
+};
+use uuid::Uuid;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+struct User {
+    #[partition_key]
+    email: String,
+
+    #[cipherstash(query = "exact")]
+    name: String,
+}
+
+async fn client() -> aws_sdk_dynamodb::Client {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    aws_sdk_dynamodb::Client::new(&config)
+}
+
+#[tokio::test]
+async fn test_create_on_demand_table() -> Result<(), Box<dyn std::error::Error>> {
+    let client = client().await;
+    let table_name = format!("create-on-demand-{}", Uuid::new_v4());
+
+    EncryptedTable::create_table(&client, &table_name, CreateTableOptions::new()).await?;
+
+    let result = EncryptedTable::init(client.clone(), &table_name).await;
+    common::delete_table(&client, &table_name).await;
+
+    assert!(
+        result.is_ok(),
+        "expected init to succeed: {:?}",
+        result.err()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_init_fails_without_term_index() -> Result<(), Box<dyn std::error::Error>> {
+    let client = client().await;
+    let table_name = format!("create-missing-index-{}", Uuid::new_v4());
+
+    client
+        .create_table()
+        .table_name(&table_name)
+        .attribute_definitions(
+            AttributeDefinition::builder()
+                .attribute_name("pk")
+                .attribute_type(ScalarAttributeType::S)
+                .build()?,
+        )
+        .attribute_definitions(
+            AttributeDefinition::builder()
+                .attribute_name("sk")
+                .attribute_type(ScalarAttributeType::S)
+                .build()?,
+        )
+        .key_schema(
+            KeySchemaElement::builder()
+                .attribute_name("pk")
+                .key_type(KeyType::Hash)
+                .build()?,
+        )
+        .key_schema(
+            KeySchemaElement::builder()
+                .attribute_name("sk")
+                .key_type(KeyType::Range)
+                .build()?,
+        )
+        .provisioned_throughput(
+            ProvisionedThroughput::builder()
+                .read_capacity_units(5)
+                .write_capacity_units(5)
+                .build()?,
+        )
+        .send()
+        .await?;
+
+    let result = EncryptedTable::init(client.clone(), &table_name).await;
+    common::delete_table(&client, &table_name).await;
+
+    assert!(
+        matches!(result, Err(InitError::InvalidTable { .. })),
+        "expected InvalidTable error"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_custom_layout_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let client = client().await;
+    let table_name = format!("create-custom-layout-{}", Uuid::new_v4());
+    let layout = TableLayout::new("PK", "SK", "GSI1PK", "GSI1");
+
+    EncryptedTable::create_table(
+        &client,
+        &table_name,
+        CreateTableOptions::new().layout(layout.clone()),
+    )
+    .await?;
+
+    let result = async {
+        let table = EncryptedTable::init_with_layout(client.clone(), &table_name, layout).await?;
+
+        let user = User {
+            email: "dan@coderdan.co".to_string(),
+            name: "Dan Draper".to_string(),
+        };
+
+        table.put(user.clone()).await?;
+
+        let found: Option<User> = table.get("dan@coderdan.co").await?;
+        let queried: Vec<User> = table.query().eq("name", "Dan Draper").send().await?;
+
+        Ok::<_, Box<dyn std::error::Error>>((user, found, queried))
+    }
+    .await;
+
+    common::delete_table(&client, &table_name).await;
+
+    let (user, found, queried) = result?;
+
+    assert_eq!(found, Some(user.clone()));
+    assert_eq!(queried, vec![user]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_init_with_default_layout_fails_on_custom_table(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let client = client().await;
+    let table_name = format!("create-layout-mismatch-{}", Uuid::new_v4());
+
+    EncryptedTable::create_table(
+        &client,
+        &table_name,
+        CreateTableOptions::new().layout(TableLayout::new("PK", "SK", "GSI1PK", "GSI1")),
+    )
+    .await?;
+
+    let result = EncryptedTable::init(client.clone(), &table_name).await;
+    common::delete_table(&client, &table_name).await;
+
+    assert!(
+        matches!(result, Err(InitError::InvalidTable { .. })),
+        "expected InvalidTable error"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_init_fails_on_partial_projection() -> Result<(), Box<dyn std::error::Error>> {
+    let client = client().await;
+    let table_name = format!("create-keys-only-{}", Uuid::new_v4());
+
+    EncryptedTable::create_table(
+        &client,
+        &table_name,
+        CreateTableOptions::new().projection_type(ProjectionType::KeysOnly),
+    )
+    .await?;
+
+    let result = EncryptedTable::init(client.clone(), &table_name).await;
+    let allowed = EncryptedTable::init_with_verify_options(
+        client.clone(),
+        &table_name,
+        TableLayout::default(),
+        VerifyOptions::new().allow_partial_projection(),
+    )
+    .await;
+    common::delete_table(&client, &table_name).await;
+
+    assert!(
+        matches!(result, Err(InitError::InvalidTable { .. })),
+        "expected InvalidTable error"
+    );
+    assert!(
+        allowed.is_ok(),
+        "expected init to succeed: {:?}",
+        allowed.err()
+    );
+
+    Ok(())
+}
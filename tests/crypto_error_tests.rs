@@ -0,0 +1,112 @@
+use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+use cipherstash_dynamodb::{
+    crypto::SealError, encrypted_table::Headless, errors::DecryptError, Decryptable, Encryptable,
+    EncryptedTable, Identifiable, Searchable,
+};
+use common::{encrypt_root, Item};
+use miette::Diagnostic;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(sort_key_prefix = "user")]
+struct User {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    email: String,
+    name: String,
+    nickname: String,
+}
+
+/// Encrypt a user and return its root item without the MAC, so that the encrypted attributes
+/// are checked on their own.
+async fn encrypt(table: &EncryptedTable<Headless>) -> Item {
+    let user = User {
+        email: "alice@example.com".to_string(),
+        name: "Alice".to_string(),
+        nickname: "Al".to_string(),
+    };
+
+    let mut item = encrypt_root(table, user).await;
+    item.remove("__mac");
+    item
+}
+
+async fn decrypt_error(table: &EncryptedTable<Headless>, item: Item) -> SealError {
+    match table.decrypt_all::<User>(vec![item]).await {
+        Err(DecryptError::SealError(error)) => error,
+        other => panic!("expected a SealError, got {other:?}"),
+    }
+}
+
+fn code(error: &SealError) -> String {
+    error
+        .code()
+        .map(|code| code.to_string())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_descriptor_mismatch() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let mut item = encrypt(&table).await;
+    let name = item["name"].clone();
+    item.insert("nickname".to_string(), name);
+
+    let error = decrypt_error(&table, item).await;
+    assert!(matches!(
+        &error,
+        SealError::DescriptorMismatch { expected, found }
+            if expected == "user/nickname" && found == "user/name"
+    ));
+    assert_eq!(
+        code(&error),
+        "cipherstash_dynamodb::tampering::descriptor_mismatch"
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_ciphertext() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let mut item = encrypt(&table).await;
+    item.insert(
+        "name".to_string(),
+        AttributeValue::B(Blob::new(b"not a ciphertext".to_vec())),
+    );
+
+    let error = decrypt_error(&table, item).await;
+    assert!(
+        matches!(&error, SealError::MalformedCiphertext(attribute) if attribute == "user/name")
+    );
+    assert_eq!(
+        code(&error),
+        "cipherstash_dynamodb::storage::malformed_ciphertext"
+    );
+}
+
+#[tokio::test]
+async fn test_unsupported_stored_type() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let mut item = encrypt(&table).await;
+    item.insert("name".to_string(), AttributeValue::S("Alice".to_string()));
+
+    let error = decrypt_error(&table, item).await;
+    assert!(matches!(
+        &error,
+        SealError::UnsupportedStoredType { attribute, found }
+            if attribute == "user/name" && *found == "a string"
+    ));
+    assert_eq!(
+        code(&error),
+        "cipherstash_dynamodb::storage::unsupported_stored_type"
+    );
+}
@@ -0,0 +1,111 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use common::{check_eq, check_none, secondary_dataset_id, with_encrypted_table};
+use std::time::Duration;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+struct User {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "exact")]
+    name: String,
+}
+
+fn dan() -> User {
+    User {
+        email: "dan@coderdan.co".to_string(),
+        name: "Dan Draper".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_dataset_handle() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("dataset-handle", |table| async move {
+        let dataset_id = secondary_dataset_id();
+        let dataset = table.dataset(dataset_id).await?;
+        check_eq(dataset.id(), dataset_id)?;
+
+        dataset.put(dan()).await?;
+
+        let found: Option<User> = dataset.get("dan@coderdan.co").await?;
+        check_eq(found, Some(dan()))?;
+
+        let found: Vec<User> = dataset.query().eq("name", "Dan Draper").send().await?;
+        check_eq(found, vec![dan()])?;
+
+        // Records written with the handle are in the dataset, not the default one
+        let found: Option<User> = table.get_via("dan@coderdan.co", dataset_id).await?;
+        check_eq(found, Some(dan()))?;
+
+        let found: Option<User> = table.get("dan@coderdan.co").await?;
+        check_none(found)?;
+
+        dataset.delete::<User>("dan@coderdan.co").await?;
+
+        let found: Option<User> = dataset.get("dan@coderdan.co").await?;
+        check_none(found)?;
+
+        let found: Vec<User> = dataset.query().eq("name", "Dan Draper").send().await?;
+        check_eq(found, Vec::<User>::new())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_invalidate_scoped_cipher() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("dataset-invalidate", |table| async move {
+        let dataset_id = secondary_dataset_id();
+        table.put_via(dan(), dataset_id).await?;
+
+        table.invalidate_scoped_cipher(Some(dataset_id));
+
+        let found: Option<User> = table.get_via("dan@coderdan.co", dataset_id).await?;
+        check_eq(found, Some(dan()))?;
+
+        table.clear_scoped_ciphers();
+
+        let found: Vec<User> = table
+            .query()
+            .via(dataset_id)
+            .eq("name", "Dan Draper")
+            .send()
+            .await?;
+        check_eq(found, vec![dan()])
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_dataset_handle_after_invalidate() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("dataset-handle-invalidate", |table| async move {
+        let dataset_id = secondary_dataset_id();
+        let dataset = table.dataset(dataset_id).await?;
+        dataset.put(dan()).await?;
+
+        // The handle doesn't keep the cipher, so it is created again from the table
+        table.invalidate_scoped_cipher(Some(dataset_id));
+
+        let found: Option<User> = dataset.get("dan@coderdan.co").await?;
+        check_eq(found, Some(dan()))?;
+
+        table.clear_scoped_ciphers();
+
+        let found: Vec<User> = dataset.query().eq("name", "Dan Draper").send().await?;
+        check_eq(found, vec![dan()])
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_scoped_cipher_cache_can_be_disabled() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("dataset-no-cache", |table| async move {
+        let table = table.with_scoped_cipher_ttl(Duration::ZERO);
+
+        table.put(dan()).await?;
+
+        let found: Option<User> = table.get("dan@coderdan.co").await?;
+        check_eq(found, Some(dan()))
+    })
+    .await
+}
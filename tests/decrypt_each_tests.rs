@@ -0,0 +1,108 @@
+use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+use cipherstash_dynamodb::{
+    crypto::{SealError, UnsealSpec},
+    encrypted_table::Headless,
+    errors::{DecryptError, ReadConversionError},
+    EncryptedTable,
+};
+use common::{encrypt_root, user, Item, User};
+
+mod common;
+
+/// Encrypt a record and change a plaintext attribute of its root item.
+async fn encrypt_tampered(table: &EncryptedTable<Headless>, record: User) -> Item {
+    let mut item = encrypt_root(table, record).await;
+    item.insert("role".to_string(), AttributeValue::S("admin".to_string()));
+    item
+}
+
+#[tokio::test]
+async fn test_bad_items_dont_fail_the_batch() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let alice = encrypt_root(&table, user("alice@example.com")).await;
+
+    let tampered = encrypt_tampered(&table, user("bob@example.com")).await;
+
+    // Without a MAC the item reaches decryption, as the type doesn't require one
+    let mut malformed = encrypt_root(&table, user("carol@example.com")).await;
+    malformed.remove("__mac");
+    malformed.insert(
+        "name".to_string(),
+        AttributeValue::B(Blob::new(b"not a ciphertext".to_vec())),
+    );
+
+    let mut keyless = encrypt_root(&table, user("dave@example.com")).await;
+    keyless.remove("sk");
+
+    let results = table
+        .decrypt_each::<User>(vec![alice, tampered, malformed, keyless])
+        .await
+        .expect("failed to decrypt");
+
+    let keys = results
+        .iter()
+        .map(|(key, _)| key.as_ref().map(|key| key.pk.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        [
+            Some("alice@example.com"),
+            Some("bob@example.com"),
+            Some("carol@example.com"),
+            None
+        ]
+    );
+
+    assert_eq!(
+        results[0].1.as_ref().expect("failed to decrypt alice"),
+        &user("alice@example.com")
+    );
+    assert!(matches!(
+        results[1].1,
+        Err(DecryptError::SealError(SealError::PlaintextTampered))
+    ));
+    assert!(matches!(
+        results[2].1,
+        Err(DecryptError::SealError(SealError::MalformedCiphertext(_)))
+    ));
+    assert!(matches!(
+        results[3].1,
+        Err(DecryptError::SealError(SealError::ReadConversionError(
+            ReadConversionError::NoSuchAttribute(_)
+        )))
+    ));
+
+    // The strict variant fails the whole batch
+    assert!(table
+        .decrypt_all::<User>(vec![
+            encrypt_root(&table, user("alice@example.com")).await,
+            encrypt_tampered(&table, user("bob@example.com")).await,
+        ])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_unseal_each() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let alice = encrypt_root(&table, user("alice@example.com")).await;
+    let tampered = encrypt_tampered(&table, user("bob@example.com")).await;
+
+    let results = table
+        .unseal_each(
+            UnsealSpec::new_for_decryptable::<User>(),
+            vec![alice, tampered],
+        )
+        .await
+        .expect("failed to unseal");
+
+    assert_eq!(results.len(), 2);
+    assert!(results[0].1.is_ok());
+    assert!(matches!(results[1].1, Err(SealError::PlaintextTampered)));
+}
@@ -0,0 +1,218 @@
+use cipherstash_dynamodb::{
+    encrypted_table::{DynamicRecord, DynamicSchema},
+    traits::{KeyBinding, UnknownAttributes},
+    Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable, SingleIndex,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(sort_key_prefix = "user")]
+struct User {
+    #[partition_key]
+    #[cipherstash(query = "exact", compound = "email#name")]
+    email: String,
+
+    #[cipherstash(query = "prefix", compound = "email#name")]
+    #[cipherstash(query = "prefix")]
+    name: String,
+
+    #[cipherstash(plaintext)]
+    tag: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(version = 3, key_binding = "required", deny_unknown_attributes)]
+struct Account {
+    #[partition_key]
+    id: String,
+
+    #[cipherstash(query = "exact")]
+    owner: String,
+}
+
+/// A runtime description of the same type as [`User`].
+fn user_schema() -> Arc<DynamicSchema> {
+    Arc::new(
+        DynamicSchema::new("user", "email")
+            .query("name", SingleIndex::Prefix)
+            .compound_query(("email", SingleIndex::Exact), ("name", SingleIndex::Prefix))
+            .plaintext("tag"),
+    )
+}
+
+fn dynamic_user(schema: &Arc<DynamicSchema>, email: &str, name: &str, tag: &str) -> DynamicRecord {
+    let mut record = DynamicRecord::new(schema.clone());
+    record.add_protected("email", email);
+    record.add_protected("name", name);
+    record.add_plaintext("tag", tag);
+    record
+}
+
+#[tokio::test]
+async fn test_dynamic_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = format!("dynamic-records-{}", Uuid::new_v4());
+
+    common::create_table(&client, &table_name).await;
+
+    let result = async {
+        let table = EncryptedTable::init(client.clone(), &table_name).await?;
+        let schema = user_schema();
+
+        let dan = dynamic_user(&schema, "dan@coderdan.co", "Dan Draper", "admin");
+        let ada = dynamic_user(&schema, "ada@example.com", "Ada Lovelace", "user");
+
+        table.put_dynamic(dan.clone()).await?;
+        table.put_dynamic(ada.clone()).await?;
+
+        assert_eq!(
+            table.get_dynamic(&schema, "dan@coderdan.co").await?,
+            Some(dan.clone())
+        );
+
+        let results = table
+            .query_dynamic(&schema)
+            .starts_with("name", "Ada")
+            .send()
+            .await?;
+
+        assert_eq!(results, vec![ada.clone()]);
+
+        let results = table
+            .query_dynamic(&schema)
+            .eq("email", "dan@coderdan.co")
+            .starts_with("name", "Dan")
+            .send()
+            .await?;
+
+        assert_eq!(results, vec![dan.clone()]);
+
+        // Records written from a dynamic schema can be read by an equivalent derived type
+        assert_eq!(
+            table.get::<User>("dan@coderdan.co").await?,
+            Some(User {
+                email: "dan@coderdan.co".to_string(),
+                name: "Dan Draper".to_string(),
+                tag: "admin".to_string(),
+            })
+        );
+
+        table.delete_dynamic(&schema, "dan@coderdan.co").await?;
+
+        assert_eq!(table.get_dynamic(&schema, "dan@coderdan.co").await?, None);
+        assert!(table
+            .query_dynamic(&schema)
+            .starts_with("name", "Dan")
+            .send()
+            .await?
+            .is_empty());
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    common::delete_table(&client, &table_name).await;
+
+    result
+}
+
+#[tokio::test]
+async fn test_dynamic_missing_key() -> Result<(), Box<dyn std::error::Error>> {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = format!("dynamic-records-{}", Uuid::new_v4());
+
+    common::create_table(&client, &table_name).await;
+
+    let result = async {
+        let table = EncryptedTable::init(client.clone(), &table_name).await?;
+
+        let mut record = DynamicRecord::new(user_schema());
+        record.add_protected("name", "Dan Draper");
+
+        assert!(table.put_dynamic(record).await.is_err());
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    common::delete_table(&client, &table_name).await;
+
+    result
+}
+
+#[tokio::test]
+async fn test_dynamic_schema_settings() -> Result<(), Box<dyn std::error::Error>> {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = format!("dynamic-records-{}", Uuid::new_v4());
+
+    common::create_table(&client, &table_name).await;
+
+    let result = async {
+        let table = EncryptedTable::init(client.clone(), &table_name).await?;
+
+        // The settings of `Account` so that both can read each other's records
+        let schema = Arc::new(
+            DynamicSchema::new("account", "id")
+                .query("owner", SingleIndex::Exact)
+                .version(3)
+                .key_binding(KeyBinding::Required)
+                .unknown_attributes(UnknownAttributes::Deny),
+        );
+
+        let mut record = DynamicRecord::new(schema.clone());
+        record.add_protected("id", "acc-1");
+        record.add_protected("owner", "Dan Draper");
+
+        table.put_dynamic(record).await?;
+
+        assert_eq!(
+            table.get::<Account>("acc-1").await?,
+            Some(Account {
+                id: "acc-1".to_string(),
+                owner: "Dan Draper".to_string(),
+            })
+        );
+
+        table
+            .put(Account {
+                id: "acc-2".to_string(),
+                owner: "Ada Lovelace".to_string(),
+            })
+            .await?;
+
+        let record = table
+            .get_dynamic(&schema, "acc-2")
+            .await?
+            .expect("record should exist");
+
+        assert_eq!(
+            record.get_protected("owner"),
+            Some(&"Ada Lovelace".to_string().into())
+        );
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    common::delete_table(&client, &table_name).await;
+
+    result
+}
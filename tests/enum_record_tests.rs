@@ -0,0 +1,172 @@
+use cipherstash_dynamodb::{
+    crypto::{SealError, Unsealed},
+    traits::{Plaintext, ReadConversionError},
+    Decryptable, Encryptable, Identifiable, IndexType, Searchable, SingleIndex,
+};
+use common::{check_eq, fail_not_found, with_encrypted_table};
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(tag = "method")]
+enum Payment {
+    Card {
+        #[partition_key]
+        #[cipherstash(plaintext)]
+        id: String,
+        #[cipherstash(query = "exact")]
+        holder: String,
+        #[cipherstash(query = "prefix")]
+        number: String,
+    },
+    Bank {
+        #[partition_key]
+        #[cipherstash(plaintext)]
+        id: String,
+        #[cipherstash(query = "exact")]
+        holder: String,
+        account: String,
+        #[cipherstash(skip)]
+        verified: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable)]
+#[cipherstash(plaintext_tag)]
+enum Event {
+    Created {
+        #[partition_key]
+        id: String,
+        #[sort_key]
+        at: String,
+    },
+    Deleted {
+        #[partition_key]
+        id: String,
+        #[sort_key]
+        at: String,
+        reason: Option<String>,
+    },
+}
+
+fn card() -> Payment {
+    Payment::Card {
+        id: "payment-1".to_string(),
+        holder: "Dan".to_string(),
+        number: "4111111111111111".to_string(),
+    }
+}
+
+fn bank() -> Payment {
+    Payment::Bank {
+        id: "payment-2".to_string(),
+        holder: "Dan".to_string(),
+        account: "12345678".to_string(),
+        verified: false,
+    }
+}
+
+#[test]
+fn test_enum_attributes() {
+    assert_eq!(
+        <Payment as Encryptable>::protected_attributes().as_ref(),
+        &["account", "holder", "method", "number"]
+    );
+    assert_eq!(
+        <Payment as Encryptable>::plaintext_attributes().as_ref(),
+        &["id"]
+    );
+    assert_eq!(
+        <Event as Encryptable>::plaintext_attributes().as_ref(),
+        &["type"]
+    );
+    assert!(!Payment::is_pk_encrypted());
+
+    let index_names = Payment::protected_indexes()
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(index_names, ["holder", "number"]);
+}
+
+#[test]
+fn test_enum_primary_key() {
+    assert_eq!(card().get_primary_key().0, "payment-1");
+    assert_eq!(bank().get_primary_key().0, "payment-2");
+
+    let event = Event::Deleted {
+        id: "event-1".to_string(),
+        at: "2024-01-01".to_string(),
+        reason: None,
+    };
+
+    let key = event.get_primary_key();
+    assert_eq!((key.0.as_str(), key.1.as_str()), ("event-1", "2024-01-01"));
+}
+
+#[test]
+fn test_enum_indexes() {
+    let prefix = IndexType::Single(SingleIndex::Prefix);
+
+    assert!(card().attribute_for_index("number", prefix).is_some());
+    assert!(bank().attribute_for_index("number", prefix).is_none());
+    assert!(bank()
+        .attribute_for_index("holder", IndexType::Single(SingleIndex::Exact))
+        .is_some());
+}
+
+#[test]
+fn test_enum_unsealed_round_trip() -> Result<(), SealError> {
+    let mut unsealed = card().into_unsealed();
+    assert_eq!(
+        unsealed.take_protected("method"),
+        Some(Plaintext::from("Card"))
+    );
+
+    check_unsealed_round_trip(card())?;
+    check_unsealed_round_trip(bank())?;
+    check_unsealed_round_trip(Event::Created {
+        id: "event-1".to_string(),
+        at: "2024-01-01".to_string(),
+    })
+}
+
+fn check_unsealed_round_trip<T>(value: T) -> Result<(), SealError>
+where
+    T: Encryptable + Decryptable + Clone + PartialEq,
+{
+    assert_eq!(T::from_unsealed(value.clone().into_unsealed())?, value);
+    Ok(())
+}
+
+#[test]
+fn test_enum_unknown_variant() {
+    let mut unsealed = Unsealed::new();
+    unsealed.add_protected("method", "Cash");
+
+    assert!(matches!(
+        Payment::from_unsealed(unsealed),
+        Err(SealError::ReadConversionError(
+            ReadConversionError::UnknownVariant(tag)
+        )) if tag == "Cash"
+    ));
+}
+
+#[tokio::test]
+async fn test_enum_put_and_query() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("enum-record", |table| async move {
+        table.put(card()).await?;
+        table.put(bank()).await?;
+
+        let payment: Payment = table.get("payment-1").await?.ok_or(fail_not_found())?;
+        check_eq(payment, card())?;
+
+        let mut payments = table.query::<Payment>().eq("holder", "Dan").send().await?;
+
+        payments.sort_by_key(|payment| payment.get_primary_key().0);
+
+        check_eq(payments, vec![card(), bank()])
+    })
+    .await
+}
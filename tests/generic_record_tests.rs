@@ -0,0 +1,127 @@
+use cipherstash_dynamodb::{
+    crypto::SealError,
+    traits::{Plaintext, TryFromPlaintext},
+    Decryptable, Encryptable, Identifiable, IndexType, Nested, Searchable, SingleIndex,
+};
+use common::{check_eq, fail_not_found, with_encrypted_table};
+use std::fmt::Debug;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+struct Envelope<T> {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    id: String,
+    #[cipherstash(query = "exact")]
+    label: String,
+    payload: T,
+}
+
+trait Payload: Debug + Clone + Into<Plaintext> + TryFromPlaintext {}
+
+impl Payload for String {}
+impl Payload for i64 {}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(sort_key_prefix = "audit")]
+struct Audit<P: Payload, K = String>
+where
+    K: Clone,
+{
+    #[partition_key]
+    key: K,
+    #[cipherstash(query = "exact")]
+    actor: K,
+    #[cipherstash(nested)]
+    change: Change<P>,
+    #[cipherstash(skip)]
+    cached: Option<P>,
+}
+
+#[derive(Debug, Clone, PartialEq, Nested)]
+struct Change<P> {
+    #[cipherstash(query = "exact")]
+    before: P,
+    after: P,
+}
+
+fn envelope() -> Envelope<i64> {
+    Envelope {
+        id: "envelope-1".to_string(),
+        label: "count".to_string(),
+        payload: 42,
+    }
+}
+
+fn audit() -> Audit<String> {
+    Audit {
+        key: "audit-1".to_string(),
+        actor: "Dan".to_string(),
+        change: Change {
+            before: "draft".to_string(),
+            after: "published".to_string(),
+        },
+        cached: None,
+    }
+}
+
+#[test]
+fn test_generic_attributes() {
+    assert_eq!(
+        <Envelope<i64> as Encryptable>::protected_attributes().as_ref(),
+        &["label", "payload"]
+    );
+    assert_eq!(
+        <Audit<String> as Encryptable>::protected_attributes().as_ref(),
+        &["actor", "key", "change"]
+    );
+    assert_eq!(Audit::<i64>::type_name(), Audit::<String>::type_name());
+
+    let index_names = Audit::<String>::protected_indexes()
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(index_names, ["actor", "change.before"]);
+}
+
+#[test]
+fn test_generic_indexes() {
+    let exact = IndexType::Single(SingleIndex::Exact);
+
+    assert!(envelope().attribute_for_index("label", exact).is_some());
+    assert!(audit()
+        .attribute_for_index("change.before", exact)
+        .is_some());
+    assert!(Audit::<String>::index_by_name("change.before", exact).is_some());
+}
+
+#[test]
+fn test_generic_unsealed_round_trip() -> Result<(), SealError> {
+    assert_eq!(
+        Envelope::<i64>::from_unsealed(envelope().into_unsealed())?,
+        envelope()
+    );
+    assert_eq!(
+        Audit::<String>::from_unsealed(audit().into_unsealed())?,
+        audit()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generic_put_and_get() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("generic-record", |table| async move {
+        table.put(envelope()).await?;
+        table.put(audit()).await?;
+
+        let found: Envelope<i64> = table.get("envelope-1").await?.ok_or(fail_not_found())?;
+        check_eq(found, envelope())?;
+
+        let found: Audit<String> = table.get("audit-1").await?.ok_or(fail_not_found())?;
+        check_eq(found, audit())
+    })
+    .await
+}
@@ -0,0 +1,119 @@
+use cipherstash_dynamodb::{
+    crypto::Unsealed,
+    encrypted_table::DynamicSchema,
+    errors::SealError,
+    traits::{Plaintext, TryFromPlaintext},
+    Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable, SingleIndex,
+};
+use serde_json::json;
+use std::{collections::BTreeMap, sync::Arc};
+use uuid::Uuid;
+
+mod common;
+
+fn put_attrs(unsealed: &mut Unsealed, attrs: BTreeMap<String, String>) {
+    attrs.into_iter().for_each(|(k, v)| {
+        unsealed.add_protected_map_field("attrs", k, Plaintext::from(v));
+    })
+}
+
+fn get_attrs<T>(unsealed: &mut Unsealed) -> Result<T, SealError>
+where
+    T: FromIterator<(String, String)>,
+{
+    unsealed
+        .take_protected_map("attrs")
+        .ok_or(SealError::MissingAttribute("attrs".to_string()))?
+        .into_iter()
+        .map(|(k, v)| {
+            TryFromPlaintext::try_from_plaintext(v)
+                .map(|v| (k, v))
+                .map_err(SealError::from)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(sort_key_prefix = "user")]
+struct User {
+    #[partition_key]
+    #[cipherstash(query = "exact")]
+    email: String,
+
+    #[cipherstash(query = "prefix")]
+    name: String,
+
+    age: i32,
+
+    #[cipherstash(plaintext)]
+    tag: String,
+
+    #[cipherstash(encryptable_with = put_attrs, decryptable_with = get_attrs)]
+    attrs: BTreeMap<String, String>,
+}
+
+#[tokio::test]
+async fn test_get_and_query_json() -> Result<(), Box<dyn std::error::Error>> {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = format!("json-{}", Uuid::new_v4());
+
+    common::create_table(&client, &table_name).await;
+
+    let result = async {
+        let table = EncryptedTable::init(client.clone(), &table_name).await?;
+
+        table
+            .put(User {
+                email: "dan@coderdan.co".to_string(),
+                name: "Dan Draper".to_string(),
+                age: 42,
+                tag: "admin".to_string(),
+                attrs: BTreeMap::from([("team".to_string(), "core".to_string())]),
+            })
+            .await?;
+
+        // Records are read with a runtime description of the type instead of `User`
+        let schema = Arc::new(
+            DynamicSchema::new("user", "email")
+                .query("email", SingleIndex::Exact)
+                .query("name", SingleIndex::Prefix)
+                .protected("age")
+                .protected("attrs")
+                .plaintext("tag"),
+        );
+
+        let expected = json!({
+            "email": "dan@coderdan.co",
+            "name": "Dan Draper",
+            "age": 42,
+            "tag": "admin",
+            "attrs": { "team": "core" },
+        });
+
+        assert_eq!(
+            table.get_json(&schema, "dan@coderdan.co").await?,
+            Some(expected.clone())
+        );
+        assert_eq!(table.get_json(&schema, "ada@example.com").await?, None);
+
+        let results = table
+            .query_json(&schema)
+            .starts_with("name", "Dan")
+            .send()
+            .await?;
+
+        assert_eq!(results, vec![expected]);
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    common::delete_table(&client, &table_name).await;
+
+    result
+}
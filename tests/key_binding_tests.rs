@@ -0,0 +1,172 @@
+use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+use cipherstash_dynamodb::{
+    crypto::SealError, errors::DecryptError, Decryptable, Encryptable, EncryptedTable,
+    Identifiable, Searchable,
+};
+use common::encrypt_root;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(key_binding)]
+struct Account {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    email: String,
+    #[cipherstash(query = "exact")]
+    plan: String,
+    balance: i64,
+}
+
+/// Reads accounts that must be bound to their primary key.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "account", key_binding = "required")]
+struct RequiredAccount {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    email: String,
+    #[cipherstash(query = "exact")]
+    plan: String,
+    balance: i64,
+}
+
+/// Writes accounts without binding them to their primary key.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "account")]
+struct UnboundAccount {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    email: String,
+    #[cipherstash(query = "exact")]
+    plan: String,
+    balance: i64,
+}
+
+/// A record with a sort key, so that several records share a partition key.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(key_binding)]
+struct Transfer {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    account: String,
+    #[sort_key]
+    #[cipherstash(plaintext)]
+    id: String,
+    amount: i64,
+}
+
+fn transfer(id: &str, amount: i64) -> Transfer {
+    Transfer {
+        account: "alice@example.com".to_string(),
+        id: id.to_string(),
+        amount,
+    }
+}
+
+fn account(email: &str, balance: i64) -> Account {
+    Account {
+        email: email.to_string(),
+        plan: "pro".to_string(),
+        balance,
+    }
+}
+
+#[tokio::test]
+async fn test_bound_attributes_round_trip() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let item = encrypt_root(&table, account("alice@example.com", 100)).await;
+
+    let decrypted: Vec<Account> = table
+        .decrypt_all(vec![item.clone()])
+        .await
+        .expect("failed to decrypt");
+    assert_eq!(decrypted, [account("alice@example.com", 100)]);
+
+    // Bound attributes are accepted when binding is required
+    let decrypted: Vec<RequiredAccount> = table
+        .decrypt_all(vec![item])
+        .await
+        .expect("failed to decrypt");
+    assert_eq!(decrypted[0].balance, 100);
+}
+
+#[tokio::test]
+async fn test_swapped_attributes_are_rejected() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let alice = encrypt_root(&table, account("alice@example.com", 100)).await;
+    let mut mallory = encrypt_root(&table, account("mallory@example.com", 0)).await;
+
+    // Copy Alice's balance into Mallory's record, and remove the MAC so that only the binding is
+    // checked
+    mallory.insert("balance".to_string(), alice["balance"].clone());
+    mallory.remove("__mac");
+
+    assert!(matches!(
+        table.decrypt_all::<Account>(vec![mallory]).await,
+        Err(DecryptError::SealError(SealError::KeyBindingMismatch(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_unbound_attributes_are_rejected_when_required() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let item = encrypt_root(
+        &table,
+        UnboundAccount {
+            email: "alice@example.com".to_string(),
+            plan: "pro".to_string(),
+            balance: 100,
+        },
+    )
+    .await;
+
+    // Unbound attributes can still be read by types that don't require binding
+    let decrypted: Vec<Account> = table
+        .decrypt_all(vec![item.clone()])
+        .await
+        .expect("failed to decrypt");
+    assert_eq!(decrypted, [account("alice@example.com", 100)]);
+
+    assert!(table
+        .decrypt_all::<RequiredAccount>(vec![item])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_root_items_posing_as_term_items_are_rejected() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let large = encrypt_root(&table, transfer("1", 1_000_000)).await;
+    let small = encrypt_root(&table, transfer("2", 1)).await;
+
+    // Move the large transfer to the key of the small one, and claim that it is a term item of
+    // the large transfer so that its attributes would be checked against its original key
+    let mut moved = large.clone();
+    moved.insert("sk".to_string(), small["sk"].clone());
+    moved.insert("term".to_string(), AttributeValue::B(Blob::new(vec![0])));
+    moved.insert("__rsk".to_string(), large["sk"].clone());
+
+    assert!(matches!(
+        table.decrypt_all::<Transfer>(vec![moved.clone()]).await,
+        Err(DecryptError::SealError(SealError::PlaintextTampered))
+    ));
+
+    // Without a MAC the sort key of the root item isn't trusted
+    moved.remove("__mac");
+    assert!(matches!(
+        table.decrypt_all::<Transfer>(vec![moved]).await,
+        Err(DecryptError::SealError(SealError::KeyBindingMismatch(_)))
+    ));
+}
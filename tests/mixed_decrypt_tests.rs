@@ -0,0 +1,222 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use cipherstash_dynamodb::{
+    encrypted_table::MixedDecoder,
+    errors::{DecryptError, RegistryError},
+    Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable,
+};
+use common::secondary_dataset_id;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+struct User {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    account: String,
+
+    #[cipherstash(query = "exact")]
+    email: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(sort_key_prefix = "license")]
+struct License {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    account: String,
+
+    #[sort_key]
+    number: String,
+
+    #[cipherstash(query = "exact")]
+    holder: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Item {
+    User(User),
+    License(License),
+}
+
+async fn partition_items(
+    client: &aws_sdk_dynamodb::Client,
+    table_name: &str,
+    account: &str,
+) -> Vec<HashMap<String, AttributeValue>> {
+    client
+        .query()
+        .table_name(table_name)
+        .key_condition_expression("pk = :pk")
+        // Skip index term items which hold copies of the record attributes
+        .filter_expression("attribute_not_exists(term)")
+        .expression_attribute_values(":pk", AttributeValue::S(account.to_string()))
+        .send()
+        .await
+        .expect("failed to query partition")
+        .items
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn test_decrypt_mixed_partition() -> Result<(), Box<dyn std::error::Error>> {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = format!("mixed-decrypt-{}", Uuid::new_v4());
+
+    common::create_table(&client, &table_name).await;
+
+    let result = async {
+        let table = EncryptedTable::init(client.clone(), &table_name)
+            .await?
+            .register::<User>()?
+            .register::<License>()?;
+
+        let user = User {
+            account: "acme".to_string(),
+            email: "dan@coderdan.co".to_string(),
+        };
+
+        let licenses = vec![
+            License {
+                account: "acme".to_string(),
+                number: "L-1".to_string(),
+                holder: "Dan".to_string(),
+            },
+            License {
+                account: "acme".to_string(),
+                number: "L-2".to_string(),
+                holder: "Ada".to_string(),
+            },
+        ];
+
+        table.put(user.clone()).await?;
+
+        for license in licenses.iter() {
+            table.put(license.clone()).await?;
+        }
+
+        let items = partition_items(&client, &table_name, "acme").await;
+        assert_eq!(items.len(), 3);
+
+        let decoder = MixedDecoder::new()
+            .variant(Item::User)?
+            .variant(Item::License)?;
+
+        let records = table.decrypt_mixed(items.clone(), &decoder).await?;
+
+        assert_eq!(records.len(), 3);
+        assert!(records.contains(&Item::User(user)));
+
+        for license in licenses {
+            assert!(records.contains(&Item::License(license)));
+        }
+
+        // Records are returned in the order of the items
+        let mut reversed = records.clone();
+        reversed.reverse();
+
+        let reversed_items = items.iter().rev().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            table.decrypt_mixed(reversed_items, &decoder).await?,
+            reversed
+        );
+
+        // Items of types that weren't added to the decoder are an error unless skipped
+        let users_only = MixedDecoder::new().variant(Item::User)?;
+
+        assert!(matches!(
+            table.decrypt_mixed(items.clone(), &users_only).await,
+            Err(DecryptError::UnknownType(_))
+        ));
+
+        let users_only = users_only.skip_unknown();
+        assert_eq!(
+            table.decrypt_mixed(items.clone(), &users_only).await?.len(),
+            1
+        );
+
+        // Types added to the decoder must be registered with the table
+        let unregistered = EncryptedTable::init(client.clone(), &table_name).await?;
+
+        assert!(matches!(
+            unregistered.decrypt_mixed(items, &decoder).await,
+            Err(DecryptError::RegistryError(
+                RegistryError::UnregisteredType(_)
+            ))
+        ));
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    common::delete_table(&client, &table_name).await;
+
+    result
+}
+
+#[tokio::test]
+async fn test_decrypt_mixed_via_dataset() -> Result<(), Box<dyn std::error::Error>> {
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let client = aws_sdk_dynamodb::Client::new(&config);
+    let table_name = format!("mixed-decrypt-dataset-{}", Uuid::new_v4());
+
+    common::create_table(&client, &table_name).await;
+
+    let result = async {
+        let table = EncryptedTable::init(client.clone(), &table_name)
+            .await?
+            .register::<User>()?
+            .register::<License>()?;
+
+        let dataset_id = secondary_dataset_id();
+
+        let user = User {
+            account: "acme".to_string(),
+            email: "dan@coderdan.co".to_string(),
+        };
+
+        let license = License {
+            account: "acme".to_string(),
+            number: "L-1".to_string(),
+            holder: "Dan".to_string(),
+        };
+
+        table.put_via(user.clone(), dataset_id).await?;
+        table.put_via(license.clone(), dataset_id).await?;
+
+        let items = partition_items(&client, &table_name, "acme").await;
+        assert_eq!(items.len(), 2);
+
+        let decoder = MixedDecoder::new()
+            .variant(Item::User)?
+            .variant(Item::License)?;
+
+        let records = table
+            .decrypt_mixed_via(items.clone(), &decoder, dataset_id)
+            .await?;
+
+        assert_eq!(records.len(), 2);
+        assert!(records.contains(&Item::User(user)));
+        assert!(records.contains(&Item::License(license)));
+
+        // The MACs of the items don't match the key of the default dataset
+        assert!(table.decrypt_mixed(items, &decoder).await.is_err());
+
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    common::delete_table(&client, &table_name).await;
+
+    result
+}
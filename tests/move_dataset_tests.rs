@@ -0,0 +1,89 @@
+use cipherstash_dynamodb::{Decryptable, Encryptable, Identifiable, Searchable};
+use common::{check_eq, check_none, secondary_dataset_id, with_encrypted_table};
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+struct User {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "exact")]
+    name: String,
+}
+
+fn user(email: &str, name: &str) -> User {
+    User {
+        email: email.to_string(),
+        name: name.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_move_to_dataset() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("move-dataset", |table| async move {
+        let dan = user("dan@coderdan.co", "Dan Draper");
+        table.put(dan.clone()).await?;
+
+        let dataset_id = secondary_dataset_id();
+        let moved = table
+            .move_to_dataset::<User>("dan@coderdan.co", None, Some(dataset_id))
+            .await?;
+        check_eq(moved, true)?;
+
+        let found: Option<User> = table.get_via("dan@coderdan.co", dataset_id).await?;
+        check_eq(found, Some(dan.clone()))?;
+
+        let found: Vec<User> = table
+            .query()
+            .via(dataset_id)
+            .eq("name", "Dan Draper")
+            .send()
+            .await?;
+        check_eq(found, vec![dan])?;
+
+        // The old root and index term items are deleted
+        let found: Option<User> = table.get("dan@coderdan.co").await?;
+        check_none(found)?;
+
+        let found: Vec<User> = table.query().eq("name", "Dan Draper").send().await?;
+        check_eq(found, Vec::<User>::new())?;
+
+        // The record is no longer in the default dataset
+        let moved = table
+            .move_to_dataset::<User>("dan@coderdan.co", None, Some(dataset_id))
+            .await?;
+        check_eq(moved, false)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_move_all_to_dataset() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("move-all-dataset", |table| async move {
+        table.put(user("dan@coderdan.co", "Dan Draper")).await?;
+        table.put(user("jane@smith.org", "Jane Smith")).await?;
+
+        let dataset_id = secondary_dataset_id();
+        let moved = table
+            .move_all_to_dataset::<User>(
+                ["dan@coderdan.co", "jane@smith.org", "missing@example.com"],
+                None,
+                Some(dataset_id),
+            )
+            .await?;
+        check_eq(moved, 2)?;
+
+        let found: Option<User> = table.get_via("jane@smith.org", dataset_id).await?;
+        check_eq(found, Some(user("jane@smith.org", "Jane Smith")))?;
+
+        // Records can be moved back
+        let moved = table
+            .move_all_to_dataset::<User>(["jane@smith.org"], Some(dataset_id), None)
+            .await?;
+        check_eq(moved, 1)?;
+
+        let found: Option<User> = table.get("jane@smith.org").await?;
+        check_eq(found, Some(user("jane@smith.org", "Jane Smith")))
+    })
+    .await
+}
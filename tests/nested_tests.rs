@@ -4,9 +4,9 @@ use cipherstash_dynamodb::{
     crypto::Unsealed,
     errors::SealError,
     traits::{Plaintext, TryFromPlaintext},
-    Decryptable, Encryptable, EncryptedTable, Identifiable,
+    Decryptable, Encryptable, EncryptedTable, Identifiable, IndexType, Nested, Searchable,
+    SingleIndex,
 };
-use cipherstash_dynamodb_derive::Searchable;
 use miette::IntoDiagnostic;
 use std::collections::BTreeMap;
 
@@ -87,3 +87,129 @@ async fn test_round_trip() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[derive(Debug, Clone, PartialEq, Nested)]
+struct Geo {
+    lat: f64,
+    lng: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Nested)]
+struct Address {
+    #[cipherstash(query = "exact")]
+    city: String,
+    #[cipherstash(plaintext)]
+    postcode: String,
+    #[cipherstash(nested)]
+    geo: Geo,
+}
+
+#[derive(Debug, Clone, PartialEq, Nested)]
+struct Audit {
+    #[cipherstash(query = "prefix")]
+    created_by: String,
+    #[cipherstash(plaintext)]
+    created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Searchable, Encryptable, Decryptable, Identifiable)]
+struct Customer {
+    #[partition_key]
+    email: String,
+    #[cipherstash(nested)]
+    address: Address,
+    #[cipherstash(flatten)]
+    audit: Audit,
+}
+
+fn customer() -> Customer {
+    Customer {
+        email: "dan@coderdan.co".to_string(),
+        address: Address {
+            city: "Sydney".to_string(),
+            postcode: "2000".to_string(),
+            geo: Geo {
+                lat: -33.87,
+                lng: 151.21,
+            },
+        },
+        audit: Audit {
+            created_by: "admin".to_string(),
+            created_at: "2024-01-01".to_string(),
+        },
+    }
+}
+
+#[test]
+fn test_nested_attributes() {
+    assert_eq!(
+        <Customer as Encryptable>::protected_attributes().as_ref(),
+        &["email", "address", "created_by"]
+    );
+    assert_eq!(
+        <Customer as Encryptable>::plaintext_attributes().as_ref(),
+        &["address.postcode", "created_at"]
+    );
+
+    let index_names = Customer::protected_indexes()
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(index_names, ["address.city", "created_by"]);
+
+    let customer = customer();
+    let exact = IndexType::Single(SingleIndex::Exact);
+
+    assert!(customer
+        .attribute_for_index("address.city", exact)
+        .is_some());
+    assert!(customer.attribute_for_index("city", exact).is_none());
+    assert!(Customer::index_by_name("address.city", exact).is_some());
+    assert!(
+        Customer::index_by_name("created_by", IndexType::Single(SingleIndex::Prefix)).is_some()
+    );
+}
+
+#[test]
+fn test_nested_unsealed_paths() -> Result<(), SealError> {
+    let mut unsealed = customer().into_unsealed();
+
+    assert_eq!(
+        unsealed.take_protected_path("address.city"),
+        Some(Plaintext::from("Sydney"))
+    );
+    assert_eq!(
+        unsealed.take_protected_path("address.geo.lat"),
+        Some(Plaintext::from(-33.87))
+    );
+    assert_eq!(
+        unsealed.take_protected_path("created_by"),
+        Some(Plaintext::from("admin"))
+    );
+
+    let unsealed = customer().into_unsealed();
+    assert_eq!(Customer::from_unsealed(unsealed)?, customer());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nested_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    common::with_encrypted_table("nested-struct", |table| async move {
+        let record = customer();
+        table.put(record.clone()).await?;
+
+        let check = table.get::<Customer>("dan@coderdan.co").await?;
+        common::check_eq(check, Some(record.clone()))?;
+
+        let results = table
+            .query::<Customer>()
+            .eq("address.city", "Sydney")
+            .send()
+            .await?;
+
+        common::check_eq(results, vec![record])
+    })
+    .await
+}
@@ -0,0 +1,146 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use cipherstash_dynamodb::{
+    crypto::{SealError, UnsealSpec},
+    encrypted_table::PreparedRecord,
+    errors::DecryptError,
+    EncryptedTable,
+};
+use common::{
+    encrypt_items, encrypt_root, secondary_dataset_id, user, Item, MacRequiredUser, User,
+};
+
+mod common;
+
+fn is_tampered<T>(result: Result<T, DecryptError>) -> bool {
+    matches!(
+        result,
+        Err(DecryptError::SealError(SealError::PlaintextTampered))
+    )
+}
+
+#[tokio::test]
+async fn test_items_with_a_mac_round_trip() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let (root, terms) = encrypt_items(&table, user("alice@example.com")).await;
+    assert!(root.contains_key("__mac"));
+
+    let decrypted: Vec<User> = table
+        .decrypt_all(std::iter::once(root).chain(terms))
+        .await
+        .expect("failed to decrypt");
+
+    assert!(decrypted
+        .iter()
+        .all(|found| found == &user("alice@example.com")));
+}
+
+#[tokio::test]
+async fn test_changed_plaintext_attributes_are_rejected() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let (mut root, mut terms) = encrypt_items(&table, user("alice@example.com")).await;
+
+    root.insert("role".to_string(), AttributeValue::S("admin".to_string()));
+    assert!(is_tampered(table.decrypt_all::<User>(vec![root]).await));
+
+    // Term items are checked too as they are returned by queries
+    let mut term = terms.remove(0);
+    term.insert("logins".to_string(), AttributeValue::N("0".to_string()));
+    assert!(is_tampered(table.decrypt_all::<User>(vec![term]).await));
+}
+
+#[tokio::test]
+async fn test_items_moved_to_another_key_are_rejected() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let mut root = encrypt_root(&table, user("alice@example.com")).await;
+
+    root.insert(
+        "pk".to_string(),
+        AttributeValue::S("mallory@example.com".to_string()),
+    );
+    assert!(is_tampered(table.decrypt_all::<User>(vec![root]).await));
+}
+
+#[tokio::test]
+async fn test_items_without_a_mac_are_rejected_when_required() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    // Removing the MAC doesn't get around the check
+    let (mut root, mut terms) = encrypt_items(&table, user("alice@example.com")).await;
+    root.remove("__mac");
+    root.insert("role".to_string(), AttributeValue::S("admin".to_string()));
+    assert!(is_tampered(
+        table.decrypt_all::<MacRequiredUser>(vec![root]).await
+    ));
+
+    let mut term = terms.remove(0);
+    term.remove("__mac");
+    assert!(is_tampered(
+        table.decrypt_all::<MacRequiredUser>(vec![term]).await
+    ));
+}
+
+#[tokio::test]
+async fn test_items_without_a_mac_are_accepted_by_default() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    // Records written before MACs were added don't have one
+    let mut root = encrypt_root(&table, user("alice@example.com")).await;
+    root.remove("__mac");
+
+    let decrypted: Vec<User> = table
+        .decrypt_all(vec![root])
+        .await
+        .expect("failed to decrypt");
+    assert_eq!(decrypted, [user("alice@example.com")]);
+}
+
+#[tokio::test]
+async fn test_items_from_another_dataset_are_checked_with_its_key() {
+    let table = EncryptedTable::init_headless()
+        .await
+        .expect("failed to init table");
+
+    let dataset_id = secondary_dataset_id();
+    let record = PreparedRecord::prepare_record(user("alice@example.com"))
+        .expect("failed to prepare record");
+    let items: Vec<Item> = table
+        .create_put_patch(record, Some(dataset_id), |_, _| true)
+        .await
+        .expect("failed to encrypt")
+        .put_records;
+
+    // The MAC was computed with the key of the dataset, so it doesn't match the default key
+    assert!(is_tampered(table.decrypt_all::<User>(items.clone()).await));
+
+    let spec = || UnsealSpec::new_for_decryptable::<User>();
+
+    table
+        .unseal_via(spec(), items[0].clone(), dataset_id)
+        .await
+        .expect("failed to unseal");
+
+    let unsealed = table
+        .unseal_all_via(spec(), items.clone(), dataset_id)
+        .await
+        .expect("failed to unseal");
+    assert_eq!(unsealed.len(), items.len());
+
+    let results = table
+        .unseal_each_via(spec(), items, dataset_id)
+        .await
+        .expect("failed to unseal");
+    assert!(results.iter().all(|(_, result)| result.is_ok()));
+}
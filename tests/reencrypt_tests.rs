@@ -0,0 +1,241 @@
+use aws_sdk_dynamodb::Client;
+use cipherstash_dynamodb::{Decryptable, Encryptable, EncryptedTable, Identifiable, Searchable};
+use common::{
+    check_eq, check_err, check_none, create_table, delete_table, secondary_dataset_id,
+    with_encrypted_table,
+};
+use std::sync::{Arc, Mutex};
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+struct User {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "exact")]
+    name: String,
+}
+
+/// Reads the records of [`User`] but rejects items without a MAC.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "user", record_mac = "required")]
+struct MacRequiredUser {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "exact")]
+    name: String,
+}
+
+fn user(email: &str, name: &str) -> User {
+    User {
+        email: email.to_string(),
+        name: name.to_string(),
+    }
+}
+
+fn users() -> Vec<User> {
+    vec![
+        user("dan@coderdan.co", "Dan Draper"),
+        user("jane@smith.org", "Jane Smith"),
+        user("daniel@example.com", "Daniel Johnson"),
+    ]
+}
+
+async fn put_users(table: &EncryptedTable) -> miette::Result<()> {
+    for user in users() {
+        table.put(user).await?;
+    }
+
+    Ok(())
+}
+
+/// Remove the MAC of every item in the table, like records stored before MACs were added.
+async fn remove_macs(client: &Client, table_name: &str) {
+    let items = client
+        .scan()
+        .table_name(table_name)
+        .send()
+        .await
+        .expect("failed to scan table")
+        .items
+        .unwrap_or_default();
+
+    for item in items {
+        client
+            .update_item()
+            .table_name(table_name)
+            .set_key(Some(
+                item.into_iter()
+                    .filter(|(name, _)| name == "pk" || name == "sk")
+                    .collect(),
+            ))
+            .update_expression("REMOVE #mac")
+            .expression_attribute_names("#mac", "__mac")
+            .send()
+            .await
+            .expect("failed to remove MAC");
+    }
+}
+
+#[tokio::test]
+async fn test_reencrypt() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("reencrypt", |table| async move {
+        put_users(&table).await?;
+
+        let pages = Arc::new(Mutex::new(0));
+        let progress = table
+            .reencrypt::<User>()
+            .page_size(1)
+            .on_progress({
+                let pages = pages.clone();
+                move |_| *pages.lock().unwrap() += 1
+            })
+            .run()
+            .await?;
+
+        check_eq(progress.scanned, 3)?;
+        check_eq(progress.reencrypted, 3)?;
+        check_eq(progress.conflicts.len(), 0)?;
+        check_eq(progress.failures.len(), 0)?;
+        check_eq(progress.checkpoint, None)?;
+        // Each page reads one item, which may be an index term item
+        check_eq(*pages.lock().unwrap() > 3, true)?;
+
+        for user in users() {
+            let found: Option<User> = table.get(user.email.as_str()).await?;
+            check_eq(found, Some(user.clone()))?;
+
+            let found: Vec<User> = table.query().eq("name", user.name.as_str()).send().await?;
+            check_eq(found, vec![user])?;
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_reencrypt_resumes_from_checkpoint() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("reencrypt-resume", |table| async move {
+        put_users(&table).await?;
+
+        let checkpoints = Arc::new(Mutex::new(vec![]));
+        table
+            .reencrypt::<User>()
+            .page_size(1)
+            .on_progress({
+                let checkpoints = checkpoints.clone();
+                move |progress| {
+                    if let Some(checkpoint) = &progress.checkpoint {
+                        checkpoints
+                            .lock()
+                            .unwrap()
+                            .push((progress.reencrypted, checkpoint.clone()));
+                    }
+                }
+            })
+            .run()
+            .await?;
+
+        // Resume after the first record was re-encrypted
+        let (_, checkpoint) = checkpoints
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(reencrypted, _)| *reencrypted == 1)
+            .cloned()
+            .expect("expected a checkpoint after the first record");
+
+        let progress = table
+            .reencrypt::<User>()
+            .resume_from(checkpoint)
+            .run()
+            .await?;
+
+        check_eq(progress.reencrypted, 2)?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_reencrypt_into_another_dataset() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("reencrypt-dataset", |table| async move {
+        put_users(&table).await?;
+
+        let dataset_id = secondary_dataset_id();
+
+        // Small pages make the scan more likely to read the items the job has written
+        let progress = table
+            .reencrypt::<User>()
+            .to_dataset(dataset_id)
+            .page_size(1)
+            .run()
+            .await?;
+
+        check_eq(progress.reencrypted, 3)?;
+        check_eq(progress.scanned, 3)?;
+        check_eq(progress.failures.len(), 0)?;
+
+        // Records already in the target dataset are skipped when the job is run again
+        let progress = table
+            .reencrypt::<User>()
+            .to_dataset(dataset_id)
+            .run()
+            .await?;
+
+        check_eq(progress.scanned, 0)?;
+        check_eq(progress.failures.len(), 0)?;
+
+        for user in users() {
+            let found: Option<User> = table.get_via(user.email.as_str(), dataset_id).await?;
+            check_eq(found, Some(user.clone()))?;
+
+            let found: Option<User> = table.get(user.email.as_str()).await?;
+            check_none(found)?;
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_reencrypt_adds_macs_to_records_without_one() -> Result<(), Box<dyn std::error::Error>>
+{
+    let config = aws_config::from_env()
+        .endpoint_url("http://localhost:8000")
+        .load()
+        .await;
+
+    let table_name = format!("reencrypt-mac-{}", uuid::Uuid::new_v4());
+    let client = Client::new(&config);
+
+    create_table(&client, &table_name).await;
+    let table = EncryptedTable::init(client.clone(), &table_name).await?;
+
+    let result: miette::Result<()> = async {
+        put_users(&table).await?;
+        remove_macs(&client, &table_name).await;
+
+        check_err(table.get::<MacRequiredUser>("dan@coderdan.co").await)?;
+
+        // Records without a MAC are re-encrypted even when the type requires one
+        let progress = table.reencrypt::<MacRequiredUser>().run().await?;
+
+        check_eq(progress.reencrypted, 3)?;
+        check_eq(progress.failures.len(), 0)?;
+
+        for user in users() {
+            let found: Option<MacRequiredUser> = table.get(user.email.as_str()).await?;
+            check_eq(found.map(|found| found.name), Some(user.name))?;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    delete_table(&client, &table_name).await;
+    Ok(result?)
+}
@@ -0,0 +1,143 @@
+use cipherstash_dynamodb::{
+    crypto::SealError, traits::Plaintext, Decryptable, Encryptable, Identifiable, IndexType,
+    Nested, Searchable, SingleIndex,
+};
+use common::{check_eq, fail_not_found, with_encrypted_table};
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Nested)]
+struct Address {
+    #[cipherstash(rename = "town", query = "exact")]
+    city: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "customer")]
+struct Client {
+    #[partition_key]
+    #[cipherstash(rename = "email")]
+    email_address: String,
+    #[cipherstash(rename = "name", query = "prefix", compound = "name#tier")]
+    full_name: String,
+    #[cipherstash(rename = "tier", query = "exact", compound = "name#tier")]
+    #[cipherstash(plaintext)]
+    level: String,
+    #[cipherstash(nested, rename = "home")]
+    address: Address,
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable)]
+enum Contact {
+    Email {
+        #[partition_key]
+        #[cipherstash(rename = "id")]
+        contact_id: String,
+        #[cipherstash(rename = "value")]
+        address: String,
+    },
+    Phone {
+        #[partition_key]
+        #[cipherstash(rename = "id")]
+        contact_id: String,
+        #[cipherstash(rename = "value")]
+        number: String,
+    },
+}
+
+fn client() -> Client {
+    Client {
+        email_address: "dan@example.com".to_string(),
+        full_name: "Dan Draper".to_string(),
+        level: "gold".to_string(),
+        address: Address {
+            city: "Sydney".to_string(),
+        },
+    }
+}
+
+#[test]
+fn test_renamed_attributes() {
+    assert_eq!(Client::type_name(), "customer");
+    assert_eq!(Client::sort_key_prefix().as_deref(), Some("customer"));
+    assert_eq!(
+        <Client as Encryptable>::protected_attributes().as_ref(),
+        &["email", "name", "home"]
+    );
+    assert_eq!(
+        <Client as Encryptable>::plaintext_attributes().as_ref(),
+        &["tier"]
+    );
+
+    let index_names = Client::protected_indexes()
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+
+    assert_eq!(index_names, ["name#tier", "home.town"]);
+
+    assert!(client()
+        .attribute_for_index("home.town", IndexType::Single(SingleIndex::Exact))
+        .is_some());
+}
+
+#[test]
+fn test_renamed_unsealed_round_trip() -> Result<(), SealError> {
+    let mut unsealed = client().into_unsealed();
+    assert_eq!(
+        unsealed.take_protected("name"),
+        Some(Plaintext::from("Dan Draper"))
+    );
+    assert_eq!(
+        unsealed.take_protected_path("home.town"),
+        Some(Plaintext::from("Sydney"))
+    );
+
+    assert_eq!(Client::from_unsealed(client().into_unsealed())?, client());
+
+    let phone = Contact::Phone {
+        contact_id: "contact-1".to_string(),
+        number: "0400000000".to_string(),
+    };
+
+    assert_eq!(
+        <Contact as Encryptable>::protected_attributes().as_ref(),
+        &["id", "type", "value"]
+    );
+    assert_eq!(phone.get_primary_key().0, "contact-1");
+    assert_eq!(
+        Contact::from_unsealed(phone.clone().into_unsealed())?,
+        phone
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_renamed_put_and_query() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("renamed-record", |table| async move {
+        table.put(client()).await?;
+
+        let found: Client = table
+            .get("dan@example.com")
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found, client())?;
+
+        let found = table
+            .query::<Client>()
+            .starts_with("name", "Dan")
+            .eq("tier", "gold")
+            .send()
+            .await?;
+        check_eq(found, vec![client()])?;
+
+        let found = table
+            .query::<Client>()
+            .eq("home.town", "Sydney")
+            .send()
+            .await?;
+        check_eq(found, vec![client()])
+    })
+    .await
+}
@@ -0,0 +1,189 @@
+use cipherstash_dynamodb::{
+    crypto::{SealError, Unsealed},
+    traits::UnknownAttributes,
+    Decryptable, Encryptable, Identifiable, Nested, Searchable,
+};
+use common::{check_eq, check_err, fail_not_found, with_encrypted_table};
+
+mod common;
+
+/// The first version of the record type.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "user")]
+struct UserV1 {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "exact")]
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Nested)]
+struct Preferences {
+    #[cipherstash(default)]
+    theme: String,
+    #[cipherstash(plaintext, default = default_locale)]
+    locale: String,
+}
+
+/// The same type with fields added after records were written.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "user")]
+struct UserV2 {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "exact")]
+    name: String,
+    #[cipherstash(default)]
+    nickname: String,
+    #[cipherstash(default = default_login_count)]
+    login_count: i64,
+    #[cipherstash(plaintext, default = default_role)]
+    role: String,
+    #[cipherstash(nested)]
+    preferences: Preferences,
+}
+
+/// The same type with a field removed which rejects records that still have it.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "user", deny_unknown_attributes)]
+struct StrictUser {
+    #[partition_key]
+    email: String,
+}
+
+fn default_login_count() -> i64 {
+    1
+}
+
+fn default_role() -> String {
+    "member".to_string()
+}
+
+fn default_locale() -> String {
+    "en-AU".to_string()
+}
+
+fn user_v1() -> UserV1 {
+    UserV1 {
+        email: "dan@coderdan.co".to_string(),
+        name: "Dan Draper".to_string(),
+    }
+}
+
+fn upgraded_user() -> UserV2 {
+    UserV2 {
+        email: "dan@coderdan.co".to_string(),
+        name: "Dan Draper".to_string(),
+        nickname: String::new(),
+        login_count: 1,
+        role: "member".to_string(),
+        preferences: Preferences {
+            theme: String::new(),
+            locale: "en-AU".to_string(),
+        },
+    }
+}
+
+#[test]
+fn test_defaults_for_missing_attributes() -> Result<(), SealError> {
+    assert_eq!(
+        UserV2::from_unsealed(user_v1().into_unsealed())?,
+        upgraded_user()
+    );
+
+    let user = UserV2 {
+        nickname: "Dan".to_string(),
+        login_count: 7,
+        role: "admin".to_string(),
+        preferences: Preferences {
+            theme: "dark".to_string(),
+            locale: "en-GB".to_string(),
+        },
+        ..upgraded_user()
+    };
+
+    // Stored values are used when they are present
+    assert_eq!(UserV2::from_unsealed(user.clone().into_unsealed())?, user);
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_attributes_without_defaults() {
+    let mut unsealed = Unsealed::new_with_descriptor("user");
+    unsealed.add_protected("email", "dan@coderdan.co");
+
+    assert!(UserV1::from_unsealed(unsealed).is_err());
+}
+
+#[test]
+fn test_unknown_attributes_mode() {
+    assert_eq!(UserV1::unknown_attributes(), UnknownAttributes::Ignore);
+    assert_eq!(StrictUser::unknown_attributes(), UnknownAttributes::Deny);
+}
+
+#[tokio::test]
+async fn test_read_old_records_with_new_fields() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("schema-evolution-defaults", |table| async move {
+        table.put(user_v1()).await?;
+
+        let found: UserV2 = table
+            .get("dan@coderdan.co")
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found, upgraded_user())?;
+
+        let found = table
+            .query::<UserV2>()
+            .eq("name", "Dan Draper")
+            .send()
+            .await?;
+        check_eq(found, vec![upgraded_user()])?;
+
+        // Old and new records can be decrypted together
+        table
+            .put(UserV2 {
+                email: "jane@example.com".to_string(),
+                nickname: "Jane".to_string(),
+                ..upgraded_user()
+            })
+            .await?;
+
+        let found = table
+            .query::<UserV2>()
+            .eq("name", "Dan Draper")
+            .send()
+            .await?;
+        check_eq(found.len(), 2)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_removed_fields() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("schema-evolution-unknown", |table| async move {
+        table.put(upgraded_user()).await?;
+
+        // Attributes of fields that UserV1 doesn't have are ignored by default
+        let found: UserV1 = table
+            .get("dan@coderdan.co")
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found, user_v1())?;
+
+        check_err(table.get::<StrictUser>("dan@coderdan.co").await)?;
+
+        table
+            .put(StrictUser {
+                email: "jane@example.com".to_string(),
+            })
+            .await?;
+
+        let found: StrictUser = table
+            .get("jane@example.com")
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found.email, "jane@example.com")
+    })
+    .await
+}
@@ -0,0 +1,121 @@
+use cipherstash_dynamodb::{
+    crypto::{SealError, Unsealed},
+    traits::Upcast,
+    Decryptable, Encryptable, Identifiable, Searchable,
+};
+use common::{check_eq, fail_not_found, with_encrypted_table};
+
+mod common;
+
+/// The first version of the record type which didn't set a version.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "user")]
+struct UserV1 {
+    #[partition_key]
+    email: String,
+    name: String,
+}
+
+/// The current version of the record type which renamed `name` to `full_name`.
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(type_name = "user", version = 2, upcast, legacy_protected = "name")]
+struct User {
+    #[partition_key]
+    email: String,
+    #[cipherstash(query = "prefix")]
+    full_name: String,
+    #[cipherstash(plaintext)]
+    upgraded_from: Option<u32>,
+}
+
+impl Upcast for User {
+    fn upcast(version: u32, mut unsealed: Unsealed) -> Result<Unsealed, SealError> {
+        if let Some(name) = unsealed.take_protected("name") {
+            unsealed.add_protected("full_name", name);
+        }
+
+        unsealed.add_unprotected("upgraded_from", version);
+
+        Ok(unsealed)
+    }
+}
+
+fn user_v1() -> UserV1 {
+    UserV1 {
+        email: "dan@coderdan.co".to_string(),
+        name: "Dan Draper".to_string(),
+    }
+}
+
+fn user() -> User {
+    User {
+        email: "jane@example.com".to_string(),
+        full_name: "Jane Doe".to_string(),
+        upgraded_from: None,
+    }
+}
+
+#[test]
+fn test_schema_version() {
+    assert_eq!(<UserV1 as Encryptable>::schema_version(), 0);
+    assert_eq!(<User as Encryptable>::schema_version(), 2);
+    assert_eq!(<User as Decryptable>::schema_version(), 2);
+    assert_eq!(
+        <User as Decryptable>::legacy_protected_attributes().as_ref(),
+        ["name"]
+    );
+}
+
+#[test]
+fn test_upcast_unversioned_record() -> Result<(), SealError> {
+    let unsealed = user_v1().into_unsealed();
+    assert_eq!(unsealed.version(), 0);
+
+    assert_eq!(
+        unsealed.into_value::<User>()?,
+        User {
+            email: "dan@coderdan.co".to_string(),
+            full_name: "Dan Draper".to_string(),
+            upgraded_from: Some(0),
+        }
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_upcast_on_read() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("schema-version", |table| async move {
+        table.put(user_v1()).await?;
+        table.put(user()).await?;
+
+        let found: User = table
+            .get("dan@coderdan.co")
+            .await?
+            .ok_or(fail_not_found())?;
+
+        check_eq(
+            found,
+            User {
+                email: "dan@coderdan.co".to_string(),
+                full_name: "Dan Draper".to_string(),
+                upgraded_from: Some(0),
+            },
+        )?;
+
+        // Records stored with the current version aren't upcast
+        let found: User = table
+            .get("jane@example.com")
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found, user())?;
+
+        let found = table
+            .query::<User>()
+            .starts_with("full_name", "Jane")
+            .send()
+            .await?;
+        check_eq(found, vec![user()])
+    })
+    .await
+}
@@ -0,0 +1,93 @@
+use cipherstash_dynamodb::{
+    traits::{SerdeRecord, SerdeSchema},
+    Identifiable, SingleIndex,
+};
+use common::{check_eq, fail_not_found, with_encrypted_table};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+mod common;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Plan {
+    Free,
+    Paid { seats: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Address {
+    city: String,
+    postcode: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Identifiable)]
+#[cipherstash(sort_key_prefix = "account")]
+struct Account {
+    #[partition_key]
+    email: String,
+    name: String,
+    plan: Plan,
+    address: Address,
+    created_at: String,
+}
+
+impl SerdeRecord for Account {
+    fn schema() -> &'static SerdeSchema {
+        static SCHEMA: OnceLock<SerdeSchema> = OnceLock::new();
+
+        SCHEMA.get_or_init(|| {
+            SerdeSchema::new()
+                .query("email", SingleIndex::Exact)
+                .query("name", SingleIndex::Prefix)
+                .protected("plan")
+                .protected("address")
+                .plaintext("created_at")
+        })
+    }
+}
+
+fn account() -> Account {
+    Account {
+        email: "dan@coderdan.co".to_string(),
+        name: "Dan Draper".to_string(),
+        plan: Plan::Paid { seats: 5 },
+        address: Address {
+            city: "Sydney".to_string(),
+            postcode: None,
+        },
+        created_at: "2024-01-01".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_serde_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("serde-record", |table| async move {
+        let record = account();
+        table.put(record.clone()).await?;
+
+        let result: Account = table
+            .get("dan@coderdan.co")
+            .await?
+            .ok_or(fail_not_found())?;
+
+        check_eq(result, record)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_serde_query() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("serde-record", |table| async move {
+        let record = account();
+        table.put(record.clone()).await?;
+
+        let results = table
+            .query::<Account>()
+            .starts_with("name", "Dan")
+            .send()
+            .await?;
+
+        check_eq(results, vec![record])
+    })
+    .await
+}
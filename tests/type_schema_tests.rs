@@ -0,0 +1,135 @@
+use cipherstash_dynamodb::{
+    traits::{AttributeMode, KeySchema},
+    Decryptable, Encryptable, Identifiable, Searchable,
+};
+use serde_json::json;
+use std::borrow::Cow;
+
+#[derive(Debug, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(sort_key_prefix = "user")]
+struct User {
+    #[partition_key]
+    #[cipherstash(query = "exact", compound = "email#name")]
+    email: String,
+
+    #[sort_key]
+    #[cipherstash(plaintext)]
+    created_at: String,
+
+    #[cipherstash(query = "prefix", compound = "email#name")]
+    #[cipherstash(query = "prefix")]
+    name: String,
+
+    #[cipherstash(plaintext)]
+    tag: String,
+
+    #[cipherstash(skip)]
+    #[allow(dead_code)]
+    cached: Option<String>,
+}
+
+#[derive(Debug, Encryptable, Decryptable)]
+struct Manual {
+    name: String,
+}
+
+impl Identifiable for Manual {
+    type PrimaryKey = cipherstash_dynamodb::Pk;
+
+    fn get_primary_key(&self) -> Self::PrimaryKey {
+        cipherstash_dynamodb::Pk(self.name.clone())
+    }
+
+    fn type_name() -> Cow<'static, str> {
+        Cow::Borrowed("manual")
+    }
+
+    fn sort_key_prefix() -> Option<Cow<'static, str>> {
+        None
+    }
+}
+
+impl Searchable for Manual {}
+
+#[test]
+fn test_derived_type_schema() {
+    let schema = User::type_schema();
+
+    assert_eq!(schema.type_name, "user");
+    assert_eq!(
+        schema.partition_key,
+        KeySchema {
+            field: Some(Cow::Borrowed("email")),
+            encrypted: true,
+        }
+    );
+    assert_eq!(
+        schema.sort_key,
+        Some(KeySchema {
+            field: Some(Cow::Borrowed("created_at")),
+            encrypted: false,
+        })
+    );
+    assert_eq!(schema.skipped, vec![Cow::Borrowed("cached")]);
+
+    let tag = schema
+        .attributes
+        .iter()
+        .find(|attribute| attribute.name == "tag")
+        .expect("tag attribute");
+
+    assert_eq!(tag.mode, AttributeMode::Plaintext);
+}
+
+#[test]
+fn test_derived_type_schema_json() {
+    let value = serde_json::to_value(User::type_schema()).unwrap();
+
+    assert_eq!(
+        value,
+        json!({
+            "type_name": "user",
+            "sort_key_prefix": "user",
+            "partition_key": { "field": "email", "encrypted": true },
+            "sort_key": { "field": "created_at", "encrypted": false },
+            "attributes": [
+                { "name": "email", "mode": "protected" },
+                { "name": "name", "mode": "protected" },
+                { "name": "created_at", "mode": "plaintext" },
+                { "name": "tag", "mode": "plaintext" },
+            ],
+            "indexes": [
+                {
+                    "name": "email#name",
+                    "index_type": "exact:prefix",
+                    "components": [
+                        { "field": "email", "index_type": "exact" },
+                        { "field": "name", "index_type": "prefix" },
+                    ],
+                },
+                {
+                    "name": "name",
+                    "index_type": "prefix",
+                    "components": [{ "field": "name", "index_type": "prefix" }],
+                },
+            ],
+            "skipped": ["cached"],
+        })
+    );
+}
+
+#[test]
+fn test_manual_type_schema() {
+    let schema = Manual::type_schema();
+
+    assert_eq!(schema.type_name, "manual");
+    assert_eq!(
+        schema.partition_key,
+        KeySchema {
+            field: None,
+            encrypted: false,
+        }
+    );
+    assert_eq!(schema.sort_key, None);
+    assert!(schema.indexes.is_empty());
+}
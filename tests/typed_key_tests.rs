@@ -0,0 +1,123 @@
+use chrono::NaiveDate;
+use cipherstash_dynamodb::{
+    traits::{KeyPart, PrimaryKey, ReadConversionError},
+    Decryptable, Encryptable, Identifiable, Pk, PkSk, Searchable,
+};
+use common::{check_eq, check_none, fail_not_found, with_encrypted_table};
+
+mod common;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AccountId(u64);
+
+impl KeyPart for AccountId {
+    fn to_key_part(&self) -> String {
+        self.0.to_key_part()
+    }
+
+    fn from_key_part(value: &str) -> Result<Self, ReadConversionError> {
+        u64::from_key_part(value).map(Self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(typed_keys)]
+struct Statement {
+    #[partition_key]
+    account: i64,
+    #[sort_key]
+    #[cipherstash(plaintext)]
+    issued_on: NaiveDate,
+    #[cipherstash(query = "exact")]
+    status: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Identifiable, Encryptable, Decryptable, Searchable)]
+#[cipherstash(typed_keys)]
+struct Counter {
+    #[partition_key]
+    #[cipherstash(plaintext)]
+    id: u64,
+    count: i64,
+}
+
+fn statement(issued_on: NaiveDate) -> Statement {
+    Statement {
+        account: 42,
+        issued_on,
+        status: "paid".to_string(),
+    }
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn test_typed_primary_key() {
+    let PkSk(account, issued_on) = statement(date(2024, 3, 1)).get_primary_key();
+    assert_eq!(account, 42);
+    assert_eq!(issued_on, date(2024, 3, 1));
+
+    let parts = Counter { id: 7, count: 1 }
+        .get_primary_key()
+        .into_parts(&Counter::type_name(), None);
+    assert_eq!(parts.pk, "00000000000000000007");
+
+    let Pk(id) = Pk::<u64>::from_parts(&parts, None).unwrap();
+    assert_eq!(id, 7);
+}
+
+#[test]
+fn test_newtype_key_part() {
+    let parts = Pk(AccountId(9)).into_parts("account", None);
+    assert_eq!(parts.pk, "00000000000000000009");
+
+    let Pk(id) = Pk::<AccountId>::from_parts(&parts, None).unwrap();
+    assert_eq!(id, AccountId(9));
+}
+
+#[test]
+fn test_sort_keys_are_ordered() {
+    let sort_key = |issued_on| {
+        statement(issued_on)
+            .get_primary_key()
+            .into_parts(
+                &Statement::type_name(),
+                Statement::sort_key_prefix().as_deref(),
+            )
+            .sk
+    };
+
+    assert!(sort_key(date(2023, 12, 31)) < sort_key(date(2024, 1, 1)));
+    assert!(sort_key(date(2024, 2, 9)) < sort_key(date(2024, 2, 10)));
+}
+
+#[tokio::test]
+async fn test_get_with_typed_key() -> Result<(), Box<dyn std::error::Error>> {
+    with_encrypted_table("typed-keys", |table| async move {
+        table.put(statement(date(2024, 3, 1))).await?;
+        table.put(statement(date(2024, 4, 1))).await?;
+        table.put(Counter { id: 7, count: 3 }).await?;
+
+        let found: Statement = table
+            .get((42, date(2024, 3, 1)))
+            .await?
+            .ok_or(fail_not_found())?;
+        check_eq(found, statement(date(2024, 3, 1)))?;
+
+        let found: Counter = table.get(7u64).await?.ok_or(fail_not_found())?;
+        check_eq(found.count, 3)?;
+
+        table.delete::<Statement>((42, date(2024, 3, 1))).await?;
+        check_none(table.get::<Statement>((42, date(2024, 3, 1))).await?)?;
+
+        let found = table
+            .query::<Statement>()
+            .eq("status", "paid")
+            .send()
+            .await?;
+        check_eq(found, vec![statement(date(2024, 4, 1))])
+    })
+    .await
+}
@@ -0,0 +1,12 @@
+use cipherstash_dynamodb::Encryptable;
+
+#[derive(Encryptable)]
+#[cipherstash(sort_key = "year#month#day")]
+struct Event {
+    #[partition_key]
+    id: String,
+    year: u16,
+    month: u8,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use cipherstash_dynamodb::Decryptable;
+
+#[derive(Decryptable)]
+#[cipherstash(version = 2, legacy_protected = "name")]
+struct User {
+    #[partition_key]
+    email: String,
+    full_name: String,
+}
+
+fn main() {}
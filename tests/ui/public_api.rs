@@ -5,7 +5,7 @@ use cipherstash_dynamodb::{Decryptable, Encryptable, Searchable};
 
 // Errors
 use cipherstash_dynamodb::{
-    errors::{DeleteError, GetError, InitError, PutError, QueryError},
+    errors::{DeleteError, DynamoErrorKind, GetError, InitError, PutError, QueryError},
     Error,
 };
 